@@ -75,11 +75,6 @@ impl LocalRw for Assign {
 
 impl fmt::Display for Assign {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Formatter {
-            indentation_level: 0,
-            indentation_mode: Default::default(),
-            output: f,
-        }
-        .format_assign(self)
+        Formatter::new(f, Default::default()).format_assign(self)
     }
 }
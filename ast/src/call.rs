@@ -63,12 +63,7 @@ impl LocalRw for Call {
 
 impl fmt::Display for Call {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Formatter {
-            indentation_level: 0,
-            indentation_mode: Default::default(),
-            output: f,
-        }
-        .format_call(self)
+        Formatter::new(f, Default::default()).format_call(self)
     }
 }
 
@@ -127,11 +122,6 @@ impl LocalRw for MethodCall {
 
 impl fmt::Display for MethodCall {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Formatter {
-            indentation_level: 0,
-            indentation_mode: Default::default(),
-            output: f,
-        }
-        .format_method_call(self)
+        Formatter::new(f, Default::default()).format_method_call(self)
     }
 }
@@ -16,12 +16,65 @@ pub enum Upvalue {
     Ref(RcLocal),
 }
 
+/// Describes the bytecode function a `Function` was lifted from.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FunctionMetadata {
+    /// The index of the function prototype in the chunk, if the format has one.
+    pub id: Option<usize>,
+    pub line_defined: usize,
+    pub num_parameters: u8,
+    pub num_upvalues: u8,
+    pub max_stack_size: u8,
+    /// The number of instruction words.
+    pub bytecode_size: usize,
+}
+
+impl FunctionMetadata {
+    /// A single line summary, `name` being the function's debug name, which is escaped
+    /// like a string literal.
+    pub fn describe(&self, name: Option<&str>) -> String {
+        let mut description = "function".to_string();
+        if let Some(id) = self.id {
+            description += &format!(" {}", id);
+        }
+        if let Some(name) = name {
+            // the name comes from the bytecode, it could contain a newline and end the comment
+            description += &format!(
+                " \"{}\"",
+                Formatter::<String>::escape_string(name.as_bytes())
+            );
+        }
+        format!(
+            "{} (line {}): {} params, {} upvalues, max stack {}, {} instructions",
+            description,
+            self.line_defined,
+            self.num_parameters,
+            self.num_upvalues,
+            self.max_stack_size,
+            self.bytecode_size
+        )
+    }
+}
+
 #[derive(Default, Debug, PartialEq, Clone)]
 pub struct Function {
     pub name: Option<String>,
     pub parameters: Vec<RcLocal>,
     pub is_variadic: bool,
+    /// Set by `name_locals` when the first parameter is the `self` of a method definition,
+    /// so the function can be written as `function t:f()`.
+    pub is_method: bool,
     pub body: Block,
+    pub metadata: Option<FunctionMetadata>,
+}
+
+impl Function {
+    /// The header comment for this function, if it has metadata.
+    pub fn header(&self) -> Option<String> {
+        self.metadata
+            .as_ref()
+            .map(|metadata| metadata.describe(self.name.as_deref()))
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -56,12 +109,7 @@ impl Infer for Closure {
 
 impl fmt::Display for Closure {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Formatter {
-            indentation_level: 0,
-            indentation_mode: Default::default(),
-            output: f,
-        }
-        .format_closure(self)
+        Formatter::new(f, Default::default()).format_closure(self)
     }
 }
 
@@ -8,15 +8,22 @@ use std::{
 use itertools::Itertools;
 
 use crate::{
-    Assign, Binary, BinaryOperation, Block, Call, Closure, GenericFor, If, Index, LValue, Literal,
-    MethodCall, NumericFor, RValue, Repeat, Return, Select, Statement, Table, Unary, While,
+    Assign, Binary, BinaryOperation, Block, Call, Closure, Do, GenericFor, Global, If, Index,
+    LValue, Literal, MethodCall, NumericFor, RValue, Repeat, Return, Select, Statement, Table,
+    Unary, While,
 };
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum IndentationMode {
     Spaces(u8),
     Tab,
 }
 
+// how many columns a tab counts as when measuring line width
+const TAB_WIDTH: usize = 4;
+
 impl IndentationMode {
     pub fn display(&self, out: &mut impl fmt::Write, indentation_level: usize) -> fmt::Result {
         let string = match self {
@@ -42,6 +49,108 @@ impl Default for IndentationMode {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum QuoteStyle {
+    #[default]
+    Double,
+    Single,
+}
+
+impl QuoteStyle {
+    fn quote(self) -> char {
+        match self {
+            Self::Double => '"',
+            Self::Single => '\'',
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum TableSeparator {
+    #[default]
+    Comma,
+    Semicolon,
+}
+
+impl TableSeparator {
+    fn separator(self) -> char {
+        match self {
+            Self::Comma => ',',
+            Self::Semicolon => ';',
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum SemicolonPolicy {
+    /// Only where the next statement would otherwise be parsed as part of this one.
+    #[default]
+    WhenAmbiguous,
+    Always,
+}
+
+/// The Lua version the output is meant to run on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Dialect {
+    #[default]
+    Lua51,
+    Luau,
+    /// Lua 5.2 and later, which replaced function environments with `_ENV`.
+    Lua52,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct FormatOptions {
+    /// Decides how globals whose names aren't identifiers are accessed.
+    pub dialect: Dialect,
+    pub indentation_mode: IndentationMode,
+    /// Argument lists and table constructors that would make a line longer than this
+    /// are split over multiple lines. `None` never splits them.
+    pub max_line_width: Option<usize>,
+    pub quote_style: QuoteStyle,
+    pub table_separator: TableSeparator,
+    /// Whether the last item of a multi-line table constructor gets a separator too.
+    pub trailing_separator: bool,
+    pub semicolons: SemicolonPolicy,
+    /// Number of empty lines around function declarations in the main block.
+    pub blank_lines_between_functions: usize,
+    /// Starts each function with a comment describing the bytecode it was lifted from.
+    pub function_headers: bool,
+}
+
+fn display_width(string: &str) -> usize {
+    string
+        .chars()
+        .map(|c| if c == '\t' { TAB_WIDTH } else { 1 })
+        .sum()
+}
+
+/// Keeps track of the column the next character will be written at.
+pub(crate) struct Output<'a, W: fmt::Write> {
+    inner: &'a mut W,
+    column: usize,
+}
+
+impl<'a, W: fmt::Write> fmt::Write for Output<'a, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        match s.rfind('\n') {
+            Some(newline) => self.column = display_width(&s[newline + 1..]),
+            None => self.column += display_width(s),
+        }
+        self.inner.write_str(s)
+    }
+}
+
 pub(crate) fn format_arg_list(list: &[RValue]) -> String {
     let mut s = String::new();
     for (index, rvalue) in list.iter().enumerate() {
@@ -60,29 +169,89 @@ pub(crate) fn format_arg_list(list: &[RValue]) -> String {
 
 pub struct Formatter<'a, W: fmt::Write> {
     pub(crate) indentation_level: usize,
-    pub(crate) indentation_mode: IndentationMode,
-    pub(crate) output: &'a mut W,
+    pub(crate) options: FormatOptions,
+    pub(crate) output: Output<'a, W>,
 }
 
-impl<'a, W: fmt::Write> Formatter<'a, W> {
-    pub fn format(
+impl Formatter<'_, String> {
+    /// Formats the top-level statement `index` of `main` along with the line breaks before it,
+    /// so that a block can be formatted a statement at a time as each one is ready. The parts
+    /// add up to what `format` would have written.
+    pub fn format_top_level_statement(
         main: &Block,
-        output: &'a mut W,
-        indentation_mode: IndentationMode,
-    ) -> fmt::Result {
-        let mut formatter = Self {
+        index: usize,
+        options: FormatOptions,
+    ) -> Result<String, fmt::Error> {
+        let mut output = String::new();
+        Formatter::new(&mut output, options).format_statement_in(main, index)?;
+        Ok(output)
+    }
+}
+
+impl<'a, W: fmt::Write> Formatter<'a, W> {
+    pub(crate) fn new(output: &'a mut W, options: FormatOptions) -> Self {
+        Self {
             indentation_level: 0,
-            indentation_mode,
-            output,
-        };
-        formatter.format_block_no_indent(main)
+            options,
+            output: Output {
+                inner: output,
+                column: 0,
+            },
+        }
+    }
+
+    pub fn format(main: &Block, output: &'a mut W, options: FormatOptions) -> fmt::Result {
+        Self::new(output, options).format_block_no_indent(main)
     }
 
     fn indent(&mut self) -> fmt::Result {
-        self.indentation_mode
+        self.options
+            .indentation_mode
             .display(&mut self.output, self.indentation_level)
     }
 
+    /// Whether something rendered on a single line fits in `max_line_width`, leaving room for
+    /// `trailing` more characters. Something that spans multiple lines anyway, like a callback,
+    /// fits as long as its first and last lines do, since its body is wrapped on its own. It is
+    /// measured without a width, so that nested calls are rendered once each level instead of
+    /// once for each way of laying out every level above them.
+    fn fits(
+        &self,
+        trailing: usize,
+        render: impl Fn(&mut Formatter<String>) -> fmt::Result,
+    ) -> Result<bool, fmt::Error> {
+        let Some(max_line_width) = self.options.max_line_width else {
+            return Ok(true);
+        };
+        let mut rendered = String::new();
+        let mut formatter = Formatter::new(
+            &mut rendered,
+            FormatOptions {
+                max_line_width: None,
+                ..self.options.clone()
+            },
+        );
+        formatter.indentation_level = self.indentation_level;
+        formatter.output.column = self.output.column;
+        render(&mut formatter)?;
+        let first_line = rendered.split('\n').next().unwrap();
+        let last_line = rendered.rsplit('\n').next().unwrap();
+        Ok(if first_line.len() == rendered.len() {
+            self.output.column + display_width(&rendered) + trailing <= max_line_width
+        } else {
+            self.output.column + display_width(first_line) <= max_line_width
+                && display_width(last_line) + trailing <= max_line_width
+        })
+    }
+
+    fn is_function_declaration(statement: &Statement) -> bool {
+        matches!(
+            statement,
+            Statement::Assign(Assign { left, right, .. })
+                if left.len() == 1 && matches!(right.as_slice(), [RValue::Closure(_)])
+        )
+    }
+
     // (function() end)()
     // (function() end)[1]
     fn should_wrap_left_rvalue(value: &RValue) -> bool {
@@ -103,64 +272,80 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
     }
 
     fn format_block_no_indent(&mut self, block: &Block) -> fmt::Result {
-        for (i, statement) in block.iter().enumerate() {
-            if i != 0 {
-                writeln!(self.output)?;
-            }
-            self.format_statement(statement)?;
-            if let Some(next_statement) =
-                block.iter().skip(i + 1).find(|s| s.as_comment().is_none())
+        (0..block.len()).try_for_each(|i| self.format_statement_in(block, i))
+    }
+
+    // the statement at `i` in `block`, the line breaks before it and the semicolon after it
+    fn format_statement_in(&mut self, block: &Block, i: usize) -> fmt::Result {
+        let statement = &block[i];
+        if i != 0 {
+            writeln!(self.output)?;
+            if self.indentation_level == 0
+                && (Self::is_function_declaration(statement)
+                    || Self::is_function_declaration(&block[i - 1]))
             {
-                fn is_ambiguous(r: &RValue) -> bool {
-                    match r {
-                        RValue::Local(_)
-                        | RValue::Global(_)
-                        | RValue::Index(_)
-                        | RValue::Call(_)
-                        | RValue::MethodCall(_)
-                        | RValue::Select(Select::Call(_) | Select::MethodCall(_)) => true,
-                        RValue::Binary(binary) => is_ambiguous(&binary.right),
-                        _ => false,
-                    }
+                for _ in 0..self.options.blank_lines_between_functions {
+                    writeln!(self.output)?;
                 }
+            }
+        }
+        self.format_statement(statement)?;
+        if self.options.semicolons == SemicolonPolicy::Always
+            && !matches!(statement, Statement::Comment(_) | Statement::Empty(_))
+        {
+            write!(self.output, ";")?;
+        } else if let Some(next_statement) =
+            block.iter().skip(i + 1).find(|s| s.as_comment().is_none())
+        {
+            fn is_ambiguous(r: &RValue) -> bool {
+                match r {
+                    RValue::Local(_)
+                    | RValue::Global(_)
+                    | RValue::Index(_)
+                    | RValue::Call(_)
+                    | RValue::MethodCall(_)
+                    | RValue::Select(Select::Call(_) | Select::MethodCall(_)) => true,
+                    RValue::Binary(binary) => is_ambiguous(&binary.right),
+                    _ => false,
+                }
+            }
 
-                let disambiguate = match statement {
-                    Statement::Call(_) | Statement::MethodCall(_) => true,
-                    Statement::Repeat(repeat) => is_ambiguous(&repeat.condition),
-                    Statement::Assign(Assign { right: list, .. })
-                    | Statement::Return(Return { values: list }) => {
-                        if let Some(last) = list.last() {
-                            is_ambiguous(last)
+            let disambiguate = match statement {
+                Statement::Call(_) | Statement::MethodCall(_) => true,
+                Statement::Repeat(repeat) => is_ambiguous(&repeat.condition),
+                Statement::Assign(Assign { right: list, .. })
+                | Statement::Return(Return { values: list }) => {
+                    if let Some(last) = list.last() {
+                        is_ambiguous(last)
+                    } else {
+                        false
+                    }
+                }
+                Statement::Goto(_) | Statement::Continue(_) | Statement::Break(_) => true,
+                _ => false,
+            };
+            let disambiguate = disambiguate
+                && match next_statement {
+                    Statement::Assign(Assign {
+                        left,
+                        prefix: false,
+                        ..
+                    }) => {
+                        if let Some(index) = left[0].as_index() {
+                            Self::should_wrap_left_rvalue(&index.left)
                         } else {
                             false
                         }
                     }
-                    Statement::Goto(_) | Statement::Continue(_) | Statement::Break(_) => true,
+                    Statement::Call(Call { value, .. })
+                    | Statement::MethodCall(MethodCall { value, .. }) => {
+                        Self::should_wrap_left_rvalue(value)
+                    }
+                    Statement::Comment(_) => unimplemented!(),
                     _ => false,
                 };
-                let disambiguate = disambiguate
-                    && match next_statement {
-                        Statement::Assign(Assign {
-                            left,
-                            prefix: false,
-                            ..
-                        }) => {
-                            if let Some(index) = left[0].as_index() {
-                                Self::should_wrap_left_rvalue(&index.left)
-                            } else {
-                                false
-                            }
-                        }
-                        Statement::Call(Call { value, .. })
-                        | Statement::MethodCall(MethodCall { value, .. }) => {
-                            Self::should_wrap_left_rvalue(value)
-                        }
-                        Statement::Comment(_) => unimplemented!(),
-                        _ => false,
-                    };
-                if disambiguate {
-                    write!(self.output, ";")?;
-                }
+            if disambiguate {
+                write!(self.output, ";")?;
             }
         }
         Ok(())
@@ -168,6 +353,7 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
 
     fn format_lvalue(&mut self, lvalue: &LValue) -> fmt::Result {
         match lvalue {
+            LValue::Global(global) => self.format_global(global),
             LValue::Index(index) => self.format_index(index),
             _ => write!(self.output, "{}", lvalue),
         }
@@ -200,9 +386,19 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
 
     pub(crate) fn format_table(&mut self, table: &Table) -> fmt::Result {
         let sequential_keys = Self::are_table_keys_sequential(table);
-        let should_space = !table.0.is_empty();
         let should_format = !table.0.is_empty() && (!sequential_keys || table.0.len() > 3)
             || Self::contains_table(table);
+        if should_format || self.options.max_line_width.is_none() {
+            return self.format_table_with(table, should_format);
+        }
+        let fits = self.fits(0, |f| f.format_table_with(table, false))?;
+        self.format_table_with(table, !fits)
+    }
+
+    fn format_table_with(&mut self, table: &Table, should_format: bool) -> fmt::Result {
+        let sequential_keys = Self::are_table_keys_sequential(table);
+        let should_space = !table.0.is_empty();
+        let separator = self.options.table_separator.separator();
         write!(self.output, "{{")?;
         if should_format {
             writeln!(self.output)?;
@@ -224,6 +420,9 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
                 if wrap {
                     write!(self.output, ")")?;
                 }
+                if should_format && self.options.trailing_separator {
+                    write!(self.output, "{}", separator)?;
+                }
             } else {
                 if !sequential_keys {
                     if let Some(key) = key {
@@ -233,8 +432,10 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
                     }
                 }
                 self.format_rvalue(value)?;
+                if !is_last || (should_format && self.options.trailing_separator) {
+                    write!(self.output, "{}", separator)?;
+                }
                 if !is_last {
-                    write!(self.output, ",")?;
                     write!(self.output, "{}", if should_format { "\n" } else { " " })?;
                 }
             }
@@ -279,7 +480,7 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
         parentheses(self, binary.right_group(), &binary.right)
     }
 
-    fn format_closure_parameters(&mut self, closure: &Closure) -> fmt::Result {
+    fn format_closure_parameters(&mut self, closure: &Closure, skip: usize) -> fmt::Result {
         let function = closure.function.lock();
         write!(
             self.output,
@@ -288,28 +489,30 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
                 function
                     .parameters
                     .iter()
+                    .skip(skip)
                     .map(|x| x.to_string())
                     .chain(std::iter::once("...".into()))
                     .join(", ")
             } else {
-                function.parameters.iter().join(", ")
+                function.parameters.iter().skip(skip).join(", ")
             }
         )
     }
 
     fn format_closure_body(&mut self, closure: &Closure) -> fmt::Result {
         let function = closure.function.lock();
-        if !function.body.is_empty() {
+        let header = self
+            .options
+            .function_headers
+            .then(|| function.header())
+            .flatten();
+        if !function.body.is_empty() || header.is_some() {
             writeln!(self.output)?;
             self.indentation_level += 1;
-            // if closure.name.is_some() {
-            //     self.indent()?;
-            //     writeln!(self.output, "-- function name: {}", closure.name.as_ref().unwrap())?;
-            // }
-            // if closure.line_defined.is_some() {
-            //     self.indent()?;
-            //     writeln!(self.output, "-- line defined: {}", closure.line_defined.as_ref().unwrap())?;
-            // }
+            if let Some(header) = header {
+                self.indent()?;
+                writeln!(self.output, "-- {}", header)?;
+            }
             if !closure.upvalues.is_empty() {
                 self.indent()?;
                 write!(self.output, "-- upvalues: ")?;
@@ -331,8 +534,10 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
             }
             self.indentation_level -= 1;
 
-            self.format_block(&function.body)?;
-            writeln!(self.output)?;
+            if !function.body.is_empty() {
+                self.format_block(&function.body)?;
+                writeln!(self.output)?;
+            }
             self.indent()
         } else {
             write!(self.output, " ")
@@ -341,15 +546,29 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
 
     pub(crate) fn format_closure(&mut self, closure: &Closure) -> fmt::Result {
         write!(self.output, "function(")?;
-        self.format_closure_parameters(closure)?;
+        self.format_closure_parameters(closure, 0)?;
         write!(self.output, ")")?;
         self.format_closure_body(closure)?;
         write!(self.output, "end")
     }
 
     fn format_named_function(&mut self, name: &LValue, closure: &Closure) -> fmt::Result {
-        write!(self.output, "function {}(", name)?;
-        self.format_closure_parameters(closure)?;
+        let is_method = closure.function.lock().is_method;
+        // `function t:f()` declares the `self` parameter implicitly
+        if let LValue::Index(index) = name
+            && is_method
+        {
+            write!(self.output, "function ")?;
+            self.format_rvalue(&index.left)?;
+            let RValue::Literal(Literal::String(key)) = index.right.as_ref() else {
+                unreachable!()
+            };
+            write!(self.output, ":{}(", std::str::from_utf8(key).unwrap())?;
+            self.format_closure_parameters(closure, 1)?;
+        } else {
+            write!(self.output, "function {}(", name)?;
+            self.format_closure_parameters(closure, 0)?;
+        }
         write!(self.output, ")")?;
         self.format_closure_body(closure)?;
         write!(self.output, "end")
@@ -366,6 +585,17 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
             RValue::Unary(unary) => self.format_unary(unary),
             RValue::Binary(binary) => self.format_binary(binary),
             RValue::Closure(closure) => self.format_closure(closure),
+            RValue::Global(global) => self.format_global(global),
+            RValue::Literal(Literal::String(string)) => {
+                let quote = self.options.quote_style.quote();
+                write!(
+                    self.output,
+                    "{}{}{}",
+                    quote,
+                    Self::escape_string(string),
+                    quote
+                )
+            }
             RValue::Literal(Literal::Number(n)) if n.is_infinite() => {
                 // TODO: only insert parentheses when necessary
                 write!(self.output, "(")?;
@@ -393,7 +623,23 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
     }
 
     fn format_arg_list(&mut self, list: &[RValue]) -> fmt::Result {
+        if list.is_empty() || self.options.max_line_width.is_none() {
+            return self.format_arg_list_with(list, false);
+        }
+        // leave room for the closing parenthesis
+        let fits = self.fits(1, |f| f.format_arg_list_with(list, false))?;
+        self.format_arg_list_with(list, !fits)
+    }
+
+    fn format_arg_list_with(&mut self, list: &[RValue], multi_line: bool) -> fmt::Result {
+        if multi_line {
+            writeln!(self.output)?;
+            self.indentation_level += 1;
+        }
         for (index, rvalue) in list.iter().enumerate() {
+            if multi_line {
+                self.indent()?;
+            }
             if index + 1 == list.len() {
                 let wrap = matches!(rvalue, RValue::Select(_));
                 if wrap {
@@ -405,9 +651,19 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
                 }
             } else {
                 self.format_rvalue(rvalue)?;
-                write!(self.output, ", ")?;
+                write!(self.output, ",")?;
+                if multi_line {
+                    writeln!(self.output)?;
+                } else {
+                    write!(self.output, " ")?;
+                }
             }
         }
+        if multi_line {
+            writeln!(self.output)?;
+            self.indentation_level -= 1;
+            self.indent()?;
+        }
         Ok(())
     }
     pub(crate) fn is_valid_name(name: &[u8]) -> bool {
@@ -480,6 +736,25 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
         }
     }
 
+    pub(crate) fn format_global(&mut self, global: &Global) -> fmt::Result {
+        if Self::is_valid_name(&global.0) {
+            return write!(self.output, "{}", std::str::from_utf8(&global.0).unwrap());
+        }
+        let environment = match self.options.dialect {
+            Dialect::Lua51 | Dialect::Luau => "getfenv()",
+            Dialect::Lua52 => "_ENV",
+        };
+        let quote = self.options.quote_style.quote();
+        write!(
+            self.output,
+            "{}[{}{}{}]",
+            environment,
+            quote,
+            Self::escape_string(&global.0),
+            quote
+        )
+    }
+
     pub(crate) fn format_index(&mut self, index: &Index) -> fmt::Result {
         let wrap = Self::should_wrap_left_rvalue(&index.left);
         if wrap {
@@ -573,32 +848,37 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
             && let RValue::Closure(closure) = &assign.right[0]
         {
             let left = &assign.left[0];
-            if assign.prefix || left.as_global().is_some() || {
-                if let LValue::Index(ref index) = left {
-                    let mut index = index;
-                    let mut valid = true;
-                    loop {
-                        if let box RValue::Literal(Literal::String(ref key)) = &index.right
-                            && Self::is_valid_name(key)
-                        {
-                            match index.left {
-                                box RValue::Index(ref i) => {
-                                    index = i;
-                                    continue;
+            if assign.prefix
+                || left
+                    .as_global()
+                    .is_some_and(|global| Self::is_valid_name(&global.0))
+                || {
+                    if let LValue::Index(ref index) = left {
+                        let mut index = index;
+                        let mut valid = true;
+                        loop {
+                            if let box RValue::Literal(Literal::String(ref key)) = &index.right
+                                && Self::is_valid_name(key)
+                            {
+                                match index.left {
+                                    box RValue::Index(ref i) => {
+                                        index = i;
+                                        continue;
+                                    }
+                                    box RValue::Global(_) | box RValue::Local(_) => {}
+                                    _ => valid = false,
                                 }
-                                box RValue::Global(_) | box RValue::Local(_) => {}
-                                _ => valid = false,
+                            } else {
+                                valid = false;
                             }
-                        } else {
-                            valid = false;
+                            break;
                         }
-                        break;
+                        valid
+                    } else {
+                        false
                     }
-                    valid
-                } else {
-                    false
                 }
-            } {
+            {
                 return self.format_named_function(left, closure);
             }
         }
@@ -644,6 +924,14 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
         write!(self.output, "end")
     }
 
+    pub(crate) fn format_do(&mut self, r#do: &Do) -> fmt::Result {
+        writeln!(self.output, "do")?;
+        self.format_block(&r#do.block.lock())?;
+        writeln!(self.output)?;
+        self.indent()?;
+        write!(self.output, "end")
+    }
+
     pub(crate) fn format_repeat(&mut self, r#repeat: &Repeat) -> fmt::Result {
         writeln!(self.output, "repeat")?;
         self.format_block(&repeat.block.lock())?;
@@ -728,6 +1016,7 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
             Statement::If(r#if) => self.format_if(r#if),
             Statement::While(r#while) => self.format_while(r#while),
             Statement::Repeat(repeat) => self.format_repeat(repeat),
+            Statement::Do(r#do) => self.format_do(r#do),
             Statement::NumericFor(numeric_for) => self.format_numeric_for(numeric_for),
             Statement::GenericFor(generic_for) => self.format_generic_for(generic_for),
             Statement::Call(call) => self.format_call(call),
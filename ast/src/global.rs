@@ -30,14 +30,6 @@ impl<'a> From<&'a str> for Global {
 
 impl fmt::Display for Global {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        if Formatter::<fmt::Formatter>::is_valid_name(&self.0) {
-            write!(f, "{}", std::str::from_utf8(&self.0).unwrap())
-        } else {
-            write!(
-                f,
-                "__FENV[\"{}\"]",
-                Formatter::<fmt::Formatter>::escape_string(&self.0)
-            )
-        }
+        Formatter::new(f, Default::default()).format_global(self)
     }
 }
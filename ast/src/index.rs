@@ -51,11 +51,6 @@ impl Traverse for Index {
 
 impl fmt::Display for Index {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Formatter {
-            indentation_level: 0,
-            indentation_mode: Default::default(),
-            output: f,
-        }
-        .format_index(self)
+        Formatter::new(f, Default::default()).format_index(self)
     }
 }
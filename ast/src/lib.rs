@@ -19,6 +19,7 @@ mod call;
 mod close;
 mod closure;
 mod r#continue;
+mod r#do;
 mod r#for;
 pub mod formatter;
 mod global;
@@ -32,6 +33,7 @@ pub mod local_declarations;
 pub mod name_locals;
 mod repeat;
 pub mod replace_locals;
+pub mod report;
 mod r#return;
 mod set_list;
 mod side_effects;
@@ -54,6 +56,7 @@ pub use literal::*;
 pub use local::*;
 pub use r#break::*;
 pub use r#continue::*;
+pub use r#do::*;
 pub use r#for::*;
 pub use r#if::*;
 pub use r#return::*;
@@ -270,6 +273,7 @@ pub enum Statement {
     Label(Label),
     While(While),
     Repeat(Repeat),
+    Do(Do),
     NumForInit(NumForInit),
     NumForNext(NumForNext),
     NumericFor(NumericFor),
@@ -301,7 +305,16 @@ impl fmt::Display for Empty {
 
 impl fmt::Display for Comment {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "-- {}", self.text)
+        if self.text.contains('\n') {
+            // a long comment with a level that the text can't close
+            let level = (0..)
+                .map(|level| "=".repeat(level))
+                .find(|level| !self.text.contains(&format!("]{}]", level)))
+                .unwrap();
+            write!(f, "--[{}[\n{}\n]{}]", level, self.text, level)
+        } else {
+            write!(f, "-- {}", self.text)
+        }
     }
 }
 
@@ -318,6 +331,7 @@ impl fmt::Display for Statement {
             Statement::Label(label) => write!(f, "{}", label),
             Statement::While(while_) => write!(f, "{}", while_),
             Statement::Repeat(repeat) => write!(f, "{}", repeat),
+            Statement::Do(r#do) => write!(f, "{}", r#do),
             Statement::NumForInit(num_for_init) => write!(f, "{}", num_for_init),
             Statement::NumForNext(num_for_next) => write!(f, "{}", num_for_next),
             Statement::NumericFor(numeric_for) => write!(f, "{}", numeric_for),
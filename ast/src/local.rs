@@ -2,11 +2,13 @@ use crate::{type_system::Infer, SideEffects, Traverse, Type, TypeSystem};
 use by_address::ByAddress;
 use derive_more::From;
 use enum_dispatch::enum_dispatch;
-use nohash_hasher::NoHashHasher;
-use parking_lot::Mutex;
+use parking_lot::{Mutex, MutexGuard};
 use std::{
+    cell::Cell,
+    cmp::Ordering,
     fmt::{self, Display},
     hash::{Hash, Hasher},
+    sync::atomic::{self, AtomicUsize},
 };
 use triomphe::Arc;
 
@@ -28,8 +30,111 @@ impl fmt::Display for Local {
     }
 }
 
-#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct RcLocal(pub ByAddress<Arc<Mutex<Local>>>);
+thread_local! {
+    // the function whose locals are being numbered and the number of the next one
+    static NEXT_LOCAL_ID: Cell<Option<(usize, usize)>> = const { Cell::new(None) };
+}
+
+// locals created outside of `with_local_ids` are numbered in one sequence for all threads
+static NEXT_UNSCOPED_LOCAL_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// The function whose locals are being numbered and where its numbering continues from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalIds {
+    function_id: usize,
+    next: usize,
+}
+
+impl LocalIds {
+    pub fn new(function_id: usize) -> Self {
+        Self {
+            function_id,
+            next: 0,
+        }
+    }
+}
+
+/// Numbers the locals created by `f` on this thread `(function_id, n)`, continuing from where
+/// the last call with `ids` stopped. Locals are hashed and ordered by their number, so as long as
+/// `f` creates them in the same order, hash map iteration and thus the output doesn't depend on
+/// where they were allocated or on what other threads do. Each function that is decompiled
+/// needs its own id so that no two locals share a number.
+pub fn with_local_ids<R>(ids: &mut LocalIds, f: impl FnOnce() -> R) -> R {
+    // saves where the numbering stopped and restores the outer one, even if `f` panics
+    struct Restore<'a> {
+        ids: &'a mut LocalIds,
+        outer: Option<(usize, usize)>,
+    }
+
+    impl Drop for Restore<'_> {
+        fn drop(&mut self) {
+            let (_, next) = NEXT_LOCAL_ID.replace(self.outer).unwrap();
+            self.ids.next = next;
+        }
+    }
+
+    let outer = NEXT_LOCAL_ID.replace(Some((ids.function_id, ids.next)));
+    let _restore = Restore { ids, outer };
+    f()
+}
+
+/// A `Local` along with the number it was created with. Identity is still the address, but
+/// no two locals share a number.
+#[derive(Debug)]
+pub struct LocalCell {
+    id: (usize, usize),
+    local: Mutex<Local>,
+}
+
+impl LocalCell {
+    fn new(local: Local) -> Self {
+        let id = match NEXT_LOCAL_ID.get() {
+            Some((function_id, next)) => {
+                NEXT_LOCAL_ID.set(Some((function_id, next + 1)));
+                (function_id, next)
+            }
+            None => (
+                usize::MAX,
+                NEXT_UNSCOPED_LOCAL_ID.fetch_add(1, atomic::Ordering::Relaxed),
+            ),
+        };
+        Self {
+            id,
+            local: Mutex::new(local),
+        }
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, Local> {
+        self.local.lock()
+    }
+}
+
+impl Default for LocalCell {
+    fn default() -> Self {
+        Self::new(Local::default())
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RcLocal(pub ByAddress<Arc<LocalCell>>);
+
+impl Hash for RcLocal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.id.hash(state);
+    }
+}
+
+impl PartialOrd for RcLocal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for RcLocal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.id.cmp(&other.0.id)
+    }
+}
 
 impl Infer for RcLocal {
     fn infer<'a: 'b, 'b>(&'a mut self, system: &mut TypeSystem<'b>) -> Type {
@@ -41,11 +146,7 @@ impl Display for RcLocal {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match &self.0 .0.lock().0 {
             Some(name) => write!(f, "{}", name),
-            None => {
-                let mut hasher = NoHashHasher::<u8>::default();
-                self.hash(&mut hasher);
-                write!(f, "UNNAMED_{}", hasher.finish())
-            }
+            None => write!(f, "UNNAMED_{}", self.0.id.1),
         }
     }
 }
@@ -56,7 +157,7 @@ impl Traverse for RcLocal {}
 
 impl RcLocal {
     pub fn new(local: Local) -> Self {
-        Self(ByAddress(Arc::new(Mutex::new(local))))
+        Self(ByAddress(Arc::new(LocalCell::new(local))))
     }
 }
 
@@ -13,7 +13,7 @@ use petgraph::{
 use rustc_hash::{FxHashMap, FxHashSet};
 use triomphe::Arc;
 
-use crate::{Assign, Block, LocalRw, RcLocal, Statement};
+use crate::{Assign, Block, Do, LocalRw, RValue, RcLocal, Statement, Traverse, Upvalue};
 
 #[derive(Default)]
 pub struct LocalDeclarer {
@@ -58,6 +58,10 @@ impl LocalDeclarer {
                     let child = self.visit(r#repeat.block.clone(), stat_index);
                     self.graph.add_edge(node, child, ());
                 }
+                Statement::Do(r#do) => {
+                    let child = self.visit(r#do.block.clone(), stat_index);
+                    self.graph.add_edge(node, child, ());
+                }
                 Statement::NumericFor(numeric_for) => {
                     let child = self.visit(r#numeric_for.block.clone(), stat_index);
                     self.graph.add_edge(node, child, ());
@@ -77,7 +81,7 @@ impl LocalDeclarer {
         root_block: Arc<Mutex<Block>>,
         locals_to_ignore: &FxHashSet<RcLocal>,
     ) {
-        let root_node = self.visit(root_block, 0);
+        let root_node = self.visit(root_block.clone(), 0);
         let dominators = simple_fast(&self.graph, root_node);
         for (local, usages) in self.local_usages {
             if locals_to_ignore.contains(&local) {
@@ -171,5 +175,222 @@ impl LocalDeclarer {
                 }
             }
         }
+
+        scope_conflicts(&mut root_block.lock());
+        limit_scopes(&mut root_block.lock(), 0);
+    }
+}
+
+// Lua and Luau refuse to compile a function with more than 200 locals in scope at once
+const MAX_LOCALS: usize = 200;
+
+fn declared_locals(statement: &Statement) -> Vec<&RcLocal> {
+    match statement {
+        Statement::Assign(assign) if assign.prefix => {
+            assign.left.iter().filter_map(|l| l.as_local()).collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn nested_blocks(statement: &Statement) -> Vec<Arc<Mutex<Block>>> {
+    match statement {
+        Statement::If(r#if) => vec![r#if.then_block.clone(), r#if.else_block.clone()],
+        Statement::While(r#while) => vec![r#while.block.clone()],
+        Statement::Repeat(repeat) => vec![repeat.block.clone()],
+        Statement::NumericFor(numeric_for) => vec![numeric_for.block.clone()],
+        Statement::GenericFor(generic_for) => vec![generic_for.block.clone()],
+        Statement::Do(r#do) => vec![r#do.block.clone()],
+        _ => Vec::new(),
+    }
+}
+
+fn referenced_locals(statement: &Statement, locals: &mut FxHashSet<RcLocal>) {
+    locals.extend(statement.values().into_iter().cloned());
+    for block in nested_blocks(statement) {
+        for statement in block.lock().iter() {
+            referenced_locals(statement, locals);
+        }
+    }
+}
+
+// the index of the last statement that references a local declared by each statement
+fn reaches(block: &Block) -> Vec<usize> {
+    let mut last_use = FxHashMap::default();
+    for (stat_index, statement) in block.iter().enumerate() {
+        let mut locals = FxHashSet::default();
+        referenced_locals(statement, &mut locals);
+        for local in locals {
+            last_use.insert(local, stat_index);
+        }
+    }
+    block
+        .iter()
+        .enumerate()
+        .map(|(stat_index, statement)| {
+            declared_locals(statement)
+                .into_iter()
+                .filter_map(|local| last_use.get(local).copied())
+                .max()
+                .unwrap_or(stat_index)
+        })
+        .collect()
+}
+
+// the end of the smallest range of statements from `start` that no local declared in it outlives
+fn range_end(reach: &[usize], start: usize) -> usize {
+    let mut end = reach[start];
+    let mut stat_index = start;
+    while stat_index < end {
+        stat_index += 1;
+        end = end.max(reach[stat_index]);
+    }
+    end
+}
+
+fn captured_by_reference(statement: &mut Statement, locals: &mut FxHashSet<RcLocal>) {
+    statement.traverse_rvalues(&mut |rvalue| {
+        if let RValue::Closure(closure) = rvalue {
+            locals.extend(closure.upvalues.iter().filter_map(|upvalue| match upvalue {
+                Upvalue::Ref(local) => Some(local.clone()),
+                Upvalue::Copy(_) => None,
+            }));
+        }
+    });
+    for block in nested_blocks(statement) {
+        for statement in block.lock().iter_mut() {
+            captured_by_reference(statement, locals);
+        }
+    }
+}
+
+fn name(local: &RcLocal) -> Option<String> {
+    local.0.lock().0.clone()
+}
+
+// whether the lifetime of the locals declared by the first statement of `range` conflicts with
+// the statements after it
+fn conflicts(range: &mut [Statement], after: &[Statement]) -> bool {
+    let declared = declared_locals(&range[0])
+        .into_iter()
+        .cloned()
+        .collect_vec();
+    if declared.is_empty() {
+        return false;
+    }
+    let mut captured = FxHashSet::default();
+    for statement in range.iter_mut() {
+        captured_by_reference(statement, &mut captured);
+    }
+    let names = declared.iter().filter_map(name).collect::<FxHashSet<_>>();
+    declared.iter().any(|local| captured.contains(local))
+        || after
+            .iter()
+            .flat_map(declared_locals)
+            .any(|local| name(local).is_some_and(|name| names.contains(&name)))
+}
+
+/// Wraps the statements from where a local is declared to where it is last used in
+/// `do ... end` when its lifetime conflicts with the rest of the block: a closure captures it
+/// by reference, so its upvalue has to be closed once it is dead, or a later local in the block
+/// has the same name. These are the places where the compiler ended a scope and reused its
+/// registers, and the scope is only as large as the locals declared in it need.
+fn scope_conflicts(block: &mut Block) {
+    let reach = reaches(block);
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    while start < block.len() {
+        let end = range_end(&reach, start);
+        let (range, after) = block.split_at_mut(end + 1);
+        // a scope that ends with the block wouldn't change anything
+        if !after.is_empty() && conflicts(&mut range[start..], after) {
+            ranges.push((start, end));
+            start = end + 1;
+        } else {
+            start += 1;
+        }
+    }
+    for (start, end) in ranges.into_iter().rev() {
+        let statements = block.drain(start..=end).collect_vec();
+        block.insert(start, Do::new(Block(statements)).into());
+    }
+
+    for statement in block.iter() {
+        for nested in nested_blocks(statement) {
+            scope_conflicts(&mut nested.lock());
+        }
+    }
+}
+
+/// Wraps runs of statements whose locals aren't used after them in `do ... end` until
+/// no more than `MAX_LOCALS` locals are in scope at once. The compiler reuses the registers
+/// of locals that are no longer used, so a long function can have far more locals than
+/// would fit in a single scope.
+fn limit_scopes(block: &mut Block, enclosing: usize) {
+    let declared = block
+        .iter()
+        .map(|statement| declared_locals(statement).len())
+        .collect_vec();
+    let total = enclosing + declared.iter().sum::<usize>();
+    if total > MAX_LOCALS {
+        let reach = reaches(block);
+
+        // the smallest ranges of statements that no local declared in them outlives
+        let mut ranges = Vec::new();
+        let mut start = 0;
+        while start < block.len() {
+            if declared[start] == 0 {
+                start += 1;
+                continue;
+            }
+            let end = range_end(&reach, start);
+            if end + 1 < block.len() {
+                ranges.push((start, end));
+                start = end + 1;
+            } else {
+                // a scope that ends with the block wouldn't free anything
+                start += 1;
+            }
+        }
+
+        // the locals that stay in scope no matter what
+        let fixed = total
+            - ranges
+                .iter()
+                .map(|&(start, end)| declared[start..=end].iter().sum::<usize>())
+                .sum::<usize>();
+        let budget = MAX_LOCALS.saturating_sub(fixed);
+        let excess = total - MAX_LOCALS;
+        let mut scopes: Vec<(usize, usize, usize)> = Vec::new();
+        let mut freed = 0;
+        for (start, end) in ranges {
+            if freed >= excess {
+                break;
+            }
+            let locals = declared[start..=end].iter().sum::<usize>();
+            freed += locals;
+            match scopes.last_mut() {
+                Some((_, scope_end, scope_locals))
+                    if declared[*scope_end + 1..start].iter().all(|&n| n == 0)
+                        && *scope_locals + locals <= budget =>
+                {
+                    *scope_end = end;
+                    *scope_locals += locals;
+                }
+                _ => scopes.push((start, end, locals)),
+            }
+        }
+        for (start, end, _) in scopes.into_iter().rev() {
+            let statements = block.drain(start..=end).collect_vec();
+            block.insert(start, Do::new(Block(statements)).into());
+        }
+    }
+
+    let mut in_scope = enclosing;
+    for statement in block.iter() {
+        for nested in nested_blocks(statement) {
+            limit_scopes(&mut nested.lock(), in_scope);
+        }
+        in_scope += declared_locals(statement).len();
     }
 }
@@ -1,85 +1,279 @@
 use rustc_hash::FxHashSet;
 use triomphe::Arc;
 
-use crate::{Block, RValue, RcLocal, Statement, Traverse, Upvalue};
+use crate::{
+    formatter::Formatter, Assign, Block, Function, LValue, Literal, MethodCall, RValue, RcLocal,
+    Select, Statement, Traverse, Upvalue,
+};
 
 struct Namer {
     rename: bool,
     counter: usize,
     upvalues: FxHashSet<RcLocal>,
+    // locals that are used as the object of a method call
+    receivers: FxHashSet<RcLocal>,
+    // locals must not shadow any global that is referenced
+    globals: FxHashSet<String>,
+    // the names declared in each enclosing scope
+    scopes: Vec<FxHashSet<String>>,
 }
 
 impl Namer {
-    fn name_local(&mut self, prefix: &str, local: &RcLocal) {
+    fn new(rename: bool, globals: FxHashSet<String>) -> Self {
+        Self {
+            rename,
+            counter: 1,
+            upvalues: FxHashSet::default(),
+            receivers: FxHashSet::default(),
+            globals,
+            scopes: Vec::new(),
+        }
+    }
+
+    fn is_available(&self, name: &str) -> bool {
+        Formatter::<String>::is_valid_name(name.as_bytes())
+            && !self.globals.contains(name)
+            && !self.scopes.iter().any(|scope| scope.contains(name))
+    }
+
+    fn declare(&mut self, name: String) {
+        self.scopes.last_mut().unwrap().insert(name);
+    }
+
+    // picks the first available hint, or numbers the first one until it is available
+    fn unique_name(&self, hints: &[&str]) -> String {
+        if let Some(hint) = hints.iter().find(|hint| self.is_available(hint)) {
+            return hint.to_string();
+        }
+        let separator = if hints[0].ends_with(|c: char| c.is_ascii_digit()) {
+            "_"
+        } else {
+            ""
+        };
+        (2..)
+            .map(|n| format!("{}{}{}", hints[0], separator, n))
+            .find(|name| self.is_available(name))
+            .unwrap()
+    }
+
+    fn name_local(&mut self, prefix: &str, local: &RcLocal, hints: &[&str]) {
         let mut lock = local.0 .0.lock();
         if self.rename || lock.0.is_none() {
             // TODO: hacky and slow
             if Arc::count(&local.0 .0) == 1 {
                 lock.0 = Some("_".to_string());
-            } else {
+                return;
+            }
+            let name = if hints.is_empty() {
                 let prefix = prefix.to_string()
                     + if self.upvalues.contains(local) {
                         "_u_"
                     } else {
                         ""
                     };
-                lock.0 = Some(format!("{}{}", prefix, self.counter));
-                self.counter += 1;
+                loop {
+                    let name = format!("{}{}", prefix, self.counter);
+                    self.counter += 1;
+                    if self.is_available(&name) {
+                        break name;
+                    }
+                }
+            } else {
+                self.unique_name(hints)
+            };
+            lock.0 = Some(name);
+        }
+        if let Some(name) = &lock.0 {
+            self.declare(name.clone());
+        }
+    }
+
+    fn name_function(&mut self, function: &mut Function, is_method: bool) {
+        self.scopes.push(FxHashSet::default());
+        function.is_method = false;
+        for (i, param) in function.parameters.iter().enumerate() {
+            if i == 0 && is_method && self.receivers.contains(param) {
+                self.name_local("p", param, &["self"]);
+                // an enclosing method's `self` would be shadowed otherwise
+                function.is_method = param.0 .0.lock().0.as_deref() == Some("self");
+            } else {
+                self.name_local("p", param, &[]);
             }
         }
+        self.name_statements(&mut function.body);
+        self.scopes.pop();
     }
 
     fn name_locals(&mut self, block: &mut Block) {
+        self.scopes.push(FxHashSet::default());
+        self.name_statements(block);
+        self.scopes.pop();
+    }
+
+    fn name_statements(&mut self, block: &mut Block) {
         for statement in &mut block.0 {
-            // TODO: traverse_rvalues
-            statement.post_traverse_values(&mut |value| -> Option<()> {
-                if let itertools::Either::Right(RValue::Closure(closure)) = value {
-                    let mut function = closure.function.lock();
-                    for param in &function.parameters {
-                        self.name_local("p", param);
+            self.name_statement(statement);
+        }
+    }
+
+    fn name_statement(&mut self, statement: &mut Statement) {
+        // the declared locals are named first so that closures on the right
+        // don't take their names
+        if let Statement::Assign(assign) = statement
+            && assign.prefix
+        {
+            for (i, lvalue) in assign.left.iter().enumerate() {
+                let hint = (assign.left.len() == assign.right.len())
+                    .then(|| Self::hint(&assign.right[i]))
+                    .flatten();
+                self.name_local("v", lvalue.as_local().unwrap(), hint.as_deref().as_slice());
+            }
+        }
+        let is_method = Self::is_method_definition(statement);
+        // TODO: traverse_rvalues
+        statement.post_traverse_values(&mut |value| -> Option<()> {
+            if let itertools::Either::Right(RValue::Closure(closure)) = value {
+                self.name_function(&mut closure.function.lock(), is_method);
+            };
+            None
+        });
+        match statement {
+            Statement::If(r#if) => {
+                self.name_locals(&mut r#if.then_block.lock());
+                self.name_locals(&mut r#if.else_block.lock());
+            }
+            Statement::While(r#while) => {
+                self.name_locals(&mut r#while.block.lock());
+            }
+            Statement::Repeat(repeat) => {
+                self.name_locals(&mut repeat.block.lock());
+            }
+            Statement::Do(r#do) => {
+                self.name_locals(&mut r#do.block.lock());
+            }
+            Statement::NumericFor(numeric_for) => {
+                self.scopes.push(FxHashSet::default());
+                self.name_local("v", &numeric_for.counter, &["i", "j", "k"]);
+                self.name_statements(&mut numeric_for.block.lock());
+                self.scopes.pop();
+            }
+            Statement::GenericFor(generic_for) => {
+                let hints: &[&str] = match generic_for.right.first() {
+                    Some(RValue::Call(call) | RValue::Select(Select::Call(call)))
+                        if Self::is_global(&call.value, "ipairs") =>
+                    {
+                        &["i", "v"]
                     }
-                    self.name_locals(&mut function.body);
-                };
-                None
-            });
-            match statement {
-                Statement::Assign(assign) if assign.prefix => {
-                    for lvalue in &assign.left {
-                        self.name_local("v", lvalue.as_local().unwrap());
+                    Some(RValue::Call(call) | RValue::Select(Select::Call(call)))
+                        if Self::is_global(&call.value, "pairs") =>
+                    {
+                        &["k", "v"]
                     }
+                    Some(generator) if Self::is_global(generator, "next") => &["k", "v"],
+                    _ => &[],
+                };
+                self.scopes.push(FxHashSet::default());
+                for (i, res_local) in generic_for.res_locals.iter().enumerate() {
+                    self.name_local("v", res_local, hints.get(i..=i).unwrap_or_default());
                 }
-                Statement::If(r#if) => {
-                    self.name_locals(&mut r#if.then_block.lock());
-                    self.name_locals(&mut r#if.else_block.lock());
-                }
-                Statement::While(r#while) => {
-                    self.name_locals(&mut r#while.block.lock());
-                }
-                Statement::Repeat(repeat) => {
-                    self.name_locals(&mut repeat.block.lock());
-                }
-                Statement::NumericFor(numeric_for) => {
-                    self.name_local("v", &numeric_for.counter);
-                    self.name_locals(&mut numeric_for.block.lock());
-                }
-                Statement::GenericFor(generic_for) => {
-                    for res_local in &generic_for.res_locals {
-                        self.name_local("v", res_local);
+                self.name_statements(&mut generic_for.block.lock());
+                self.scopes.pop();
+            }
+            _ => {}
+        }
+    }
+
+    fn is_global(rvalue: &RValue, name: &str) -> bool {
+        matches!(rvalue, RValue::Global(global) if global.0 == name.as_bytes())
+    }
+
+    fn string_literal(rvalue: &RValue) -> Option<&str> {
+        match rvalue {
+            RValue::Literal(Literal::String(string)) => std::str::from_utf8(string).ok(),
+            _ => None,
+        }
+    }
+
+    // a closure assigned to a table field, e.g. `function Class.method(...)`
+    fn is_method_definition(statement: &Statement) -> bool {
+        matches!(
+            statement,
+            Statement::Assign(Assign { left, right, .. })
+                if matches!(left.as_slice(), [LValue::Index(_)])
+                    && matches!(right.as_slice(), [RValue::Closure(_)])
+        )
+    }
+
+    // derives a name for a local from the value it is initialized with
+    fn hint(rvalue: &RValue) -> Option<String> {
+        let hint = match rvalue {
+            // the name from debug info, e.g. `local function foo()`
+            RValue::Closure(closure) => closure.function.lock().name.clone(),
+            // game:GetService("Players")
+            RValue::MethodCall(method_call) | RValue::Select(Select::MethodCall(method_call))
+                if method_call.method == "GetService" =>
+            {
+                Self::string_literal(method_call.arguments.first()?).map(str::to_string)
+            }
+            RValue::Call(call) | RValue::Select(Select::Call(call)) => {
+                match call.value.as_ref() {
+                    // require(script.Parent.Foo)
+                    value if Self::is_global(value, "require") => match call.arguments.first()? {
+                        RValue::Index(index) => {
+                            Self::string_literal(&index.right).map(str::to_string)
+                        }
+                        _ => None,
+                    },
+                    // Instance.new("Part")
+                    RValue::Index(index)
+                        if Self::is_global(&index.left, "Instance")
+                            && Self::string_literal(&index.right) == Some("new") =>
+                    {
+                        Self::string_literal(call.arguments.first()?).map(Self::lower_camel_case)
                     }
-                    self.name_locals(&mut generic_for.block.lock());
+                    _ => None,
                 }
-                _ => {}
             }
+            _ => None,
+        }?;
+        // keywords are fine, they just get a number
+        (!hint.is_empty()
+            && !hint.starts_with(|c: char| c.is_ascii_digit())
+            && hint.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+        .then_some(hint)
+    }
+
+    // Part -> part, UIListLayout -> uiListLayout
+    fn lower_camel_case(name: &str) -> String {
+        let upper = name.chars().take_while(|c| c.is_ascii_uppercase()).count();
+        let lower = if upper > 1 && upper < name.len() {
+            upper - 1
+        } else {
+            upper
+        };
+        name[..lower].to_ascii_lowercase() + &name[lower..]
+    }
+
+    fn find_receiver(&mut self, method_call: &MethodCall) {
+        if let RValue::Local(local) = method_call.value.as_ref() {
+            self.receivers.insert(local.clone());
         }
     }
 
     // TODO: does this need to be mut?
-    fn find_upvalues(&mut self, block: &mut Block) {
+    // the bodies of closures are only looked into when `deep` is set
+    fn find_upvalues(&mut self, block: &mut Block, deep: bool) {
         for statement in &mut block.0 {
-            // TODO: traverse_values
-            // TODO: doesnt need to be mut
-            statement.post_traverse_values(&mut |value| -> Option<()> {
-                if let itertools::Either::Right(RValue::Closure(closure)) = value {
+            self.find_upvalues_in(statement, deep);
+        }
+    }
+
+    fn find_upvalues_in(&mut self, statement: &mut Statement, deep: bool) {
+        // TODO: traverse_values
+        // TODO: doesnt need to be mut
+        statement.post_traverse_values(&mut |value| -> Option<()> {
+            match value {
+                itertools::Either::Right(RValue::Closure(closure)) => {
                     self.upvalues.extend(
                         closure
                             .upvalues
@@ -89,39 +283,79 @@ impl Namer {
                             })
                             .cloned(),
                     );
-                    self.find_upvalues(&mut closure.function.lock().body);
-                };
-                None
-            });
-            match statement {
-                Statement::If(r#if) => {
-                    self.find_upvalues(&mut r#if.then_block.lock());
-                    self.find_upvalues(&mut r#if.else_block.lock());
-                }
-                Statement::While(r#while) => {
-                    self.find_upvalues(&mut r#while.block.lock());
-                }
-                Statement::Repeat(repeat) => {
-                    self.find_upvalues(&mut repeat.block.lock());
-                }
-                Statement::NumericFor(numeric_for) => {
-                    self.find_upvalues(&mut numeric_for.block.lock());
+                    if deep {
+                        self.find_upvalues(&mut closure.function.lock().body, true);
+                    }
                 }
-                Statement::GenericFor(generic_for) => {
-                    self.find_upvalues(&mut generic_for.block.lock());
+                itertools::Either::Right(RValue::Global(global))
+                | itertools::Either::Left(LValue::Global(global)) => {
+                    self.globals
+                        .insert(String::from_utf8_lossy(&global.0).into_owned());
                 }
+                itertools::Either::Right(
+                    RValue::MethodCall(method_call)
+                    | RValue::Select(Select::MethodCall(method_call)),
+                ) => self.find_receiver(method_call),
                 _ => {}
+            };
+            None
+        });
+        match statement {
+            Statement::If(r#if) => {
+                self.find_upvalues(&mut r#if.then_block.lock(), deep);
+                self.find_upvalues(&mut r#if.else_block.lock(), deep);
+            }
+            Statement::While(r#while) => {
+                self.find_upvalues(&mut r#while.block.lock(), deep);
+            }
+            Statement::Repeat(repeat) => {
+                self.find_upvalues(&mut repeat.block.lock(), deep);
             }
+            Statement::Do(r#do) => {
+                self.find_upvalues(&mut r#do.block.lock(), deep);
+            }
+            Statement::NumericFor(numeric_for) => {
+                self.find_upvalues(&mut numeric_for.block.lock(), deep);
+            }
+            Statement::GenericFor(generic_for) => {
+                self.find_upvalues(&mut generic_for.block.lock(), deep);
+            }
+            Statement::MethodCall(method_call) => self.find_receiver(method_call),
+            _ => {}
         }
     }
 }
 
+/// Names locals after how they are initialized and used where possible, e.g.
+/// `local Players = game:GetService("Players")` or `for i, v in ipairs(t)`,
+/// and falls back to numbered names. Names never shadow a local in an enclosing scope
+/// or a global that is referenced anywhere in `block`.
 pub fn name_locals(block: &mut Block, rename: bool) {
-    let mut namer = Namer {
-        rename,
-        counter: 1,
-        upvalues: FxHashSet::default(),
-    };
-    namer.find_upvalues(block);
+    let mut namer = Namer::new(rename, FxHashSet::default());
+    namer.find_upvalues(block, true);
     namer.name_locals(block);
 }
+
+/// Names the locals of a block like `name_locals`, but a top-level statement at a time, so
+/// that a statement can be named before the closures in the ones after it have their bodies.
+/// Naming every statement in order gives the same names as `name_locals` as long as `globals`
+/// has every global that is referenced anywhere.
+pub struct StatementNamer(Namer);
+
+impl StatementNamer {
+    /// Only looks at `block` itself and not into the bodies of its closures, which may not be
+    /// finished yet.
+    pub fn new(block: &mut Block, rename: bool, globals: FxHashSet<String>) -> Self {
+        let mut namer = Namer::new(rename, globals);
+        namer.find_upvalues(block, false);
+        namer.scopes.push(FxHashSet::default());
+        Self(namer)
+    }
+
+    /// Names the locals of the next top-level statement of the block, whose closures have to
+    /// be finished by now.
+    pub fn name_statement(&mut self, statement: &mut Statement) {
+        self.0.find_upvalues_in(statement, true);
+        self.0.name_statement(statement);
+    }
+}
@@ -51,11 +51,6 @@ impl LocalRw for Repeat {
 
 impl fmt::Display for Repeat {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Formatter {
-            indentation_level: 0,
-            indentation_mode: Default::default(),
-            output: f,
-        }
-        .format_repeat(self)
+        Formatter::new(f, Default::default()).format_repeat(self)
     }
 }
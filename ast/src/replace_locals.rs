@@ -37,6 +37,9 @@ pub fn replace_locals<H: std::hash::BuildHasher>(
             Statement::Repeat(repeat) => {
                 replace_locals(&mut repeat.block.lock(), map);
             }
+            Statement::Do(r#do) => {
+                replace_locals(&mut r#do.block.lock(), map);
+            }
             Statement::NumericFor(numeric_for) => {
                 replace_locals(&mut numeric_for.block.lock(), map);
             }
@@ -0,0 +1,248 @@
+use std::{
+    cell::{Cell, RefCell},
+    panic::{self, AssertUnwindSafe},
+    sync::Once,
+    time::{Duration, Instant},
+};
+
+use crate::{Block, Statement};
+
+/// How long a pass took, accumulated over every time it ran.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PassTiming {
+    pub pass: String,
+    pub milliseconds: f64,
+}
+
+/// Measures wall-clock time where there is a clock. `Instant::now` panics on
+/// wasm32-unknown-unknown, which the worker is built for, so nothing is measured there.
+#[derive(Debug, Clone, Copy)]
+pub struct Stopwatch(Option<Instant>);
+
+impl Stopwatch {
+    pub fn start() -> Self {
+        Self((!cfg!(all(target_arch = "wasm32", target_os = "unknown"))).then(Instant::now))
+    }
+
+    /// Always zero without a clock.
+    pub fn elapsed(&self) -> Duration {
+        self.0.map_or(Duration::ZERO, |start| start.elapsed())
+    }
+}
+
+/// Runs `f` and adds the time it took to `pass`.
+pub fn time<T>(timings: &mut Vec<PassTiming>, pass: &str, f: impl FnOnce() -> T) -> T {
+    let stopwatch = Stopwatch::start();
+    let result = f();
+    let milliseconds = stopwatch.elapsed().as_secs_f64() * 1000.0;
+    match timings.iter_mut().find(|timing| timing.pass == pass) {
+        Some(timing) => timing.milliseconds += milliseconds,
+        None => timings.push(PassTiming {
+            pass: pass.to_string(),
+            milliseconds,
+        }),
+    }
+    result
+}
+
+thread_local! {
+    // set while a function is being decompiled, so that its panics are caught quietly
+    static CATCHING_PANICS: Cell<bool> = const { Cell::new(false) };
+    static PANIC_SITE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// The panic hook is global, so it is installed once instead of being swapped around each
+/// function, which would race when functions are decompiled in parallel. Panics outside of
+/// `catch_panic` still reach the previous hook.
+fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let prev_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if CATCHING_PANICS.get() {
+                PANIC_SITE.set(info.location().map(|location| location.to_string()));
+            } else {
+                prev_hook(info);
+            }
+        }));
+    });
+}
+
+/// Runs `f`, turning a panic into the `reason` and `site` of a `FunctionStatus`.
+pub fn catch_panic<R>(f: impl FnOnce() -> R) -> Result<R, (String, Option<String>)> {
+    install_panic_hook();
+    let catching = CATCHING_PANICS.replace(true);
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING_PANICS.set(catching);
+    result.map_err(|e| {
+        let panic_information = match e.downcast::<String>() {
+            Ok(v) => *v,
+            Err(e) => match e.downcast::<&str>() {
+                Ok(v) => v.to_string(),
+                _ => "Unknown Source of Error".to_owned(),
+            },
+        };
+        (
+            format!("panicked at '{}'", panic_information),
+            PANIC_SITE.take(),
+        )
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "status", rename_all = "snake_case"))]
+pub enum FunctionStatus {
+    Ok,
+    /// Decompiled with fewer passes after the full pipeline panicked or exceeded its budget.
+    /// `reason` completes a sentence, e.g. "panicked at 'index out of bounds'".
+    Degraded {
+        level: String,
+        reason: String,
+        /// Where it panicked, e.g. `cfg/src/ssa/construct.rs:120:9`.
+        site: Option<String>,
+    },
+    /// Nothing but the disassembly could be shown.
+    Failed {
+        reason: String,
+        site: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FunctionReport {
+    pub id: usize,
+    pub name: Option<String>,
+    pub line_defined: usize,
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub status: FunctionStatus,
+    pub gotos: usize,
+    /// Labels are only emitted for blocks that couldn't be structured.
+    pub labels: usize,
+    pub timings: Vec<PassTiming>,
+    /// Whether it ran out of time, so that decompiling it again can turn out differently.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub timed_out: bool,
+}
+
+impl FunctionReport {
+    /// Counts the gotos and labels in `block`, not including the bodies of closures.
+    pub fn count_unstructured(&mut self, block: &Block) {
+        for statement in &block.0 {
+            match statement {
+                Statement::Goto(_) => self.gotos += 1,
+                Statement::Label(_) => self.labels += 1,
+                Statement::If(r#if) => {
+                    self.count_unstructured(&r#if.then_block.lock());
+                    self.count_unstructured(&r#if.else_block.lock());
+                }
+                Statement::While(r#while) => self.count_unstructured(&r#while.block.lock()),
+                Statement::Repeat(repeat) => self.count_unstructured(&repeat.block.lock()),
+                Statement::Do(r#do) => self.count_unstructured(&r#do.block.lock()),
+                Statement::NumericFor(numeric_for) => {
+                    self.count_unstructured(&numeric_for.block.lock())
+                }
+                Statement::GenericFor(generic_for) => {
+                    self.count_unstructured(&generic_for.block.lock())
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Counts {
+    pub functions: usize,
+    pub ok: usize,
+    pub degraded: usize,
+    pub failed: usize,
+    pub gotos: usize,
+    pub labels: usize,
+}
+
+impl Counts {
+    pub fn add(&mut self, other: &Counts) {
+        self.functions += other.functions;
+        self.ok += other.ok;
+        self.degraded += other.degraded;
+        self.failed += other.failed;
+        self.gotos += other.gotos;
+        self.labels += other.labels;
+    }
+}
+
+/// Everything a decompilation produced besides the source, for tools that want to know
+/// how much of the output can be trusted.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DecompileReport {
+    /// Left out of the JSON when it was taken to be sent separately.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "String::is_empty")
+    )]
+    pub source: String,
+    /// Why nothing was decompiled, e.g. malformed bytecode or a compile error.
+    pub error: Option<String>,
+    pub version: Option<u8>,
+    /// The opcode key, if the opcodes were encoded by multiplying with one.
+    pub key: Option<u8>,
+    pub functions: Vec<FunctionReport>,
+    /// The passes that run once for the whole chunk.
+    pub timings: Vec<PassTiming>,
+    pub warnings: Vec<String>,
+    pub counts: Counts,
+}
+
+impl DecompileReport {
+    /// Whether any function ran out of time.
+    pub fn timed_out(&self) -> bool {
+        self.functions.iter().any(|function| function.timed_out)
+    }
+
+    /// Derives `warnings` and `counts` from `functions`.
+    pub fn summarize(&mut self) {
+        self.warnings.clear();
+        self.counts = Counts {
+            functions: self.functions.len(),
+            ..Default::default()
+        };
+        for function in &self.functions {
+            let name = match &function.name {
+                Some(name) => format!("function {} ({})", function.id, name),
+                None => format!("function {}", function.id),
+            };
+            match &function.status {
+                FunctionStatus::Ok => self.counts.ok += 1,
+                FunctionStatus::Degraded { level, reason, .. } => {
+                    self.counts.degraded += 1;
+                    self.warnings.push(format!(
+                        "{} was decompiled {} after it {}",
+                        name, level, reason
+                    ));
+                }
+                FunctionStatus::Failed { reason, .. } => {
+                    self.counts.failed += 1;
+                    self.warnings
+                        .push(format!("{} failed to decompile: {}", name, reason));
+                }
+            }
+            if function.gotos != 0 {
+                self.warnings
+                    .push(format!("{} contains {} gotos", name, function.gotos));
+            }
+            if function.labels != 0 {
+                self.warnings.push(format!(
+                    "{} contains {} unstructured blocks",
+                    name, function.labels
+                ));
+            }
+            self.counts.gotos += function.gotos;
+            self.counts.labels += function.labels;
+        }
+    }
+}
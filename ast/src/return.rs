@@ -42,11 +42,6 @@ impl LocalRw for Return {
 
 impl fmt::Display for Return {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Formatter {
-            indentation_level: 0,
-            indentation_mode: Default::default(),
-            output: f,
-        }
-        .format_return(self)
+        Formatter::new(f, Default::default()).format_return(self)
     }
 }
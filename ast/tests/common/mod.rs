@@ -0,0 +1,66 @@
+// every test uses a different part of the builders
+#![allow(dead_code)]
+
+use ast::{
+    Assign, Block, Call, Closure, Function, Global, Index, LValue, Literal, Local, RValue, RcLocal,
+    Statement, Table,
+};
+use by_address::ByAddress;
+use parking_lot::Mutex;
+use triomphe::Arc;
+
+pub fn string(value: &str) -> RValue {
+    Literal::String(value.as_bytes().to_vec()).into()
+}
+
+pub fn number(value: f64) -> RValue {
+    Literal::Number(value).into()
+}
+
+pub fn global(name: &str) -> RValue {
+    Global::from(name).into()
+}
+
+pub fn local(name: &str) -> RcLocal {
+    RcLocal::new(Local::new(Some(name.to_string())))
+}
+
+pub fn index(left: RValue, right: &str) -> RValue {
+    Index::new(left, string(right)).into()
+}
+
+pub fn list(values: impl IntoIterator<Item = RValue>) -> RValue {
+    Table(values.into_iter().map(|value| (None, value)).collect()).into()
+}
+
+pub fn call(function: RValue, arguments: Vec<RValue>) -> Call {
+    Call::new(function, arguments)
+}
+
+pub fn assign(target: impl Into<LValue>, value: RValue) -> Statement {
+    Assign::new(vec![target.into()], vec![value]).into()
+}
+
+pub fn function(parameters: Vec<RcLocal>, body: Vec<Statement>) -> Arc<Mutex<Function>> {
+    Arc::new(Mutex::new(Function {
+        parameters,
+        body: Block(body),
+        ..Default::default()
+    }))
+}
+
+pub fn closure(parameters: Vec<RcLocal>, body: Vec<Statement>) -> RValue {
+    closure_of(&function(parameters, body))
+}
+
+pub fn closure_with(function: Function) -> RValue {
+    closure_of(&Arc::new(Mutex::new(function)))
+}
+
+pub fn closure_of(function: &Arc<Mutex<Function>>) -> RValue {
+    Closure {
+        function: ByAddress(function.clone()),
+        upvalues: Vec::new(),
+    }
+    .into()
+}
@@ -0,0 +1,319 @@
+mod common;
+
+use ast::{
+    formatter::{
+        Dialect, FormatOptions, Formatter, IndentationMode, QuoteStyle, SemicolonPolicy,
+        TableSeparator,
+    },
+    Assign, Block, Function, FunctionMetadata, Global, Index, LValue, RValue,
+};
+use common::{assign, call, closure, closure_with, global, list, local, number, string};
+
+fn format(block: &Block, options: FormatOptions) -> String {
+    let mut output = String::new();
+    Formatter::format(block, &mut output, options).unwrap();
+    output
+}
+
+fn arguments(count: usize) -> Vec<RValue> {
+    (1..=count)
+        .map(|i| string(&format!("argument {}", i)))
+        .collect()
+}
+
+fn width(max_line_width: usize) -> FormatOptions {
+    FormatOptions {
+        max_line_width: Some(max_line_width),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn indentation_mode() {
+    let block = Block(vec![assign(
+        Global::from("f"),
+        closure(Vec::new(), vec![call(global("g"), vec![]).into()]),
+    )]);
+    assert_eq!(
+        format(&block, Default::default()),
+        "function f()\n\tg()\nend"
+    );
+    assert_eq!(
+        format(
+            &block,
+            FormatOptions {
+                indentation_mode: IndentationMode::Spaces(2),
+                ..Default::default()
+            }
+        ),
+        "function f()\n  g()\nend"
+    );
+}
+
+#[test]
+fn quote_style() {
+    let block = Block(vec![
+        call(global("print"), vec![string("it's \"quoted\"")]).into()
+    ]);
+    assert_eq!(
+        format(&block, Default::default()),
+        r#"print("it\'s \"quoted\"")"#
+    );
+    assert_eq!(
+        format(
+            &block,
+            FormatOptions {
+                quote_style: QuoteStyle::Single,
+                ..Default::default()
+            }
+        ),
+        r#"print('it\'s \"quoted\"')"#
+    );
+}
+
+#[test]
+fn table_separators() {
+    let block = Block(vec![assign(
+        Global::from("t"),
+        list((1..=4).map(|i| number(i as f64))),
+    )]);
+    assert_eq!(
+        format(&block, Default::default()),
+        "t = {\n\t1,\n\t2,\n\t3,\n\t4\n}"
+    );
+    assert_eq!(
+        format(
+            &block,
+            FormatOptions {
+                table_separator: TableSeparator::Semicolon,
+                trailing_separator: true,
+                ..Default::default()
+            }
+        ),
+        "t = {\n\t1;\n\t2;\n\t3;\n\t4;\n}"
+    );
+}
+
+#[test]
+fn semicolons() {
+    let block = Block(vec![
+        call(global("f"), vec![]).into(),
+        assign(Global::from("x"), number(1.0)),
+    ]);
+    assert_eq!(format(&block, Default::default()), "f()\nx = 1");
+    assert_eq!(
+        format(
+            &block,
+            FormatOptions {
+                semicolons: SemicolonPolicy::Always,
+                ..Default::default()
+            }
+        ),
+        "f();\nx = 1;"
+    );
+}
+
+#[test]
+fn blank_lines_between_functions() {
+    let block = Block(vec![
+        assign(Global::from("x"), number(1.0)),
+        assign(Global::from("f"), closure(Vec::new(), Vec::new())),
+        assign(Global::from("g"), closure(Vec::new(), Vec::new())),
+    ]);
+    assert_eq!(
+        format(
+            &block,
+            FormatOptions {
+                blank_lines_between_functions: 1,
+                ..Default::default()
+            }
+        ),
+        "x = 1\n\nfunction f() end\n\nfunction g() end"
+    );
+}
+
+#[test]
+fn function_headers() {
+    let block = Block(vec![assign(
+        Global::from("f"),
+        closure_with(Function {
+            name: Some("f".to_string()),
+            metadata: Some(FunctionMetadata {
+                id: Some(3),
+                line_defined: 7,
+                num_parameters: 0,
+                num_upvalues: 0,
+                max_stack_size: 2,
+                bytecode_size: 1,
+            }),
+            ..Default::default()
+        }),
+    )]);
+    assert_eq!(format(&block, Default::default()), "function f() end");
+    assert_eq!(
+        format(
+            &block,
+            FormatOptions {
+                function_headers: true,
+                ..Default::default()
+            }
+        ),
+        "function f()\n\t-- function 3 \"f\" (line 7): 0 params, 0 upvalues, max stack 2, 1 instructions\nend"
+    );
+}
+
+#[test]
+fn function_header_names_are_escaped() {
+    let block = Block(vec![assign(
+        Global::from("f"),
+        closure_with(Function {
+            name: Some("f\nos.exit()\r\"".to_string()),
+            metadata: Some(FunctionMetadata {
+                id: None,
+                line_defined: 1,
+                num_parameters: 0,
+                num_upvalues: 0,
+                max_stack_size: 0,
+                bytecode_size: 1,
+            }),
+            ..Default::default()
+        }),
+    )]);
+    assert_eq!(
+        format(
+            &block,
+            FormatOptions {
+                function_headers: true,
+                ..Default::default()
+            }
+        ),
+        "function f()\n\t-- function \"f\\nos.exit()\\r\\\"\" (line 1): 0 params, 0 upvalues, max stack 0, 1 instructions\nend"
+    );
+}
+
+#[test]
+fn dialects_access_invalid_globals_through_the_environment() {
+    let block = Block(vec![call(global("not a name"), vec![]).into()]);
+    assert_eq!(
+        format(&block, Default::default()),
+        r#"getfenv()["not a name"]()"#
+    );
+    assert_eq!(
+        format(
+            &block,
+            FormatOptions {
+                dialect: Dialect::Lua52,
+                ..Default::default()
+            }
+        ),
+        r#"_ENV["not a name"]()"#
+    );
+}
+
+#[test]
+fn long_argument_lists_are_split() {
+    let block = Block(vec![call(global("print"), arguments(3)).into()]);
+    let single_line = r#"print("argument 1", "argument 2", "argument 3")"#;
+    assert_eq!(format(&block, Default::default()), single_line);
+    assert_eq!(format(&block, width(single_line.len())), single_line);
+    assert_eq!(
+        format(&block, width(single_line.len() - 1)),
+        "print(\n\t\"argument 1\",\n\t\"argument 2\",\n\t\"argument 3\"\n)"
+    );
+}
+
+#[test]
+fn short_tables_are_split_when_too_long() {
+    let block = Block(vec![assign(
+        Global::from("t"),
+        list(["first", "second", "third"].map(string)),
+    )]);
+    let single_line = r#"t = { "first", "second", "third" }"#;
+    assert_eq!(format(&block, width(single_line.len())), single_line);
+    assert_eq!(
+        format(&block, width(single_line.len() - 1)),
+        "t = {\n\t\"first\",\n\t\"second\",\n\t\"third\"\n}"
+    );
+}
+
+#[test]
+fn callback_bodies_are_wrapped() {
+    let block = Block(vec![call(
+        global("spawn"),
+        vec![closure(
+            Vec::new(),
+            vec![call(global("print"), arguments(3)).into()],
+        )],
+    )
+    .into()]);
+    assert_eq!(
+        format(&block, width(30)),
+        "spawn(function()\n\tprint(\n\t\t\"argument 1\",\n\t\t\"argument 2\",\n\t\t\"argument 3\"\n\t)\nend)"
+    );
+}
+
+#[test]
+fn bodies_of_multi_line_tables_in_arguments_are_wrapped() {
+    let block = Block(vec![call(
+        global("setup"),
+        vec![list([
+            number(1.0),
+            number(2.0),
+            number(3.0),
+            call(global("print"), arguments(3)).into(),
+        ])],
+    )
+    .into()]);
+    assert_eq!(
+        format(&block, width(30)),
+        "setup({\n\t1,\n\t2,\n\t3,\n\tprint(\n\t\t\"argument 1\",\n\t\t\"argument 2\",\n\t\t\"argument 3\"\n\t)\n})"
+    );
+}
+
+#[test]
+fn methods_are_declared_with_a_colon() {
+    let method = |is_method: bool| {
+        let receiver = local("self");
+        let parameter = local("x");
+        Block(vec![Assign::new(
+            vec![LValue::Index(Index::new(global("Class"), string("method")))],
+            vec![closure_with(Function {
+                parameters: vec![receiver, parameter],
+                is_method,
+                ..Default::default()
+            })],
+        )
+        .into()])
+    };
+    assert_eq!(
+        format(&method(true), Default::default()),
+        "function Class:method(x) end"
+    );
+    // a parameter that is only named `self` is still declared explicitly
+    assert_eq!(
+        format(&method(false), Default::default()),
+        "function Class.method(self, x) end"
+    );
+}
+
+#[test]
+fn nested_callbacks_are_formatted_in_bounded_time() {
+    const DEPTH: usize = 40;
+    let block = (0..DEPTH).fold(
+        Block(vec![call(global("f"), arguments(3)).into()]),
+        |body, _| {
+            Block(vec![call(
+                global("spawn"),
+                vec![closure(Vec::new(), body.0)],
+            )
+            .into()])
+        },
+    );
+    let start = std::time::Instant::now();
+    let formatted = format(&block, width(30));
+    // every level laid out both ways would take about 2^40 renders
+    assert!(start.elapsed() < std::time::Duration::from_secs(10));
+    assert!(formatted.starts_with("spawn(function()\n\tspawn(function()\n"));
+    assert!(formatted.contains("\tf(\n"));
+    assert!(formatted.ends_with("\tend)\nend)"));
+}
@@ -0,0 +1,142 @@
+mod common;
+
+use ast::{
+    formatter::Formatter, local_declarations::LocalDeclarer, Block, Closure, Global, If, RValue,
+    Statement, Upvalue,
+};
+use by_address::ByAddress;
+use common::{assign, call, global, local, number};
+use parking_lot::Mutex;
+use triomphe::Arc;
+
+fn print(value: RValue) -> Statement {
+    call(global("print"), vec![value]).into()
+}
+
+fn capture(upvalue: Upvalue) -> RValue {
+    Closure {
+        function: ByAddress(Arc::new(Mutex::new(Default::default()))),
+        upvalues: vec![upvalue],
+    }
+    .into()
+}
+
+fn declared(block: Vec<Statement>) -> String {
+    let block = Arc::new(Mutex::new(Block(block)));
+    LocalDeclarer::default().declare_locals(block.clone(), &Default::default());
+    let mut output = String::new();
+    Formatter::format(&block.lock(), &mut output, Default::default()).unwrap();
+    output
+}
+
+#[test]
+fn locals_captured_by_reference_are_scoped_to_their_uses() {
+    let (x, f, y, g) = (local("x"), local("f"), local("y"), local("g"));
+    assert_eq!(
+        declared(vec![
+            assign(x.clone(), number(1.0)),
+            assign(f.clone(), capture(Upvalue::Ref(x.clone()))),
+            print(f.clone().into()),
+            // the compiler reuses the register of `x` after closing its upvalue
+            assign(y.clone(), number(2.0)),
+            assign(g.clone(), capture(Upvalue::Ref(y.clone()))),
+            print(g.clone().into()),
+        ]),
+        "do\n\tlocal x = 1\n\tlocal function f() end\n\tprint(f)\nend\n\
+         local y = 2\nlocal function g() end\nprint(g)"
+    );
+}
+
+#[test]
+fn locals_captured_by_value_are_not_scoped() {
+    let (x, f, y) = (local("x"), local("f"), local("y"));
+    assert_eq!(
+        declared(vec![
+            assign(x.clone(), number(1.0)),
+            assign(f.clone(), capture(Upvalue::Copy(x.clone()))),
+            print(f.clone().into()),
+            assign(y.clone(), number(2.0)),
+            print(y.clone().into()),
+        ]),
+        "local x = 1\nlocal function f() end\nprint(f)\nlocal y = 2\nprint(y)"
+    );
+}
+
+#[test]
+fn locals_used_after_a_capture_stay_in_scope() {
+    let (x, f, y) = (local("x"), local("f"), local("y"));
+    assert_eq!(
+        declared(vec![
+            assign(x.clone(), number(1.0)),
+            assign(f.clone(), capture(Upvalue::Ref(x.clone()))),
+            assign(y.clone(), number(2.0)),
+            print(x.clone().into()),
+            print(y.clone().into()),
+        ]),
+        "local x = 1\nlocal function f() end\nlocal y = 2\nprint(x)\nprint(y)"
+    );
+}
+
+#[test]
+fn shadowed_locals_are_scoped_to_their_uses() {
+    let (first, second) = (local("x"), local("x"));
+    assert_eq!(
+        declared(vec![
+            assign(first.clone(), number(1.0)),
+            print(first.clone().into()),
+            assign(second.clone(), number(2.0)),
+            print(second.clone().into()),
+        ]),
+        "do\n\tlocal x = 1\n\tprint(x)\nend\nlocal x = 2\nprint(x)"
+    );
+}
+
+#[test]
+fn nested_blocks_are_scoped() {
+    let (x, f, y) = (local("x"), local("f"), local("y"));
+    let then_block = Block(vec![
+        assign(x.clone(), number(1.0)),
+        assign(f.clone(), capture(Upvalue::Ref(x.clone()))),
+        print(f.clone().into()),
+        assign(y.clone(), number(2.0)),
+        print(y.clone().into()),
+    ]);
+    assert_eq!(
+        declared(vec![If::new(
+            Global::from("c").into(),
+            then_block,
+            Block::default()
+        )
+        .into()]),
+        "if c then\n\tdo\n\t\tlocal x = 1\n\t\tlocal function f() end\n\t\tprint(f)\n\tend\n\
+         \tlocal y = 2\n\tprint(y)\nend"
+    );
+}
+
+#[test]
+fn scopes_keep_locals_under_the_limit() {
+    let locals = (0..300)
+        .map(|i| local(&format!("v{}", i)))
+        .collect::<Vec<_>>();
+    let mut block = locals
+        .iter()
+        .flat_map(|local| {
+            [
+                assign(local.clone(), number(1.0)),
+                print(local.clone().into()),
+            ]
+        })
+        .collect::<Vec<_>>();
+    block.push(print(number(2.0)));
+    let block = Arc::new(Mutex::new(Block(block)));
+    LocalDeclarer::default().declare_locals(block.clone(), &Default::default());
+    let block = block.lock();
+    let top_level = block
+        .iter()
+        .filter(|statement| matches!(statement, Statement::Assign(assign) if assign.prefix))
+        .count();
+    assert!(top_level <= 200, "{} locals in scope", top_level);
+    assert!(block
+        .iter()
+        .any(|statement| matches!(statement, Statement::Do(_))));
+}
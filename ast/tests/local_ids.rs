@@ -0,0 +1,60 @@
+use std::cmp::Ordering;
+
+use ast::{with_local_ids, LocalIds, RcLocal};
+
+#[test]
+fn numbering_continues_across_calls() {
+    let mut ids = LocalIds::new(0);
+    let first = with_local_ids(&mut ids, RcLocal::default);
+    let second = with_local_ids(&mut ids, RcLocal::default);
+    assert_eq!(first.cmp(&second), Ordering::Less);
+}
+
+#[test]
+fn numbering_continues_after_a_panic() {
+    let mut ids = LocalIds::new(0);
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        with_local_ids(&mut ids, || {
+            let _local = RcLocal::default();
+            panic!("the numbering should be saved")
+        })
+    }));
+    assert!(result.is_err());
+    // the same number as the local created before the panic
+    let first = with_local_ids(&mut LocalIds::new(0), RcLocal::default);
+    let second = with_local_ids(&mut ids, RcLocal::default);
+    assert_eq!(first.cmp(&second), Ordering::Less);
+}
+
+#[test]
+fn locals_are_ordered_by_function_then_creation() {
+    let mut first_function = LocalIds::new(0);
+    let mut second_function = LocalIds::new(1);
+    let later = with_local_ids(&mut second_function, RcLocal::default);
+    let earlier = with_local_ids(&mut first_function, || {
+        // a nested numbering doesn't affect the outer one
+        let _nested = with_local_ids(&mut second_function, RcLocal::default);
+        RcLocal::default()
+    });
+    let mut locals = vec![later.clone(), earlier.clone()];
+    locals.sort();
+    assert_eq!(locals, [earlier, later]);
+}
+
+#[test]
+fn locals_outside_of_a_numbering_are_distinct() {
+    let locals = std::thread::scope(|scope| {
+        let threads = (0..4)
+            .map(|_| scope.spawn(RcLocal::default))
+            .collect::<Vec<_>>();
+        threads
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+    for (i, a) in locals.iter().enumerate() {
+        for b in &locals[i + 1..] {
+            assert_ne!(a.cmp(b), Ordering::Equal);
+        }
+    }
+}
@@ -0,0 +1,246 @@
+mod common;
+
+use ast::{
+    name_locals::name_locals, Assign, Block, Do, Function, GenericFor, Global, Index, LValue,
+    MethodCall, NumericFor, RValue, RcLocal, Statement, Table,
+};
+use common::{assign, call, closure, closure_of, function, global, index, number, string};
+use parking_lot::Mutex;
+use triomphe::Arc;
+
+fn get_service(service: &str) -> RValue {
+    MethodCall::new(
+        global("game"),
+        "GetService".to_string(),
+        vec![string(service)],
+    )
+    .into()
+}
+
+fn declare(local: &RcLocal, value: RValue) -> Statement {
+    let mut assign = Assign::new(vec![local.clone().into()], vec![value]);
+    assign.prefix = true;
+    assign.into()
+}
+
+fn named(block: Vec<Statement>) -> Block {
+    let mut block = Block(block);
+    name_locals(&mut block, true);
+    block
+}
+
+#[test]
+fn services_are_named_after_the_service() {
+    let players = RcLocal::default();
+    named(vec![declare(&players, get_service("Players"))]);
+    assert_eq!(players.to_string(), "Players");
+}
+
+#[test]
+fn modules_are_named_after_the_required_instance() {
+    let module = RcLocal::default();
+    let path = index(index(global("script"), "Parent"), "Util");
+    named(vec![declare(
+        &module,
+        call(global("require"), vec![path]).into(),
+    )]);
+    assert_eq!(module.to_string(), "Util");
+}
+
+#[test]
+fn instances_are_named_after_the_class_in_lower_camel_case() {
+    let part = RcLocal::default();
+    let layout = RcLocal::default();
+    let instance_new = || index(global("Instance"), "new");
+    named(vec![
+        declare(&part, call(instance_new(), vec![string("Part")]).into()),
+        declare(
+            &layout,
+            call(instance_new(), vec![string("UIListLayout")]).into(),
+        ),
+    ]);
+    assert_eq!(part.to_string(), "part");
+    assert_eq!(layout.to_string(), "uiListLayout");
+}
+
+#[test]
+fn generic_for_variables_are_named_after_the_iterator() {
+    let names = |right: Vec<RValue>| {
+        let key = RcLocal::default();
+        let value = RcLocal::default();
+        named(vec![GenericFor::new(
+            vec![key.clone(), value.clone()],
+            right,
+            Block::default(),
+        )
+        .into()]);
+        (key.to_string(), value.to_string())
+    };
+    let t = || global("t");
+    assert_eq!(
+        names(vec![call(global("ipairs"), vec![t()]).into()]),
+        ("i".into(), "v".into())
+    );
+    assert_eq!(
+        names(vec![call(global("pairs"), vec![t()]).into()]),
+        ("k".into(), "v".into())
+    );
+    assert_eq!(names(vec![global("next"), t()]), ("k".into(), "v".into()));
+    assert_eq!(
+        names(vec![call(global("iterate"), vec![t()]).into()]),
+        ("v1".into(), "v2".into())
+    );
+}
+
+#[test]
+fn nested_numeric_for_counters_are_named_i_j_k() {
+    let counters = [RcLocal::default(), RcLocal::default(), RcLocal::default()];
+    let one = || number(1.0);
+    let block = counters
+        .iter()
+        .rev()
+        .fold(Block::default(), |block, counter| {
+            Block(vec![NumericFor::new(
+                one(),
+                one(),
+                one(),
+                counter.clone(),
+                block,
+            )
+            .into()])
+        });
+    named(block.0);
+    assert_eq!(counters.map(|counter| counter.to_string()), ["i", "j", "k"]);
+}
+
+#[test]
+fn receivers_of_methods_are_named_self() {
+    let method = |receiver: &RcLocal, body: Vec<Statement>| -> Statement {
+        Assign::new(
+            vec![LValue::Index(Index::new(global("Class"), string("method")))],
+            vec![closure(vec![receiver.clone()], body)],
+        )
+        .into()
+    };
+    let receiver_call =
+        |receiver: &RcLocal| MethodCall::new(receiver.clone().into(), "m".to_string(), vec![]);
+
+    let receiver = RcLocal::default();
+    named(vec![method(
+        &receiver,
+        vec![receiver_call(&receiver).into()],
+    )]);
+    assert_eq!(receiver.to_string(), "self");
+
+    // the first parameter isn't used as the object of a method call
+    let parameter = RcLocal::default();
+    named(vec![method(&parameter, vec![])]);
+    assert_eq!(parameter.to_string(), "p1");
+
+    // the closure isn't assigned to a table field
+    let parameter = RcLocal::default();
+    let function = RcLocal::default();
+    named(vec![declare(
+        &function,
+        closure(
+            vec![parameter.clone()],
+            vec![receiver_call(&parameter).into()],
+        ),
+    )]);
+    assert_ne!(parameter.to_string(), "self");
+}
+
+#[test]
+fn colliding_hints_are_numbered() {
+    let locals: [RcLocal; 4] = Default::default();
+    named(vec![
+        declare(&locals[0], get_service("Players")),
+        declare(&locals[1], get_service("Players")),
+        declare(&locals[2], get_service("Vector3")),
+        declare(&locals[3], get_service("Vector3")),
+    ]);
+    assert_eq!(
+        locals.map(|local| local.to_string()),
+        ["Players", "Players2", "Vector3", "Vector3_2"]
+    );
+}
+
+#[test]
+fn names_do_not_shadow_enclosing_scopes() {
+    let outer = RcLocal::default();
+    let inner = RcLocal::default();
+    let siblings = [RcLocal::default(), RcLocal::default()];
+    named(vec![
+        Do::new(Block(vec![declare(&siblings[0], get_service("Players"))])).into(),
+        Do::new(Block(vec![declare(&siblings[1], get_service("Players"))])).into(),
+        declare(&outer, get_service("Players")),
+        Do::new(Block(vec![declare(&inner, get_service("Players"))])).into(),
+    ]);
+    // scopes that have ended don't matter
+    assert_eq!(
+        siblings.map(|local| local.to_string()),
+        ["Players", "Players"]
+    );
+    assert_eq!(outer.to_string(), "Players");
+    assert_eq!(inner.to_string(), "Players2");
+}
+
+#[test]
+fn names_do_not_shadow_referenced_globals() {
+    let players = RcLocal::default();
+    let numbered = RcLocal::default();
+    let keyword = RcLocal::default();
+    named(vec![
+        declare(&players, get_service("Players")),
+        declare(&numbered, call(global("f"), vec![]).into()),
+        declare(&keyword, get_service("end")),
+        // referenced after the declarations and inside a closure
+        declare(
+            &RcLocal::default(),
+            closure(vec![], vec![assign(Global::from("Players"), global("v1"))]),
+        ),
+    ]);
+    assert_eq!(players.to_string(), "Players2");
+    assert_eq!(numbered.to_string(), "v2");
+    assert_eq!(keyword.to_string(), "end2");
+}
+
+#[test]
+fn nested_methods_are_not_declared_with_a_colon() {
+    let method = |receiver: &RcLocal, function: &Arc<Mutex<Function>>| -> Statement {
+        Assign::new(
+            vec![LValue::Index(Index::new(
+                receiver.clone().into(),
+                string("method"),
+            ))],
+            vec![closure_of(function)],
+        )
+        .into()
+    };
+    let receiver_call =
+        |receiver: &RcLocal| MethodCall::new(receiver.clone().into(), "m".to_string(), vec![]);
+
+    let inner_receiver = RcLocal::default();
+    let inner = function(
+        vec![inner_receiver.clone()],
+        vec![receiver_call(&inner_receiver).into()],
+    );
+    let outer_receiver = RcLocal::default();
+    let outer = function(
+        vec![outer_receiver.clone()],
+        vec![
+            receiver_call(&outer_receiver).into(),
+            method(&outer_receiver, &inner),
+        ],
+    );
+    let class = RcLocal::default();
+    named(vec![
+        declare(&class, Table::default().into()),
+        method(&class, &outer),
+    ]);
+    assert_eq!(outer_receiver.to_string(), "self");
+    assert!(outer.lock().is_method);
+    // `self` is taken by the enclosing method
+    assert_eq!(inner_receiver.to_string(), "self2");
+    assert!(!inner.lock().is_method);
+}
@@ -0,0 +1,13 @@
+use ast::report::catch_panic;
+
+#[test]
+fn panics_are_caught_with_their_site() {
+    let (reason, site) = catch_panic(|| -> () { panic!("the {} pass", "inline") }).unwrap_err();
+    assert_eq!(reason, "panicked at 'the inline pass'");
+    assert!(site.unwrap().starts_with("ast/tests/report.rs:"));
+    // the site of an earlier panic isn't reported again
+    let (reason, site) = catch_panic(|| std::panic::resume_unwind(Box::new(0))).unwrap_err();
+    assert_eq!(reason, "panicked at 'Unknown Source of Error'");
+    assert_eq!(site, None);
+    assert_eq!(catch_panic(|| 1), Ok(1));
+}
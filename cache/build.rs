@@ -0,0 +1,57 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+// the crates whose code decides what the output of the decompiler is
+const DECOMPILER: &[&str] = &[
+    "ast",
+    "cfg",
+    "restructure",
+    "luau-lifter",
+    "lua51-lifter",
+    "lua51-deserializer",
+];
+
+// paths are hashed relative to the workspace, so that the id doesn't depend on where it is
+fn hash(hasher: &mut blake3::Hasher, workspace: &Path, path: PathBuf) {
+    let absolute = workspace.join(&path);
+    if absolute.is_dir() {
+        let mut entries = fs::read_dir(&absolute)
+            .unwrap()
+            .map(|entry| path.join(entry.unwrap().file_name()))
+            .collect::<Vec<_>>();
+        entries.sort();
+        for entry in entries {
+            hash(hasher, workspace, entry);
+        }
+    } else {
+        let contents = fs::read(absolute).unwrap();
+        // every part is prefixed with its length so that they can't run into each other
+        for part in [path.as_os_str().as_encoded_bytes(), &contents] {
+            hasher.update(&(part.len() as u64).to_le_bytes());
+            hasher.update(part);
+        }
+    }
+}
+
+fn main() {
+    let manifest_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    let workspace = manifest_dir.parent().unwrap();
+    // identifies the decompiler, so that entries written by any other version of it are
+    // never used without anyone having to remember to bump a version
+    let mut hasher = blake3::Hasher::new();
+    for name in DECOMPILER {
+        for path in [
+            Path::new(name).join("Cargo.toml"),
+            Path::new(name).join("src"),
+        ] {
+            println!("cargo:rerun-if-changed=../{}", path.display());
+            hash(&mut hasher, workspace, path);
+        }
+    }
+    println!(
+        "cargo:rustc-env=DECOMPILER_BUILD_ID={}",
+        hasher.finalize().to_hex()
+    );
+}
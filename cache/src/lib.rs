@@ -0,0 +1,261 @@
+use std::{
+    fs, io,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+};
+
+use ast::{formatter::FormatOptions, report::DecompileReport};
+use lru::LruCache;
+use luau_lifter::{op_code_decoder::OpcodeDecoder, Budget};
+use parking_lot::Mutex;
+
+/// Part of every key so that entries written by another decompiler are never used. It is a
+/// hash of the source of the decompiler, see `build.rs`.
+const BUILD_ID: &str = env!("DECOMPILER_BUILD_ID");
+
+/// Identifies a decompilation by everything its output depends on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key(blake3::Hash);
+
+impl Key {
+    /// A missing `decoder` means the opcode encoding is detected, which is also how Lua 5.1
+    /// bytecode is keyed.
+    pub fn new(
+        bytecode: &[u8],
+        decoder: Option<&OpcodeDecoder>,
+        options: &FormatOptions,
+        budget: &Budget,
+    ) -> Self {
+        let mut hasher = blake3::Hasher::new();
+        // every part is prefixed with its length so that they can't run into each other
+        let mut update = |part: &[u8]| {
+            hasher.update(&(part.len() as u64).to_le_bytes());
+            hasher.update(part);
+        };
+        update(BUILD_ID.as_bytes());
+        update(bytecode);
+        match decoder {
+            None => update(&[]),
+            Some(OpcodeDecoder::Multiplicative(key)) => update(&[0, *key]),
+            Some(OpcodeDecoder::Table(table)) => update(&[&[1], table.as_slice()].concat()),
+        }
+        update(&serde_json::to_vec(options).unwrap());
+        update(&(budget.max_iterations as u64).to_le_bytes());
+        match budget.time_limit {
+            None => update(&[]),
+            Some(time_limit) => update(&time_limit.as_nanos().to_le_bytes()),
+        }
+        Self(hasher.finalize())
+    }
+}
+
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.to_hex())
+    }
+}
+
+/// Keeps the most recently used reports in memory and, optionally, every report on disk.
+pub struct Cache {
+    memory: Mutex<LruCache<Key, DecompileReport>>,
+    directory: Option<PathBuf>,
+}
+
+impl Cache {
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            memory: Mutex::new(LruCache::new(capacity)),
+            directory: None,
+        }
+    }
+
+    /// Also stores reports as JSON files in `directory`, which is created when needed and
+    /// can be shared by several processes.
+    pub fn with_directory(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.directory = Some(directory.into());
+        self
+    }
+
+    // e.g. `ab/abcdef...json`, so that no directory gets too large
+    fn path(directory: &Path, key: &Key) -> PathBuf {
+        let name = key.to_string();
+        directory.join(&name[..2]).join(name + ".json")
+    }
+
+    /// Entries on disk that can't be read are treated as missing.
+    pub fn get(&self, key: &Key) -> Option<DecompileReport> {
+        if let Some(report) = self.memory.lock().get(key) {
+            return Some(report.clone());
+        }
+        let contents = fs::read(Self::path(self.directory.as_ref()?, key)).ok()?;
+        let report: DecompileReport = serde_json::from_slice(&contents).ok()?;
+        self.memory.lock().put(*key, report.clone());
+        Some(report)
+    }
+
+    /// The report is kept in memory even if writing it to disk fails. Reports that ran out of
+    /// time aren't written to disk, since decompiling again might not.
+    pub fn insert(&self, key: Key, report: &DecompileReport) -> io::Result<()> {
+        self.memory.lock().put(key, report.clone());
+        if let Some(directory) = self.directory.as_ref().filter(|_| !report.timed_out()) {
+            let path = Self::path(directory, &key);
+            fs::create_dir_all(path.parent().unwrap())?;
+            // written to a temporary file first so that readers never see half an entry
+            let temporary = path.with_extension(format!("{}.tmp", std::process::id()));
+            fs::write(&temporary, serde_json::to_vec(report)?)?;
+            fs::rename(temporary, path)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the cached report for `key` or caches the one returned by `decompile`.
+    pub fn get_or_insert_with(
+        &self,
+        key: Key,
+        decompile: impl FnOnce() -> DecompileReport,
+    ) -> io::Result<DecompileReport> {
+        if let Some(report) = self.get(&key) {
+            return Ok(report);
+        }
+        let report = decompile();
+        self.insert(key, &report)?;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use ast::report::{FunctionReport, FunctionStatus};
+
+    use super::*;
+
+    fn report(source: &str) -> DecompileReport {
+        DecompileReport {
+            source: source.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn keys_depend_on_everything_that_changes_the_output() {
+        let options = FormatOptions::default();
+        let budget = Budget::default();
+        let key = Key::new(b"bytecode", None, &options, &budget);
+        assert_eq!(key, Key::new(b"bytecode", None, &options, &budget));
+        assert_ne!(key, Key::new(b"bytecodf", None, &options, &budget));
+        assert_ne!(
+            key,
+            Key::new(
+                b"bytecode",
+                Some(&OpcodeDecoder::Multiplicative(203)),
+                &options,
+                &budget
+            )
+        );
+        assert_ne!(
+            key,
+            Key::new(
+                b"bytecode",
+                None,
+                &FormatOptions {
+                    function_headers: true,
+                    ..Default::default()
+                },
+                &budget
+            )
+        );
+        assert_ne!(
+            key,
+            Key::new(
+                b"bytecode",
+                None,
+                &options,
+                &Budget {
+                    max_iterations: 1,
+                    ..Default::default()
+                }
+            )
+        );
+        assert_ne!(
+            key,
+            Key::new(
+                b"bytecode",
+                None,
+                &options,
+                &Budget {
+                    time_limit: Some(Duration::from_secs(1)),
+                    ..Default::default()
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn the_least_recently_used_report_is_evicted() {
+        let cache = Cache::new(NonZeroUsize::new(2).unwrap());
+        let options = FormatOptions::default();
+        let keys = [b"a", b"b", b"c"]
+            .map(|bytecode| Key::new(bytecode, None, &options, &Budget::default()));
+        cache.insert(keys[0], &report("a")).unwrap();
+        cache.insert(keys[1], &report("b")).unwrap();
+        assert!(cache.get(&keys[0]).is_some());
+        cache.insert(keys[2], &report("c")).unwrap();
+        assert!(cache.get(&keys[1]).is_none());
+        assert_eq!(cache.get(&keys[0]).unwrap().source, "a");
+    }
+
+    #[test]
+    fn reports_on_disk_outlive_the_cache() {
+        let directory = std::env::temp_dir().join(format!("medal-cache-{}", std::process::id()));
+        let key = Key::new(
+            b"bytecode",
+            None,
+            &FormatOptions::default(),
+            &Budget::default(),
+        );
+        Cache::new(NonZeroUsize::MIN)
+            .with_directory(&directory)
+            .insert(key, &report("print(1)"))
+            .unwrap();
+
+        let cache = Cache::new(NonZeroUsize::MIN).with_directory(&directory);
+        let report = cache
+            .get_or_insert_with(key, || unreachable!("the report should be on disk"))
+            .unwrap();
+        assert_eq!(report.source, "print(1)");
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn reports_that_ran_out_of_time_are_only_kept_in_memory() {
+        let directory =
+            std::env::temp_dir().join(format!("medal-cache-timed-out-{}", std::process::id()));
+        let key = Key::new(
+            b"bytecode",
+            None,
+            &FormatOptions::default(),
+            &Budget::default(),
+        );
+        let mut report = report("print(1)");
+        report.functions.push(FunctionReport {
+            id: 0,
+            name: None,
+            line_defined: 0,
+            status: FunctionStatus::Degraded {
+                level: "without SSA (degradation level 3)".to_string(),
+                reason: "took longer than 1s".to_string(),
+                site: None,
+            },
+            gotos: 0,
+            labels: 0,
+            timings: Vec::new(),
+            timed_out: true,
+        });
+        let cache = Cache::new(NonZeroUsize::MIN).with_directory(&directory);
+        cache.insert(key, &report).unwrap();
+        assert!(cache.get(&key).is_some());
+        assert!(!directory.exists());
+    }
+}
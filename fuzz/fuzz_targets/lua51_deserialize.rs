@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use lua51_deserializer::chunk::Chunk;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Chunk::parse(data);
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use luau_lifter::{deserializer, op_code_decoder::OpcodeDecoder};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = deserializer::deserialize(data, &OpcodeDecoder::default());
+});
@@ -1,8 +1,9 @@
 use nom::{
     combinator::opt,
+    error::{Error, ErrorKind, ParseError},
     multi::count,
     number::complete::{le_u32, le_u8},
-    IResult,
+    Err, IResult,
 };
 
 use crate::{
@@ -28,8 +29,21 @@ pub struct Function<'a> {
     pub number_of_parameters: u8,
 }
 
+// LUAI_MAXCCALLS, which also limits how deeply the official compiler nests functions
+const MAX_DEPTH: usize = 200;
+
 impl<'a> Function<'a> {
     pub fn parse(input: &'a [u8]) -> IResult<&'a [u8], Self> {
+        Self::parse_nested(input, 0)
+    }
+
+    fn parse_nested(input: &'a [u8], depth: usize) -> IResult<&'a [u8], Self> {
+        if depth > MAX_DEPTH {
+            return Err(Err::Failure(Error::from_error_kind(
+                input,
+                ErrorKind::TooLarge,
+            )));
+        }
         let (input, name) = value::parse_string(input)?;
         let (input, line_defined) = le_u32(input)?;
         let (input, last_line_defined) = le_u32(input)?;
@@ -42,7 +56,10 @@ impl<'a> Function<'a> {
         let (input, constants_length) = le_u32(input)?;
         let (input, constants) = count(Value::parse, constants_length as usize)(input)?;
         let (input, closures_length) = le_u32(input)?;
-        let (input, closures) = count(Self::parse, closures_length as usize)(input)?;
+        let (input, closures) = count(
+            |input| Self::parse_nested(input, depth + 1),
+            closures_length as usize,
+        )(input)?;
         let (input, positions) = opt(Position::parse)(input)?;
         let (input, locals) = opt(Local::parse_list)(input)?;
         let (input, upvalues) = opt(value::parse_strings)(input)?;
@@ -259,7 +259,9 @@ impl Instruction {
                 array_size: b as u8,
                 hash_size: c as u8,
             },
-            RawInstruction(OperationCode::PrepMethodCall, Layout::BC { a, b, c }) => {
+            RawInstruction(OperationCode::PrepMethodCall, Layout::BC { a, b, c })
+                if a < u8::MAX =>
+            {
                 Self::PrepMethodCall {
                     destination: Register(a),
                     self_arg: Register(a + 1),
@@ -354,31 +356,32 @@ impl Instruction {
             RawInstruction(OperationCode::Return, Layout::BC { a, b, .. }) => {
                 Self::Return(Register(a), b as u8)
             }
-            RawInstruction(OperationCode::IterateNumericForLoop, Layout::BSx { a, b_sx }) => {
+            RawInstruction(OperationCode::IterateNumericForLoop, Layout::BSx { a, b_sx })
+                if a <= u8::MAX - 4 =>
+            {
                 Self::IterateNumericForLoop {
                     control: (a..=a + 4).map(Register).collect(),
                     skip: b_sx,
                 }
             }
-            RawInstruction(OperationCode::InitNumericForLoop, Layout::BSx { a, b_sx }) => {
+            RawInstruction(OperationCode::InitNumericForLoop, Layout::BSx { a, b_sx })
+                if a <= u8::MAX - 4 =>
+            {
                 Self::InitNumericForLoop {
                     control: (a..=a + 4).map(Register).collect(),
                     skip: b_sx,
                 }
             }
-            RawInstruction(OperationCode::IterateGenericForLoop, Layout::BC { a, c, .. }) => {
-                let res = Self::IterateGenericForLoop {
+            // must have at least external control variable
+            RawInstruction(OperationCode::IterateGenericForLoop, Layout::BC { a, c, .. })
+                if c != 0 && a as u16 + 3 + c <= u8::MAX as u16 =>
+            {
+                Self::IterateGenericForLoop {
                     generator: Register(a),
                     state: Register(a + 1),
                     internal_control: Register(a + 2),
                     vars: (a + 3..a + 3 + c as u8).map(Register).collect(),
-                };
-                // must have at least external control variable
-                assert!(match &res {
-                    Self::IterateGenericForLoop { vars, .. } => !vars.is_empty(),
-                    _ => unreachable!(),
-                });
-                res
+                }
             }
             RawInstruction(OperationCode::SetList, Layout::BC { a, b, c }) => Self::SetList {
                 table: Register(a),
@@ -25,7 +25,8 @@ impl<'a> Local<'a> {
         Ok((
             input,
             Self {
-                name: &name[..name.len() - 1],
+                // exclude null terminator
+                name: name.split_last().map_or(name, |(_, name)| name),
                 range: (start..end),
             },
         ))
@@ -37,10 +37,14 @@ impl<'a> Value<'a> {
                 // TODO: lua bytecode actually allows the string to be completely empty
                 // it sets the type to string but gc to NULL
                 // this probably causes some weird behavior
-                assert!(!value.is_empty());
-
-                // exclude null terminator
-                Ok((input, Self::String(&value[..value.len() - 1])))
+                match value.split_last() {
+                    // exclude null terminator
+                    Some((_, value)) => Ok((input, Self::String(value))),
+                    None => Err(Err::Failure(Error::from_error_kind(
+                        input,
+                        ErrorKind::Verify,
+                    ))),
+                }
             }
             _ => Err(Err::Failure(Error::from_error_kind(
                 input,
@@ -0,0 +1,295 @@
+#![feature(box_patterns)]
+#![feature(let_chains)]
+
+use ast::{
+    formatter::{FormatOptions, Formatter},
+    local_declarations::LocalDeclarer,
+    name_locals::name_locals,
+    replace_locals::replace_locals,
+    report::{self, DecompileReport, FunctionReport, FunctionStatus, PassTiming},
+    Traverse,
+};
+use by_address::ByAddress;
+use cfg::ssa::{
+    self,
+    structuring::{structure_conditionals, structure_jumps, structure_method_calls},
+};
+use indexmap::IndexMap;
+use lifter::Lifter;
+use parking_lot::Mutex;
+use petgraph::algo::dominators::simple_fast;
+use rustc_hash::FxHashMap;
+use triomphe::Arc;
+
+use lua51_deserializer::chunk::Chunk;
+
+mod lifter;
+
+pub fn decompile_bytecode(bytecode: &[u8], options: &FormatOptions) -> String {
+    decompile_report(bytecode, options).source
+}
+
+/// Like `decompile_bytecode`, but also describes how each function was decompiled.
+pub fn decompile_report(bytecode: &[u8], options: &FormatOptions) -> DecompileReport {
+    let mut report = DecompileReport::default();
+    // the functions are decompiled one after another, so they can share a numbering.
+    // a panic in a function fails only that function, but lifting the chunk and putting
+    // the functions together can still take down the whole file
+    if let Err((reason, site)) = report::catch_panic(|| {
+        ast::with_local_ids(&mut ast::LocalIds::new(0), || {
+            decompile(bytecode, options, &mut report)
+        })
+    }) {
+        let error = match site {
+            Some(site) => format!("the decompiler {} ({})", reason, site),
+            None => format!("the decompiler {}", reason),
+        };
+        report.source = ast::Comment::new(error.clone()).to_string();
+        report.error = Some(error);
+    }
+    report.summarize();
+    report
+}
+
+fn decompile(bytecode: &[u8], options: &FormatOptions, report: &mut DecompileReport) {
+    let chunk = match report::time(&mut report.timings, "deserialize", || {
+        Chunk::parse(bytecode)
+    }) {
+        Ok((_, chunk)) => chunk,
+        Err(err) => {
+            let error = format!("failed to deserialize bytecode: {}", err);
+            report.source = ast::Comment::new(error.clone()).to_string();
+            report.error = Some(error);
+            return;
+        }
+    };
+    report.version = Some(0x51);
+    let lift_stopwatch = report::Stopwatch::start();
+    let mut lifted = Vec::new();
+    let (function, upvalues) = Lifter::lift(&chunk.function, &mut lifted);
+    let main = Arc::<Mutex<ast::Function>>::default();
+    main.lock().metadata = Some(Lifter::metadata(&chunk.function));
+    lifted.push((main, function, upvalues));
+    lifted.reverse();
+    report.timings.push(PassTiming {
+        pass: "lift".to_string(),
+        milliseconds: lift_stopwatch.elapsed().as_secs_f64() * 1000.0,
+    });
+
+    let (main, ..) = lifted.first().unwrap().clone();
+    let (mut upvalues, function_reports): (FxHashMap<_, _>, Vec<_>) = lifted
+        .into_iter()
+        .enumerate()
+        .map(|(id, (ast_function, function, upvalues_in))| {
+            let mut function_report = {
+                let ast_function = ast_function.lock();
+                FunctionReport {
+                    id,
+                    name: ast_function.name.clone(),
+                    line_defined: ast_function
+                        .metadata
+                        .as_ref()
+                        .map_or(0, |metadata| metadata.line_defined),
+                    status: FunctionStatus::Ok,
+                    gotos: 0,
+                    labels: 0,
+                    timings: Vec::new(),
+                    timed_out: false,
+                }
+            };
+            // keep the signature so that callers still make sense if it fails
+            let signature = (function.parameters.clone(), function.is_variadic);
+            let timings = &mut function_report.timings;
+            let result =
+                report::catch_panic(|| decompile_function(function, &upvalues_in, timings));
+            let mut ast_function_guard = ast_function.lock();
+            match result {
+                Ok((body, parameters, is_variadic)) => {
+                    ast_function_guard.body = body;
+                    ast_function_guard.parameters = parameters;
+                    ast_function_guard.is_variadic = is_variadic;
+                    function_report.count_unstructured(&ast_function_guard.body);
+                }
+                Err((reason, site)) => {
+                    (
+                        ast_function_guard.parameters,
+                        ast_function_guard.is_variadic,
+                    ) = signature;
+                    ast_function_guard.body.extend([
+                        ast::Comment::new("failed to decompile".to_string()).into(),
+                        ast::Comment::new(format!("function {} {}", id, reason)).into(),
+                    ]);
+                    function_report.status = FunctionStatus::Failed { reason, site };
+                }
+            }
+            drop(ast_function_guard);
+            ((ByAddress(ast_function), upvalues_in), function_report)
+        })
+        .unzip();
+    report.functions = function_reports;
+
+    let main = ByAddress(main);
+    upvalues.remove(&main);
+    let main = Arc::try_unwrap(main.0).unwrap().into_inner();
+    let header = main.header().filter(|_| options.function_headers);
+    let mut body = main.body;
+    report::time(&mut report.timings, "link_upvalues", || {
+        link_upvalues(&mut body, &mut upvalues)
+    });
+    report::time(&mut report.timings, "name_locals", || {
+        name_locals(&mut body, true)
+    });
+    if let Some(header) = header {
+        body.insert(0, ast::Comment::new(header).into());
+    }
+    report::time(&mut report.timings, "format", || {
+        Formatter::format(&body, &mut report.source, options.clone()).unwrap()
+    });
+}
+
+/// Structures a lifted function and returns its body and signature.
+fn decompile_function(
+    mut function: cfg::function::Function,
+    upvalues_in: &Vec<ast::RcLocal>,
+    timings: &mut Vec<PassTiming>,
+) -> (ast::Block, Vec<ast::RcLocal>, bool) {
+    let (local_count, local_groups, upvalue_in_groups, upvalue_passed_groups) =
+        report::time(timings, "ssa_construct", || {
+            cfg::ssa::construct(&mut function, upvalues_in)
+        });
+    let upvalue_to_group = upvalue_in_groups
+        .into_iter()
+        .chain(
+            upvalue_passed_groups
+                .into_iter()
+                .map(|m| (ast::RcLocal::default(), m)),
+        )
+        .flat_map(|(i, g)| g.into_iter().map(move |u| (u, i.clone())))
+        .collect::<IndexMap<_, _>>();
+    // TODO: do we even need this?
+    let local_to_group = local_groups
+        .into_iter()
+        .enumerate()
+        .flat_map(|(i, g)| g.into_iter().map(move |l| (l, i)))
+        .collect::<FxHashMap<_, _>>();
+    // TODO: REFACTOR: some way to write a macro that states
+    // if cfg::ssa::inline results in change then structure_jumps, structure_compound_conditionals,
+    // structure_for_loops and remove_unnecessary_params must run again.
+    // if structure_compound_conditionals results in change then dominators and post dominators
+    // must be recalculated.
+    // etc.
+    // the macro could also maybe generate an optimal ordering?
+    let mut changed = true;
+    while changed {
+        changed = false;
+
+        changed |= report::time(timings, "structure_jumps", || {
+            let dominators = simple_fast(function.graph(), function.entry().unwrap());
+            structure_jumps(&mut function, &dominators)
+        });
+
+        report::time(timings, "inline", || {
+            ssa::inline::inline(&mut function, &local_to_group, &upvalue_to_group)
+        });
+
+        if report::time(timings, "structure_conditionals", || {
+            structure_conditionals(&mut function)
+        })
+        // || {
+        //     let post_dominators = post_dominators(function.graph_mut());
+        //     structure_for_loops(&mut function, &dominators, &post_dominators)
+        // }
+            || report::time(timings, "structure_method_calls", || {
+                structure_method_calls(&mut function)
+            })
+        {
+            changed = true;
+        }
+        report::time(timings, "remove_unnecessary_params", || {
+            let mut local_map = FxHashMap::default();
+            // TODO: loop until returns false?
+            if ssa::construct::remove_unnecessary_params(&mut function, &mut local_map) {
+                changed = true;
+            }
+            ssa::construct::apply_local_map(&mut function, local_map);
+        });
+    }
+    report::time(timings, "ssa_destruct", || {
+        ssa::Destructor::new(
+            &mut function,
+            upvalue_to_group,
+            upvalues_in.iter().cloned().collect(),
+            local_count,
+        )
+        .destruct()
+    });
+
+    let params = std::mem::take(&mut function.parameters);
+    let is_variadic = function.is_variadic;
+    let block =
+        Arc::new(report::time(timings, "restructure", || restructure::lift(function)).into());
+    report::time(timings, "declare_locals", || {
+        LocalDeclarer::default().declare_locals(
+            // TODO: why does block.clone() not work?
+            Arc::clone(&block),
+            &upvalues_in.iter().chain(params.iter()).cloned().collect(),
+        )
+    });
+    (
+        Arc::try_unwrap(block).unwrap().into_inner(),
+        params,
+        is_variadic,
+    )
+}
+
+fn link_upvalues(
+    body: &mut ast::Block,
+    upvalues: &mut FxHashMap<ByAddress<Arc<Mutex<ast::Function>>>, Vec<ast::RcLocal>>,
+) {
+    for stat in &mut body.0 {
+        stat.traverse_rvalues(&mut |rvalue| {
+            if let ast::RValue::Closure(closure) = rvalue {
+                let old_upvalues = upvalues.remove(&closure.function).unwrap();
+                let mut function = closure.function.lock();
+                // TODO: inefficient, try constructing a map of all up -> new up first
+                // and then call replace_locals on main body
+                let mut local_map =
+                    FxHashMap::with_capacity_and_hasher(old_upvalues.len(), Default::default());
+                for (old, new) in
+                    old_upvalues
+                        .iter()
+                        .zip(closure.upvalues.iter().map(|u| match u {
+                            ast::Upvalue::Copy(l) | ast::Upvalue::Ref(l) => l,
+                        }))
+                {
+                    // println!("{} -> {}", old, new);
+                    local_map.insert(old.clone(), new.clone());
+                }
+                link_upvalues(&mut function.body, upvalues);
+                replace_locals(&mut function.body, &local_map);
+            }
+        });
+        match stat {
+            ast::Statement::If(r#if) => {
+                link_upvalues(&mut r#if.then_block.lock(), upvalues);
+                link_upvalues(&mut r#if.else_block.lock(), upvalues);
+            }
+            ast::Statement::While(r#while) => {
+                link_upvalues(&mut r#while.block.lock(), upvalues);
+            }
+            ast::Statement::Repeat(repeat) => {
+                link_upvalues(&mut repeat.block.lock(), upvalues);
+            }
+            ast::Statement::Do(r#do) => {
+                link_upvalues(&mut r#do.block.lock(), upvalues);
+            }
+            ast::Statement::NumericFor(numeric_for) => {
+                link_upvalues(&mut numeric_for.block.lock(), upvalues);
+            }
+            ast::Statement::GenericFor(generic_for) => {
+                link_upvalues(&mut generic_for.block.lock(), upvalues);
+            }
+            _ => {}
+        }
+    }
+}
@@ -606,7 +606,8 @@ impl<'a, 'b> Lifter<'a, 'b> {
                         upvalues_passed.push(local);
                     }
 
-                    let ast_function = Arc::<Mutex<_>>::default();
+                    let ast_function = Arc::<Mutex<ast::Function>>::default();
+                    ast_function.lock().metadata = Some(Lifter::metadata(closure));
 
                     let (function, upvalues) = Lifter::lift(closure, self.lifted_functions);
                     self.lifted_functions
@@ -874,6 +875,18 @@ impl<'a, 'b> Lifter<'a, 'b> {
         }
     }
 
+    pub fn metadata(bytecode: &BytecodeFunction) -> ast::FunctionMetadata {
+        ast::FunctionMetadata {
+            // functions are nested rather than indexed
+            id: None,
+            line_defined: bytecode.line_defined as usize,
+            num_parameters: bytecode.number_of_parameters,
+            num_upvalues: bytecode.number_of_upvalues,
+            max_stack_size: bytecode.maximum_stack_size,
+            bytecode_size: bytecode.code.len(),
+        }
+    }
+
     pub fn lift(
         bytecode: &'a BytecodeFunction,
         lifted_functions: &'b mut Vec<(Arc<Mutex<ast::Function>>, Function, Vec<RcLocal>)>,
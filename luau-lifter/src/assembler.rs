@@ -0,0 +1,525 @@
+use std::str::FromStr;
+
+use crate::{
+    deserializer::{
+        bytecode::Bytecode,
+        chunk::Chunk,
+        constant::Constant,
+        function::{DebugInfo, Function, LocalVariable},
+    },
+    instruction::Instruction,
+    op_code::OpCode,
+    op_code_decoder::OpcodeDecoder,
+    serializer,
+};
+
+/// Parses the format produced by [`crate::disassembler::disassemble`] back into bytecode.
+/// Instruction pcs are optional and ignored, the NOPs standing in for aux words are
+/// inserted automatically.
+pub fn assemble(source: &str) -> Result<Bytecode, String> {
+    let mut assembler = Assembler::default();
+    for (line_index, line) in source.lines().enumerate() {
+        assembler
+            .line(line)
+            .map_err(|err| format!("line {}: {}", line_index + 1, err))?;
+    }
+    assembler.finish()
+}
+
+/// Assembles `source` and serializes it with the opcodes encoded by `decoder`, which is how
+/// tests are written in the assembly.
+pub fn assemble_bytecode(source: &str, decoder: &OpcodeDecoder) -> Result<Vec<u8>, String> {
+    serializer::serialize(&assemble(source)?, decoder)
+}
+
+fn tokenize(line: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == ';' {
+            break;
+        } else if c == '"' {
+            let mut token = String::from('"');
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => {
+                        token.push('\\');
+                        token.push(chars.next().ok_or("unterminated string")?);
+                    }
+                    Some(c) => token.push(c),
+                    None => return Err("unterminated string".to_string()),
+                }
+            }
+            tokens.push(token);
+        } else {
+            let mut token = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == ';' {
+                    break;
+                }
+                token.push(c);
+                chars.next();
+            }
+            tokens.push(token);
+        }
+    }
+    Ok(tokens)
+}
+
+fn unescape(token: &str) -> Result<Vec<u8>, String> {
+    let string = token
+        .strip_prefix('"')
+        .ok_or_else(|| format!("expected string, got {}", token))?;
+    let mut bytes = Vec::with_capacity(string.len());
+    let mut chars = string.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buffer = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('n') => bytes.push(b'\n'),
+            Some('r') => bytes.push(b'\r'),
+            Some('t') => bytes.push(b'\t'),
+            Some('"') => bytes.push(b'"'),
+            Some('\\') => bytes.push(b'\\'),
+            Some('x') => {
+                let hex = chars.by_ref().take(2).collect::<String>();
+                bytes.push(
+                    u8::from_str_radix(&hex, 16)
+                        .map_err(|_| format!("invalid escape \\x{}", hex))?,
+                );
+            }
+            Some(c) => return Err(format!("invalid escape \\{}", c)),
+            None => return Err("unterminated escape".to_string()),
+        }
+    }
+    Ok(bytes)
+}
+
+fn parse_int<T: TryFrom<i64>>(token: &str) -> Result<T, String> {
+    let (negative, digits) = match token.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, token),
+    };
+    let value = match digits.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16),
+        None => digits.parse::<i64>(),
+    }
+    .map_err(|_| format!("invalid integer {}", token))?;
+    T::try_from(if negative { -value } else { value })
+        .map_err(|_| format!("integer {} out of range", token))
+}
+
+fn parse_float<T: FromStr>(token: &str, from_bits: impl Fn(u64) -> Option<T>) -> Result<T, String> {
+    match token.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16)
+            .ok()
+            .and_then(from_bits)
+            .ok_or_else(|| format!("invalid number bits {}", token)),
+        None => token
+            .parse::<T>()
+            .map_err(|_| format!("invalid number {}", token)),
+    }
+}
+
+fn parse_f64(token: &str) -> Result<f64, String> {
+    parse_float(token, |bits| Some(f64::from_bits(bits)))
+}
+
+fn parse_f32(token: &str) -> Result<f32, String> {
+    parse_float(token, |bits| u32::try_from(bits).ok().map(f32::from_bits))
+}
+
+fn parse_bool(token: &str) -> Result<bool, String> {
+    match token {
+        "true" | "1" => Ok(true),
+        "false" | "0" => Ok(false),
+        _ => Err(format!("invalid boolean {}", token)),
+    }
+}
+
+fn expect_arguments(arguments: &[String], count: usize) -> Result<(), String> {
+    if arguments.len() != count {
+        return Err(format!(
+            "expected {} arguments, got {}",
+            count,
+            arguments.len()
+        ));
+    }
+    Ok(())
+}
+
+fn empty_function() -> Function {
+    Function {
+        max_stack_size: 0,
+        num_parameters: 0,
+        num_upvalues: 0,
+        is_vararg: false,
+        flags: 0,
+        type_info: Vec::new(),
+        instructions: Vec::new(),
+        constants: Vec::new(),
+        functions: Vec::new(),
+        line_defined: 0,
+        function_name: 0,
+        line_gap_log2: None,
+        line_info_delta: None,
+        abs_line_info_delta: None,
+        debug_info: None,
+    }
+}
+
+#[derive(Default)]
+struct Assembler {
+    error: Option<String>,
+    version: Option<u8>,
+    types_version: u8,
+    userdata_types: Vec<usize>,
+    string_table: Vec<Vec<u8>>,
+    functions: Vec<Function>,
+    function: Option<Function>,
+    main: Option<usize>,
+}
+
+impl Assembler {
+    fn line(&mut self, line: &str) -> Result<(), String> {
+        let tokens = tokenize(line)?;
+        let Some((directive, arguments)) = tokens.split_first() else {
+            return Ok(());
+        };
+        if self.function.is_some() {
+            return self.function_line(directive, arguments);
+        }
+        match directive.as_str() {
+            "error" => {
+                expect_arguments(arguments, 1)?;
+                self.error = Some(String::from_utf8_lossy(&unescape(&arguments[0])?).into_owned());
+            }
+            "version" => {
+                expect_arguments(arguments, 1)?;
+                self.version = Some(parse_int(&arguments[0])?);
+            }
+            "types_version" => {
+                expect_arguments(arguments, 1)?;
+                self.types_version = parse_int(&arguments[0])?;
+            }
+            "userdata_types" => {
+                self.userdata_types = arguments
+                    .iter()
+                    .map(|a| parse_int(a))
+                    .collect::<Result<_, _>>()?;
+            }
+            "string" => {
+                expect_arguments(arguments, 2)?;
+                let index = parse_int::<usize>(&arguments[0])?;
+                if index != self.string_table.len() + 1 {
+                    return Err(format!(
+                        "expected string {}, got {}",
+                        self.string_table.len() + 1,
+                        index
+                    ));
+                }
+                self.string_table.push(unescape(&arguments[1])?);
+            }
+            "function" => {
+                expect_arguments(arguments, 1)?;
+                let function_id = parse_int::<usize>(&arguments[0])?;
+                if function_id != self.functions.len() {
+                    return Err(format!(
+                        "expected function {}, got {}",
+                        self.functions.len(),
+                        function_id
+                    ));
+                }
+                self.function = Some(empty_function());
+            }
+            "main" => {
+                expect_arguments(arguments, 1)?;
+                self.main = Some(parse_int(&arguments[0])?);
+            }
+            _ => return Err(format!("unknown directive {}", directive)),
+        }
+        Ok(())
+    }
+
+    fn function_line(&mut self, directive: &str, arguments: &[String]) -> Result<(), String> {
+        let function = self.function.as_mut().unwrap();
+        match directive {
+            "max_stack_size" => {
+                expect_arguments(arguments, 1)?;
+                function.max_stack_size = parse_int(&arguments[0])?;
+            }
+            "num_parameters" => {
+                expect_arguments(arguments, 1)?;
+                function.num_parameters = parse_int(&arguments[0])?;
+            }
+            "num_upvalues" => {
+                expect_arguments(arguments, 1)?;
+                function.num_upvalues = parse_int(&arguments[0])?;
+            }
+            "is_vararg" => {
+                expect_arguments(arguments, 1)?;
+                function.is_vararg = parse_bool(&arguments[0])?;
+            }
+            "flags" => {
+                expect_arguments(arguments, 1)?;
+                function.flags = parse_int(&arguments[0])?;
+            }
+            "type_info" => {
+                function.type_info = arguments
+                    .iter()
+                    .map(|a| u8::from_str_radix(a, 16).map_err(|_| format!("invalid byte {}", a)))
+                    .collect::<Result<_, _>>()?;
+            }
+            "line_defined" => {
+                expect_arguments(arguments, 1)?;
+                function.line_defined = parse_int(&arguments[0])?;
+            }
+            "function_name" => {
+                expect_arguments(arguments, 1)?;
+                function.function_name = parse_int(&arguments[0])?;
+            }
+            "functions" => {
+                function.functions = arguments
+                    .iter()
+                    .map(|a| parse_int(a))
+                    .collect::<Result<_, _>>()?;
+            }
+            "constant" => {
+                let (index, constant) = arguments.split_first().ok_or("expected constant index")?;
+                let index = parse_int::<usize>(index)?;
+                if index != function.constants.len() {
+                    return Err(format!(
+                        "expected constant {}, got {}",
+                        function.constants.len(),
+                        index
+                    ));
+                }
+                function.constants.push(Self::constant(constant)?);
+            }
+            "code" => {}
+            "line_gap_log2" => {
+                expect_arguments(arguments, 1)?;
+                function.line_gap_log2 = Some(parse_int(&arguments[0])?);
+            }
+            "line_info" => {
+                function.line_info_delta = Some(
+                    arguments
+                        .iter()
+                        .map(|a| parse_int(a))
+                        .collect::<Result<_, _>>()?,
+                );
+            }
+            "abs_line_info" => {
+                function.abs_line_info_delta = Some(
+                    arguments
+                        .iter()
+                        .map(|a| parse_int(a))
+                        .collect::<Result<_, _>>()?,
+                );
+            }
+            "debug_info" => {
+                function.debug_info = Some(DebugInfo {
+                    locals: Vec::new(),
+                    upvalues: Vec::new(),
+                });
+            }
+            "local" => {
+                expect_arguments(arguments, 4)?;
+                function
+                    .debug_info
+                    .as_mut()
+                    .ok_or("local outside of debug_info")?
+                    .locals
+                    .push(LocalVariable {
+                        name: parse_int(&arguments[0])?,
+                        start_pc: parse_int(&arguments[1])?,
+                        end_pc: parse_int(&arguments[2])?,
+                        register: parse_int(&arguments[3])?,
+                    });
+            }
+            "upvalue_names" => {
+                function
+                    .debug_info
+                    .as_mut()
+                    .ok_or("upvalue_names outside of debug_info")?
+                    .upvalues = arguments
+                    .iter()
+                    .map(|a| parse_int(a))
+                    .collect::<Result<_, _>>()?;
+            }
+            "end" => {
+                let function = self.function.take().unwrap();
+                Self::check_line_info(&function)?;
+                self.functions.push(function);
+            }
+            _ if directive.bytes().all(|c| c.is_ascii_digit()) => {
+                let (mnemonic, fields) = arguments
+                    .split_first()
+                    .ok_or("expected instruction after pc")?;
+                Self::instruction(function, mnemonic, fields)?;
+            }
+            _ => Self::instruction(function, directive, arguments)?,
+        }
+        Ok(())
+    }
+
+    fn constant(arguments: &[String]) -> Result<Constant, String> {
+        let (kind, arguments) = arguments.split_first().ok_or("expected constant kind")?;
+        Ok(match kind.as_str() {
+            "nil" => {
+                expect_arguments(arguments, 0)?;
+                Constant::Nil
+            }
+            "boolean" => {
+                expect_arguments(arguments, 1)?;
+                Constant::Boolean(parse_bool(&arguments[0])?)
+            }
+            "number" => {
+                expect_arguments(arguments, 1)?;
+                Constant::Number(parse_f64(&arguments[0])?)
+            }
+            "string" => {
+                expect_arguments(arguments, 1)?;
+                Constant::String(parse_int(&arguments[0])?)
+            }
+            "import" => {
+                expect_arguments(arguments, 1)?;
+                Constant::Import(parse_int::<u32>(&arguments[0])? as usize)
+            }
+            "table" => Constant::Table(
+                arguments
+                    .iter()
+                    .map(|a| parse_int(a))
+                    .collect::<Result<_, _>>()?,
+            ),
+            "closure" => {
+                expect_arguments(arguments, 1)?;
+                Constant::Closure(parse_int(&arguments[0])?)
+            }
+            "vector" => {
+                expect_arguments(arguments, 4)?;
+                Constant::Vector(
+                    parse_f32(&arguments[0])?,
+                    parse_f32(&arguments[1])?,
+                    parse_f32(&arguments[2])?,
+                    parse_f32(&arguments[3])?,
+                )
+            }
+            _ => return Err(format!("unknown constant kind {}", kind)),
+        })
+    }
+
+    fn instruction(
+        function: &mut Function,
+        mnemonic: &str,
+        fields: &[String],
+    ) -> Result<(), String> {
+        let op_code = OpCode::from_mnemonic(mnemonic)
+            .ok_or_else(|| format!("unknown instruction {}", mnemonic))?;
+        let (mut a, mut b, mut c, mut d, mut e, mut aux) = (0, 0, 0, None, None, None);
+        for field in fields {
+            let (name, value) = field
+                .split_once('=')
+                .ok_or_else(|| format!("expected operand, got {}", field))?;
+            match name.to_ascii_uppercase().as_str() {
+                "A" => a = parse_int(value)?,
+                "B" => b = parse_int(value)?,
+                "C" => c = parse_int(value)?,
+                "D" => d = Some(parse_int(value)?),
+                "E" => e = Some(parse_int(value)?),
+                "AUX" => aux = Some(parse_int(value)?),
+                _ => return Err(format!("unknown operand {}", name)),
+            }
+        }
+        if aux.is_some() && !op_code.has_aux() {
+            return Err(format!("{} does not take an aux word", mnemonic));
+        }
+        let aux = aux.unwrap_or(0);
+        function.instructions.push(match (d, e) {
+            (None, Some(e)) => Instruction::E { op_code, e },
+            (Some(d), None) => Instruction::AD { op_code, a, d, aux },
+            (None, None) => Instruction::BC {
+                op_code,
+                a,
+                b,
+                c,
+                aux,
+            },
+            (Some(_), Some(_)) => return Err("D and E operands are exclusive".to_string()),
+        });
+        if op_code.has_aux() {
+            function.instructions.push(Instruction::BC {
+                op_code: OpCode::LOP_NOP,
+                a: 0,
+                b: 0,
+                c: 0,
+                aux: 0,
+            });
+        }
+        Ok(())
+    }
+
+    fn check_line_info(function: &Function) -> Result<(), String> {
+        match (
+            function.line_gap_log2,
+            &function.line_info_delta,
+            &function.abs_line_info_delta,
+        ) {
+            (None, None, None) => Ok(()),
+            (Some(line_gap_log2), Some(line_info_delta), Some(abs_line_info_delta)) => {
+                let instruction_count = function.instructions.len();
+                if line_info_delta.len() != instruction_count {
+                    return Err(format!(
+                        "line_info has {} entries but the function has {} instructions",
+                        line_info_delta.len(),
+                        instruction_count
+                    ));
+                }
+                let expected = instruction_count
+                    .checked_sub(1)
+                    .and_then(|last| last.checked_shr(line_gap_log2.into()))
+                    .map_or(0, |intervals| intervals + 1);
+                if abs_line_info_delta.len() != expected {
+                    return Err(format!(
+                        "abs_line_info has {} entries, expected {}",
+                        abs_line_info_delta.len(),
+                        expected
+                    ));
+                }
+                Ok(())
+            }
+            _ => {
+                Err("line_gap_log2, line_info and abs_line_info must be given together".to_string())
+            }
+        }
+    }
+
+    fn finish(self) -> Result<Bytecode, String> {
+        if self.function.is_some() {
+            return Err("unterminated function".to_string());
+        }
+        if let Some(error) = self.error {
+            return Ok(Bytecode::Error(error));
+        }
+        let version = self.version.ok_or("missing version")?;
+        let main = self.main.ok_or("missing main")?;
+        if main >= self.functions.len() {
+            return Err(format!("main function {} does not exist", main));
+        }
+        Ok(Bytecode::Chunk(Chunk {
+            version,
+            types_version: self.types_version,
+            userdata_types: self.userdata_types,
+            string_table: self.string_table,
+            functions: self.functions,
+            main,
+        }))
+    }
+}
@@ -1,6 +1,12 @@
-use nom::{bytes::complete::take, number::complete::le_u8, IResult};
+use nom::{
+    bytes::complete::take,
+    error::{Error, ErrorKind, ParseError},
+    number::complete::le_u8,
+    Err, IResult,
+};
 
 use super::chunk::Chunk;
+use crate::op_code_decoder::OpcodeDecoder;
 
 #[derive(Debug)]
 pub enum Bytecode {
@@ -9,7 +15,7 @@ pub enum Bytecode {
 }
 
 impl Bytecode {
-    pub fn parse(input: &[u8], encode_key: u8) -> IResult<&[u8], Bytecode> {
+    pub fn parse<'a>(input: &'a [u8], decoder: &OpcodeDecoder) -> IResult<&'a [u8], Bytecode> {
         let (input, status_code) = le_u8(input)?;
         match status_code {
             0 => {
@@ -20,10 +26,13 @@ impl Bytecode {
                 ))
             }
             4..=6 => {
-                let (input, chunk) = Chunk::parse(input, encode_key, status_code)?;
+                let (input, chunk) = Chunk::parse(input, decoder, status_code)?;
                 Ok((input, Bytecode::Chunk(chunk)))
             }
-            _ => panic!("Unsupported bytecode version: {}", status_code),
+            _ => Err(Err::Failure(Error::from_error_kind(
+                input,
+                ErrorKind::Switch,
+            ))),
         }
     }
 }
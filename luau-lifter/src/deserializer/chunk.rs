@@ -1,39 +1,55 @@
 use super::{function::Function, list::parse_list, parse_string};
+use crate::op_code_decoder::OpcodeDecoder;
 use nom::character::complete::char;
+use nom::error::{Error, ErrorKind, ParseError};
 use nom::multi::many_till;
 use nom::number::complete::le_u8;
-use nom::IResult;
+use nom::{Err, IResult};
 use nom_leb128::leb128_usize;
 
 #[derive(Debug)]
 pub struct Chunk {
+    pub version: u8,
+    pub types_version: u8,
+    pub userdata_types: Vec<usize>,
     pub string_table: Vec<Vec<u8>>,
     pub functions: Vec<Function>,
     pub main: usize,
 }
 
 impl Chunk {
-    pub(crate) fn parse(input: &[u8], encode_key: u8, version: u8) -> IResult<&[u8], Self> {
+    pub(crate) fn parse<'a>(
+        input: &'a [u8],
+        decoder: &OpcodeDecoder,
+        version: u8,
+    ) -> IResult<&'a [u8], Self> {
         let (input, types_version) = if version >= 4 {
             le_u8(input)?
         } else {
             (input, 0)
         };
         if types_version > 3 {
-            panic!("unsupported types version");
+            return Err(Err::Failure(Error::from_error_kind(
+                input,
+                ErrorKind::Switch,
+            )));
         }
         let (input, string_table) = parse_list(input, parse_string)?;
-        let input = if types_version == 3 {
-            many_till(leb128_usize, char('\0'))(input)?.0
+        let (input, userdata_types) = if types_version == 3 {
+            let (input, (userdata_types, _)) = many_till(leb128_usize, char('\0'))(input)?;
+            (input, userdata_types)
         } else {
-            input
+            (input, Vec::new())
         };
-        let (input, functions) = parse_list(input, |i| Function::parse(i, encode_key))?;
+        let (input, functions) = parse_list(input, |i| Function::parse(i, decoder))?;
         let (input, main) = leb128_usize(input)?;
 
         Ok((
             input,
             Self {
+                version,
+                types_version,
+                userdata_types,
                 string_table,
                 functions,
                 main,
@@ -1,18 +1,19 @@
 use super::list::parse_list;
 use nom::{
+    error::{Error, ErrorKind, ParseError},
     number::complete::{le_f32, le_f64, le_u32, le_u8},
-    IResult,
+    Err, IResult,
 };
 use nom_leb128::leb128_usize;
 
-const CONSTANT_NIL: u8 = 0;
-const CONSTANT_BOOLEAN: u8 = 1;
-const CONSTANT_NUMBER: u8 = 2;
-const CONSTANT_STRING: u8 = 3;
-const CONSTANT_IMPORT: u8 = 4;
-const CONSTANT_TABLE: u8 = 5;
-const CONSTANT_CLOSURE: u8 = 6;
-const CONSTANT_VECTOR: u8 = 7;
+pub(crate) const CONSTANT_NIL: u8 = 0;
+pub(crate) const CONSTANT_BOOLEAN: u8 = 1;
+pub(crate) const CONSTANT_NUMBER: u8 = 2;
+pub(crate) const CONSTANT_STRING: u8 = 3;
+pub(crate) const CONSTANT_IMPORT: u8 = 4;
+pub(crate) const CONSTANT_TABLE: u8 = 5;
+pub(crate) const CONSTANT_CLOSURE: u8 = 6;
+pub(crate) const CONSTANT_VECTOR: u8 = 7;
 
 #[derive(Debug)]
 pub enum Constant {
@@ -62,7 +63,10 @@ impl Constant {
                 let (input, w) = le_f32(input)?;
                 Ok((input, Constant::Vector(x, y, z, w)))
             }
-            _ => panic!("{}", tag),
+            _ => Err(Err::Failure(Error::from_error_kind(
+                input,
+                ErrorKind::Switch,
+            ))),
         }
     }
 }
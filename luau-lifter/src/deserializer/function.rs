@@ -12,7 +12,21 @@ use super::{
     list::{parse_list, parse_list_len},
 };
 
-use crate::{instruction::*, op_code::OpCode};
+use crate::{instruction::*, op_code::OpCode, op_code_decoder::OpcodeDecoder};
+
+#[derive(Debug)]
+pub struct LocalVariable {
+    pub name: usize,
+    pub start_pc: usize,
+    pub end_pc: usize,
+    pub register: u8,
+}
+
+#[derive(Debug)]
+pub struct DebugInfo {
+    pub locals: Vec<LocalVariable>,
+    pub upvalues: Vec<usize>,
+}
 
 #[derive(Debug)]
 pub struct Function {
@@ -20,6 +34,8 @@ pub struct Function {
     pub num_parameters: u8,
     pub num_upvalues: u8,
     pub is_vararg: bool,
+    pub flags: u8,
+    pub type_info: Vec<u8>,
     //pub instructions: Vec<u32>,
     pub instructions: Vec<Instruction>,
     pub constants: Vec<Constant>,
@@ -29,15 +45,37 @@ pub struct Function {
     pub line_gap_log2: Option<u8>,
     pub line_info_delta: Option<Vec<u8>>,
     pub abs_line_info_delta: Option<Vec<u32>>,
+    pub debug_info: Option<DebugInfo>,
+}
+
+impl LocalVariable {
+    fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        let (input, name) = leb128_usize(input)?;
+        let (input, start_pc) = leb128_usize(input)?;
+        let (input, end_pc) = leb128_usize(input)?;
+        let (input, register) = le_u8(input)?;
+        Ok((
+            input,
+            Self {
+                name,
+                start_pc,
+                end_pc,
+                register,
+            },
+        ))
+    }
 }
 
 impl Function {
-    fn parse_instructions(vec: &Vec<u32>, encode_key: u8) -> Vec<Instruction> {
+    fn parse_instructions(
+        vec: &Vec<u32>,
+        decoder: &OpcodeDecoder,
+    ) -> Result<Vec<Instruction>, nom::error::ErrorKind> {
         let mut v: Vec<Instruction> = Vec::new();
         let mut pc = 0;
 
-        loop {
-            let ins = Instruction::parse(vec[pc], encode_key).unwrap();
+        while pc < vec.len() {
+            let ins = Instruction::parse(vec[pc], decoder)?;
             let op = match ins {
                 Instruction::BC { op_code, .. } => op_code,
                 Instruction::AD { op_code, .. } => op_code,
@@ -46,30 +84,8 @@ impl Function {
 
             // handle ops with aux values
             match op {
-                OpCode::LOP_GETGLOBAL
-                | OpCode::LOP_SETGLOBAL
-                | OpCode::LOP_GETIMPORT
-                | OpCode::LOP_GETTABLEKS
-                | OpCode::LOP_SETTABLEKS
-                | OpCode::LOP_NAMECALL
-                | OpCode::LOP_JUMPIFEQ
-                | OpCode::LOP_JUMPIFLE
-                | OpCode::LOP_JUMPIFLT
-                | OpCode::LOP_JUMPIFNOTEQ
-                | OpCode::LOP_JUMPIFNOTLE
-                | OpCode::LOP_JUMPIFNOTLT
-                | OpCode::LOP_NEWTABLE
-                | OpCode::LOP_SETLIST
-                | OpCode::LOP_FORGLOOP
-                | OpCode::LOP_LOADKX
-                | OpCode::LOP_FASTCALL2
-                | OpCode::LOP_FASTCALL2K
-                | OpCode::LOP_FASTCALL3
-                | OpCode::LOP_JUMPXEQKNIL
-                | OpCode::LOP_JUMPXEQKB
-                | OpCode::LOP_JUMPXEQKN
-                | OpCode::LOP_JUMPXEQKS => {
-                    let aux = vec[pc + 1];
+                op if op.has_aux() => {
+                    let aux = *vec.get(pc + 1).ok_or(nom::error::ErrorKind::Eof)?;
                     pc += 2;
                     match ins {
                         Instruction::BC {
@@ -101,27 +117,24 @@ impl Function {
                     pc += 1;
                 }
             }
-
-            if pc == vec.len() {
-                break;
-            }
         }
 
-        v
+        Ok(v)
     }
 
-    pub(crate) fn parse(input: &[u8], encode_key: u8) -> IResult<&[u8], Self> {
+    pub(crate) fn parse<'a>(input: &'a [u8], decoder: &OpcodeDecoder) -> IResult<&'a [u8], Self> {
         let (input, max_stack_size) = le_u8(input)?;
         let (input, num_parameters) = le_u8(input)?;
         let (input, num_upvalues) = le_u8(input)?;
         let (input, is_vararg) = le_u8(input)?;
 
         let (input, flags) = le_u8(input)?;
-        let (input, _) = parse_list(input, le_u8)?;
+        let (input, type_info) = parse_list(input, le_u8)?;
 
         let (input, u32_instructions) = parse_list(input, le_u32)?;
         //let (input, instructions) = parse_list(input, Function::parse_instrution)?;
-        let instructions = Self::parse_instructions(&u32_instructions, encode_key);
+        let instructions = Self::parse_instructions(&u32_instructions, decoder)
+            .map_err(|kind| nom::Err::Failure(nom::error::Error::new(input, kind)))?;
         let (input, constants) = parse_list(input, Constant::parse)?;
         let (input, functions) = parse_list(input, leb128_usize)?;
         let (input, line_defined) = leb128_usize(input)?;
@@ -145,30 +158,23 @@ impl Function {
         let (input, abs_line_info_delta) = match has_line_info {
             0 => (input, None),
             _ => {
-                let (input, abs_line_info_delta) = parse_list_len(
-                    input,
-                    le_u32,
-                    ((u32_instructions.len() - 1) >> line_gap_log2.unwrap()) + 1,
-                )?;
+                // same as ((sizecode - 1) >> linegaplog2) + 1 in the official deserializer
+                let intervals = u32_instructions.len().checked_sub(1).map_or(0, |last_pc| {
+                    last_pc
+                        .checked_shr(line_gap_log2.unwrap().into())
+                        .unwrap_or(0)
+                        + 1
+                });
+                let (input, abs_line_info_delta) = parse_list_len(input, le_u32, intervals)?;
                 (input, Some(abs_line_info_delta))
             }
         };
-        let input = match le_u8(input)? {
-            (input, 0) => input,
+        let (input, debug_info) = match le_u8(input)? {
+            (input, 0) => (input, None),
             (input, _) => {
-                panic!("we have debug info");
-                let (mut input, num_locvars) = leb128_usize(input)?;
-                for _ in 0..num_locvars {
-                    (input, _) = leb128_usize(input)?;
-                    (input, _) = leb128_usize(input)?;
-                    (input, _) = leb128_usize(input)?;
-                    (input, _) = le_u8(input)?;
-                }
-                let (mut input, num_upvalues) = leb128_usize(input)?;
-                for _ in 0..num_upvalues {
-                    (input, _) = leb128_usize(input)?;
-                }
-                input
+                let (input, locals) = parse_list(input, LocalVariable::parse)?;
+                let (input, upvalues) = parse_list(input, leb128_usize)?;
+                (input, Some(DebugInfo { locals, upvalues }))
             }
         };
         Ok((
@@ -178,6 +184,8 @@ impl Function {
                 num_parameters,
                 num_upvalues,
                 is_vararg: is_vararg != 0u8,
+                flags,
+                type_info,
                 instructions,
                 constants,
                 functions,
@@ -186,6 +194,7 @@ impl Function {
                 line_gap_log2,
                 line_info_delta,
                 abs_line_info_delta,
+                debug_info,
             },
         ))
     }
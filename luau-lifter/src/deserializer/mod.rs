@@ -1,6 +1,8 @@
 use nom::{bytes::complete::take, IResult};
 use nom_leb128::leb128_usize;
 
+use crate::op_code_decoder::OpcodeDecoder;
+
 pub mod bytecode;
 pub mod chunk;
 pub mod constant;
@@ -13,8 +15,8 @@ fn parse_string(input: &[u8]) -> IResult<&[u8], Vec<u8>> {
     Ok((input, bytes.to_owned()))
 }
 
-pub fn deserialize(bytecode: &[u8], encode_key: u8) -> Result<bytecode::Bytecode, String> {
-    match bytecode::Bytecode::parse(bytecode, encode_key) {
+pub fn deserialize(bytecode: &[u8], decoder: &OpcodeDecoder) -> Result<bytecode::Bytecode, String> {
+    match bytecode::Bytecode::parse(bytecode, decoder) {
         Ok((_, deserialized_bytecode)) => Ok(deserialized_bytecode),
         Err(err) => Err(err.to_string()),
     }
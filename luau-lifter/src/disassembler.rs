@@ -0,0 +1,329 @@
+use std::fmt::{self, Write};
+
+use itertools::Itertools;
+
+use crate::{
+    deserializer::{bytecode::Bytecode, chunk::Chunk, constant::Constant, function::Function},
+    instruction::Instruction,
+    op_code::OpCode,
+};
+
+/// Produces a textual listing of the bytecode that [`crate::assembler::assemble`] accepts.
+/// Everything after a `;` is an annotation and is ignored by the assembler.
+pub fn disassemble(bytecode: &Bytecode) -> String {
+    let mut output = String::new();
+    match bytecode {
+        Bytecode::Error(msg) => {
+            writeln!(output, "error {}", escape(msg.as_bytes())).unwrap();
+        }
+        Bytecode::Chunk(chunk) => {
+            write_chunk(&mut output, chunk).unwrap();
+        }
+    }
+    output
+}
+
+/// Disassembles a single function of the chunk, or returns `None` if there is no function
+/// `function_id`.
+pub fn disassemble_function(chunk: &Chunk, function_id: usize) -> Option<String> {
+    if function_id >= chunk.functions.len() {
+        return None;
+    }
+    let mut output = String::new();
+    write_function(&mut output, chunk, function_id).unwrap();
+    Some(output)
+}
+
+pub(crate) fn escape(string: &[u8]) -> String {
+    let mut escaped = String::with_capacity(string.len() + 2);
+    escaped.push('"');
+    for &c in string {
+        match c {
+            b'\n' => escaped.push_str(r"\n"),
+            b'\r' => escaped.push_str(r"\r"),
+            b'\t' => escaped.push_str(r"\t"),
+            b'"' => escaped.push_str(r#"\""#),
+            b'\\' => escaped.push_str(r"\\"),
+            b' '..=b'~' => escaped.push(c as char),
+            _ => write!(escaped, "\\x{:02x}", c).unwrap(),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn format_f64(value: f64) -> String {
+    // NaNs don't survive a round trip through their decimal form
+    if value.is_nan() {
+        format!("0x{:016x}", value.to_bits())
+    } else {
+        format!("{:?}", value)
+    }
+}
+
+fn format_f32(value: f32) -> String {
+    if value.is_nan() {
+        format!("0x{:08x}", value.to_bits())
+    } else {
+        format!("{:?}", value)
+    }
+}
+
+fn string(chunk: &Chunk, index: usize) -> Option<String> {
+    index
+        .checked_sub(1)
+        .and_then(|i| chunk.string_table.get(i))
+        .map(|s| escape(s))
+}
+
+fn describe_constant(chunk: &Chunk, function: &Function, index: usize) -> Option<String> {
+    match function.constants.get(index)? {
+        Constant::Nil => Some("nil".to_string()),
+        Constant::Boolean(value) => Some(value.to_string()),
+        Constant::Number(value) => Some(format_f64(*value)),
+        Constant::String(string_index) => string(chunk, *string_index),
+        Constant::Import(import) => describe_import(chunk, function, *import as u32),
+        Constant::Table(keys) => Some(format!("{{{}}}", keys.iter().join(", "))),
+        Constant::Closure(f_id) => Some(format!("function {}", f_id)),
+        Constant::Vector(x, y, z, w) => Some(format!(
+            "vector({}, {}, {}, {})",
+            format_f32(*x),
+            format_f32(*y),
+            format_f32(*z),
+            format_f32(*w)
+        )),
+    }
+}
+
+fn describe_import(chunk: &Chunk, function: &Function, import: u32) -> Option<String> {
+    let import_len = (import >> 30) & 3;
+    (0..import_len)
+        .map(|i| {
+            let constant = ((import >> (20 - i * 10)) & 1023) as usize;
+            match function.constants.get(constant)? {
+                Constant::String(string_index) => chunk
+                    .string_table
+                    .get(string_index.checked_sub(1)?)
+                    .map(|s| String::from_utf8_lossy(s).into_owned()),
+                _ => None,
+            }
+        })
+        .collect::<Option<Vec<_>>>()
+        .map(|path| path.join("."))
+}
+
+fn jump_target(pc: usize, offset: i32) -> String {
+    match (pc as i64 + 1).checked_add(offset as i64) {
+        Some(target) => format!("to {}", target),
+        None => "out of range".to_string(),
+    }
+}
+
+fn annotate(
+    chunk: &Chunk,
+    function: &Function,
+    pc: usize,
+    instruction: &Instruction,
+) -> Option<String> {
+    match *instruction {
+        Instruction::BC {
+            op_code, b, c, aux, ..
+        } => match op_code {
+            OpCode::LOP_GETGLOBAL
+            | OpCode::LOP_SETGLOBAL
+            | OpCode::LOP_GETTABLEKS
+            | OpCode::LOP_SETTABLEKS
+            | OpCode::LOP_NAMECALL
+            | OpCode::LOP_LOADKX
+            | OpCode::LOP_FASTCALL2K => describe_constant(chunk, function, aux as usize),
+            OpCode::LOP_ADDK
+            | OpCode::LOP_SUBK
+            | OpCode::LOP_MULK
+            | OpCode::LOP_DIVK
+            | OpCode::LOP_MODK
+            | OpCode::LOP_POWK
+            | OpCode::LOP_IDIVK
+            | OpCode::LOP_ANDK
+            | OpCode::LOP_ORK
+            | OpCode::LOP_SUBRK
+            | OpCode::LOP_DIVRK => describe_constant(chunk, function, c as usize),
+            OpCode::LOP_GETTABLEN | OpCode::LOP_SETTABLEN => Some(format!("[{}]", c as u32 + 1)),
+            OpCode::LOP_CAPTURE => Some(match instruction {
+                Instruction::BC { a: 0, .. } => format!("value R{}", b),
+                Instruction::BC { a: 1, .. } => format!("ref R{}", b),
+                Instruction::BC { a: 2, .. } => format!("upvalue U{}", b),
+                _ => "unknown capture type".to_string(),
+            }),
+            _ => instruction
+                .jump_offset()
+                .map(|offset| jump_target(pc, offset)),
+        },
+        Instruction::AD {
+            op_code, d, aux, ..
+        } => match op_code {
+            OpCode::LOP_LOADK | OpCode::LOP_DUPTABLE | OpCode::LOP_DUPCLOSURE => {
+                describe_constant(chunk, function, d as usize)
+            }
+            OpCode::LOP_GETIMPORT => describe_import(chunk, function, aux),
+            OpCode::LOP_NEWCLOSURE => function
+                .functions
+                .get(d as usize)
+                .map(|f_id| format!("function {}", f_id)),
+            OpCode::LOP_JUMPXEQKN | OpCode::LOP_JUMPXEQKS => Some(format!(
+                "{}{}, {}",
+                if aux & (1 << 31) != 0 { "not " } else { "" },
+                describe_constant(chunk, function, (aux & ((1 << 24) - 1)) as usize)
+                    .unwrap_or_else(|| "?".to_string()),
+                jump_target(pc, d as i32)
+            )),
+            _ => instruction
+                .jump_offset()
+                .map(|offset| jump_target(pc, offset)),
+        },
+        Instruction::E { .. } => instruction
+            .jump_offset()
+            .map(|offset| jump_target(pc, offset)),
+    }
+}
+
+pub(crate) fn write_instruction(
+    output: &mut impl Write,
+    chunk: &Chunk,
+    function: &Function,
+    pc: usize,
+    instruction: &Instruction,
+) -> fmt::Result {
+    write!(output, "{:>5} {:<14}", pc, instruction.op_code().mnemonic())?;
+    match *instruction {
+        Instruction::BC { a, b, c, .. } => write!(output, " A={} B={} C={}", a, b, c)?,
+        Instruction::AD { a, d, .. } => write!(output, " A={} D={}", a, d)?,
+        Instruction::E { e, .. } => write!(output, " E={}", e)?,
+    }
+    if let Some(aux) = instruction.aux() {
+        write!(output, " AUX=0x{:08x}", aux)?;
+    }
+    if let Some(annotation) = annotate(chunk, function, pc, instruction) {
+        write!(output, " ; {}", annotation)?;
+    }
+    Ok(())
+}
+
+fn write_list_line(
+    output: &mut impl Write,
+    directive: &str,
+    items: impl IntoIterator<Item = impl fmt::Display>,
+) -> fmt::Result {
+    write!(output, "{}", directive)?;
+    for item in items {
+        write!(output, " {}", item)?;
+    }
+    writeln!(output)
+}
+
+fn write_chunk(output: &mut impl Write, chunk: &Chunk) -> fmt::Result {
+    writeln!(output, "version {}", chunk.version)?;
+    writeln!(output, "types_version {}", chunk.types_version)?;
+    if chunk.types_version == 3 {
+        write_list_line(output, "userdata_types", &chunk.userdata_types)?;
+    }
+    for (index, string) in chunk.string_table.iter().enumerate() {
+        writeln!(output, "string {} {}", index + 1, escape(string))?;
+    }
+    for function_id in 0..chunk.functions.len() {
+        writeln!(output)?;
+        write_function(output, chunk, function_id)?;
+    }
+    writeln!(output)?;
+    writeln!(output, "main {}", chunk.main)
+}
+
+fn write_function(output: &mut impl Write, chunk: &Chunk, function_id: usize) -> fmt::Result {
+    let function = &chunk.functions[function_id];
+    write!(output, "function {}", function_id)?;
+    match string(chunk, function.function_name) {
+        Some(name) => writeln!(output, " ; {}", name)?,
+        None => writeln!(output)?,
+    }
+    writeln!(output, "max_stack_size {}", function.max_stack_size)?;
+    writeln!(output, "num_parameters {}", function.num_parameters)?;
+    writeln!(output, "num_upvalues {}", function.num_upvalues)?;
+    writeln!(output, "is_vararg {}", function.is_vararg)?;
+    writeln!(output, "flags {}", function.flags)?;
+    write_list_line(
+        output,
+        "type_info",
+        function.type_info.iter().map(|b| format!("{:02x}", b)),
+    )?;
+    writeln!(output, "line_defined {}", function.line_defined)?;
+    writeln!(output, "function_name {}", function.function_name)?;
+    write_list_line(output, "functions", &function.functions)?;
+    for (index, constant) in function.constants.iter().enumerate() {
+        write!(output, "constant {} ", index)?;
+        match constant {
+            Constant::Nil => write!(output, "nil")?,
+            Constant::Boolean(value) => write!(output, "boolean {}", value)?,
+            Constant::Number(value) => write!(output, "number {}", format_f64(*value))?,
+            Constant::String(string_index) => write!(output, "string {}", string_index)?,
+            Constant::Import(import) => write!(output, "import 0x{:08x}", import)?,
+            Constant::Table(keys) => {
+                write!(output, "table")?;
+                for key in keys {
+                    write!(output, " {}", key)?;
+                }
+            }
+            Constant::Closure(f_id) => write!(output, "closure {}", f_id)?,
+            Constant::Vector(x, y, z, w) => write!(
+                output,
+                "vector {} {} {} {}",
+                format_f32(*x),
+                format_f32(*y),
+                format_f32(*z),
+                format_f32(*w)
+            )?,
+        }
+        match constant {
+            Constant::String(_) | Constant::Import(_) => {
+                if let Some(description) = describe_constant(chunk, function, index) {
+                    write!(output, " ; {}", description)?;
+                }
+            }
+            _ => {}
+        }
+        writeln!(output)?;
+    }
+    writeln!(output, "code")?;
+    let mut iter = function.instructions.iter().enumerate();
+    while let Some((pc, instruction)) = iter.next() {
+        write_instruction(output, chunk, function, pc, instruction)?;
+        writeln!(output)?;
+        if instruction.aux().is_some() {
+            // skip the NOP standing in for the aux word
+            iter.next();
+        }
+    }
+    if let (Some(line_gap_log2), Some(line_info_delta), Some(abs_line_info_delta)) = (
+        function.line_gap_log2,
+        &function.line_info_delta,
+        &function.abs_line_info_delta,
+    ) {
+        writeln!(output, "line_gap_log2 {}", line_gap_log2)?;
+        write_list_line(output, "line_info", line_info_delta)?;
+        write_list_line(output, "abs_line_info", abs_line_info_delta)?;
+    }
+    if let Some(debug_info) = &function.debug_info {
+        writeln!(output, "debug_info")?;
+        for local in &debug_info.locals {
+            write!(
+                output,
+                "local {} {} {} {}",
+                local.name, local.start_pc, local.end_pc, local.register
+            )?;
+            match string(chunk, local.name) {
+                Some(name) => writeln!(output, " ; {}", name)?,
+                None => writeln!(output)?,
+            }
+        }
+        write_list_line(output, "upvalue_names", &debug_info.upvalues)?;
+    }
+    writeln!(output, "end")
+}
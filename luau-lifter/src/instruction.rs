@@ -1,6 +1,6 @@
 use std::convert::TryFrom;
 
-use crate::op_code::OpCode;
+use crate::{op_code::OpCode, op_code_decoder::OpcodeDecoder};
 
 /*
 
@@ -113,9 +113,8 @@ pub enum Instruction {
 }
 
 impl Instruction {
-    pub fn parse(insn: u32, encode_key: u8) -> Result<Instruction, nom::error::ErrorKind> {
-        let op_code = (insn & 0xFF) as u8;
-        let op_code = op_code.wrapping_mul(encode_key);
+    pub fn parse(insn: u32, decoder: &OpcodeDecoder) -> Result<Instruction, nom::error::ErrorKind> {
+        let op_code = decoder.decode((insn & 0xFF) as u8);
         match op_code {
             0
             | 1
@@ -134,7 +133,8 @@ impl Instruction {
             | 70
             | 71..=75
             | 81
-            | 82 => {
+            | 82
+            | 97 => {
                 let (a, b, c) = Self::parse_abc(insn);
 
                 Ok(Self::BC {
@@ -163,14 +163,84 @@ impl Instruction {
                     e,
                 })
             }
-            97 => Ok(Self::BC {
-                op_code: OpCode::try_from(0).unwrap(),
-                a: 0,
-                b: 0,
-                c: 0,
-                aux: 0,
-            }),
-            _ => unreachable!("{}", op_code),
+            _ => Err(nom::error::ErrorKind::Verify),
+        }
+    }
+
+    /// Encodes the instruction word, the inverse of [`Instruction::parse`].
+    /// The auxiliary word, if any, is not included.
+    pub fn encode(&self, decoder: &OpcodeDecoder) -> Result<u32, String> {
+        let (op_code, operands) = match *self {
+            Self::BC {
+                op_code, a, b, c, ..
+            } => (
+                op_code,
+                (a as u32) << 8 | (b as u32) << 16 | (c as u32) << 24,
+            ),
+            Self::AD { op_code, a, d, .. } => (op_code, (a as u32) << 8 | (d as u16 as u32) << 16),
+            Self::E { op_code, e } => (op_code, (e as u32) << 8),
+        };
+        let raw_op_code = decoder
+            .encode(op_code as u8)
+            .ok_or_else(|| format!("{} cannot be encoded with {}", op_code.mnemonic(), decoder))?;
+        Ok(raw_op_code as u32 | operands)
+    }
+
+    pub fn op_code(&self) -> OpCode {
+        match *self {
+            Self::BC { op_code, .. } | Self::AD { op_code, .. } | Self::E { op_code, .. } => {
+                op_code
+            }
+        }
+    }
+
+    pub fn aux(&self) -> Option<u32> {
+        match *self {
+            Self::BC { op_code, aux, .. } | Self::AD { op_code, aux, .. } if op_code.has_aux() => {
+                Some(aux)
+            }
+            _ => None,
+        }
+    }
+
+    /// The offset of the jump target relative to the next instruction, for instructions that jump.
+    pub fn jump_offset(&self) -> Option<i32> {
+        match *self {
+            Self::BC {
+                op_code: OpCode::LOP_LOADB,
+                c,
+                ..
+            } if c != 0 => Some(c as i32),
+            Self::AD {
+                op_code:
+                    OpCode::LOP_JUMP
+                    | OpCode::LOP_JUMPBACK
+                    | OpCode::LOP_JUMPIF
+                    | OpCode::LOP_JUMPIFNOT
+                    | OpCode::LOP_JUMPIFEQ
+                    | OpCode::LOP_JUMPIFLE
+                    | OpCode::LOP_JUMPIFLT
+                    | OpCode::LOP_JUMPIFNOTEQ
+                    | OpCode::LOP_JUMPIFNOTLE
+                    | OpCode::LOP_JUMPIFNOTLT
+                    | OpCode::LOP_JUMPXEQKNIL
+                    | OpCode::LOP_JUMPXEQKB
+                    | OpCode::LOP_JUMPXEQKN
+                    | OpCode::LOP_JUMPXEQKS
+                    | OpCode::LOP_FORNPREP
+                    | OpCode::LOP_FORNLOOP
+                    | OpCode::LOP_FORGPREP
+                    | OpCode::LOP_FORGPREP_INEXT
+                    | OpCode::LOP_FORGPREP_NEXT
+                    | OpCode::LOP_FORGLOOP,
+                d,
+                ..
+            } => Some(d as i32),
+            Self::E {
+                op_code: OpCode::LOP_JUMPX,
+                e,
+            } => Some(e),
+            _ => None,
         }
     }
 
@@ -1,10 +1,19 @@
-mod deserializer;
-mod instruction;
+pub mod assembler;
+pub mod deserializer;
+pub mod disassembler;
+pub mod instruction;
 mod lifter;
-mod op_code;
+pub mod op_code;
+pub mod op_code_decoder;
+pub mod serializer;
+pub mod verifier;
 
 use ast::{
-    local_declarations::LocalDeclarer, name_locals::name_locals, replace_locals::replace_locals,
+    formatter::{FormatOptions, Formatter},
+    local_declarations::LocalDeclarer,
+    name_locals::StatementNamer,
+    replace_locals::replace_locals,
+    report::{self, DecompileReport, FunctionReport, FunctionStatus, PassTiming, Stopwatch},
     Traverse,
 };
 
@@ -21,135 +30,690 @@ use indexmap::IndexMap;
 use lifter::Lifter;
 
 //use cfg_ir::{dot, function::Function, ssa};
-use clap::Parser;
 use parking_lot::Mutex;
 use petgraph::algo::dominators::simple_fast;
 use rayon::prelude::*;
 
-use anyhow::anyhow;
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 use triomphe::Arc;
-use walkdir::WalkDir;
 
-use std::{
-    fs::File,
-    io::{Read, Write},
-    path::Path,
-    time::Instant,
-};
+use std::{collections::VecDeque, io::Write};
 
-use deserializer::bytecode::Bytecode;
+use deserializer::{bytecode::Bytecode, chunk::Chunk};
+use op_code_decoder::OpcodeDecoder;
 
 #[cfg(feature = "dhat-heap")]
 #[global_allocator]
 static ALLOC: dhat::Alloc = dhat::Alloc;
 
-#[derive(Parser, Debug)]
-#[clap(about, version, author)]
-struct Args {
-    paths: Vec<String>,
-    /// Number of threads to use (0 = automatic)
-    #[clap(short, long, default_value_t = 0)]
-    threads: usize,
-    /// op = op * key % 256
-    /// For Roblox client bytecode, use 203
-    #[clap(short, long, default_value_t = 1)]
-    key: u8,
-    #[clap(short, long)]
-    recursive: bool,
-    #[clap(short, long)]
-    verbose: bool,
+/// Limits how long the structuring passes may run for each function. A function that
+/// exceeds its budget is decompiled without SSA instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Budget {
+    /// How many times the passes may run until none of them change anything.
+    pub max_iterations: usize,
+    /// Only checked between iterations, and never on wasm because there is no clock.
+    pub time_limit: Option<std::time::Duration>,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Self {
+            max_iterations: 1000,
+            time_limit: None,
+        }
+    }
+}
+
+pub fn decompile_bytecode(
+    bytecode: &[u8],
+    decoder: &OpcodeDecoder,
+    options: &FormatOptions,
+) -> String {
+    decompile_report(bytecode, decoder, options, &Budget::default()).source
+}
+
+/// Like `decompile_bytecode`, but also describes how each function was decompiled.
+/// Functions are decompiled in parallel on the current rayon pool, so calling this from
+/// `ThreadPool::install` chooses the threads. The output is the same with any number of them.
+pub fn decompile_report(
+    bytecode: &[u8],
+    decoder: &OpcodeDecoder,
+    options: &FormatOptions,
+    budget: &Budget,
+) -> DecompileReport {
+    let mut source = String::new();
+    let mut report = decompile_streaming(bytecode, decoder, options, budget, |event| {
+        if let Event::Output(output) = event {
+            source.push_str(output);
+        }
+    });
+    report.source = source;
+    report
+}
+
+/// What `decompile_streaming` reports while it runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event<'a> {
+    /// Another function was decompiled, `finished` of `total` so far.
+    Progress { finished: usize, total: usize },
+    /// The next part of the output, which comes a top-level statement at a time as soon as
+    /// every function in the statement is decompiled.
+    Output(&'a str),
+}
+
+/// Like `decompile_report`, but hands the output to `on_event` as it is formatted instead of
+/// keeping it in `source`. `on_event` is called from the threads decompiling functions, but
+/// never from two at once.
+pub fn decompile_streaming(
+    bytecode: &[u8],
+    decoder: &OpcodeDecoder,
+    options: &FormatOptions,
+    budget: &Budget,
+    mut on_event: impl FnMut(Event) + Send,
+) -> DecompileReport {
+    let mut decompilation = Decompilation::new(bytecode, decoder, options, budget, &mut on_event);
+    decompilation.decompile_in_parallel(&mut on_event);
+    decompilation.finish()
+}
+
+/// Like `decompile_streaming`, but writes the output to `writer`.
+pub fn decompile_to_writer(
+    bytecode: &[u8],
+    decoder: &OpcodeDecoder,
+    options: &FormatOptions,
+    budget: &Budget,
+    writer: &mut (impl Write + Send),
+) -> std::io::Result<DecompileReport> {
+    let mut result = Ok(());
+    let report = decompile_streaming(bytecode, decoder, options, budget, |event| {
+        if let (Event::Output(output), Ok(())) = (event, &result) {
+            result = writer.write_all(output.as_bytes());
+        }
+    });
+    result.map(|()| report)
+}
+
+// a function that was lifted, along with the `ast::Function` it is decompiled into, the
+// upvalues it captures, the functions of its closures and where its local numbering continues
+type Lifted = (
+    Arc<Mutex<ast::Function>>,
+    Function,
+    Vec<ast::RcLocal>,
+    Vec<(ByAddress<Arc<Mutex<ast::Function>>>, usize)>,
+    ast::LocalIds,
+);
+
+/// A decompilation that is driven a function at a time with `step` instead of in parallel,
+/// for callers that want to hand on the output between functions, e.g. by yielding to an
+/// event loop. The output is the same as `decompile_streaming`'s.
+pub struct Decompilation {
+    budget: Budget,
+    report: DecompileReport,
+    // missing when the chunk can't be decompiled at all
+    chunk: Option<Chunk>,
+    lifted: VecDeque<Lifted>,
+    output: Option<Output>,
 }
 
-pub fn decompile_bytecode(bytecode: &[u8], encode_key: u8) -> String {
-    let chunk = deserializer::deserialize(bytecode, encode_key).unwrap();
-    match chunk {
-        Bytecode::Error(msg) => msg,
-        Bytecode::Chunk(chunk) => {
-            let mut lifted = Vec::new();
-            let mut stack = vec![(Arc::<Mutex<ast::Function>>::default(), chunk.main)];
-            while let Some((ast_func, func_id)) = stack.pop() {
-                let (function, upvalues, child_functions) =
-                    Lifter::lift(&chunk.functions, &chunk.string_table, func_id);
-                lifted.push((ast_func, function, upvalues));
-                stack.extend(child_functions.into_iter().map(|(a, f)| (a.0, f)));
+impl Decompilation {
+    /// Deserializes, verifies and lifts the bytecode, and outputs why if that fails.
+    pub fn new(
+        bytecode: &[u8],
+        decoder: &OpcodeDecoder,
+        options: &FormatOptions,
+        budget: &Budget,
+        mut on_event: impl FnMut(Event),
+    ) -> Self {
+        let mut this = Self {
+            budget: budget.clone(),
+            report: DecompileReport {
+                key: match decoder {
+                    OpcodeDecoder::Multiplicative(key) => Some(*key),
+                    OpcodeDecoder::Table(_) => None,
+                },
+                ..Default::default()
+            },
+            chunk: None,
+            lifted: VecDeque::new(),
+            output: None,
+        };
+        let chunk = match report::time(&mut this.report.timings, "deserialize", || {
+            deserializer::deserialize(bytecode, decoder)
+        }) {
+            Ok(Bytecode::Chunk(chunk)) => chunk,
+            Ok(Bytecode::Error(msg)) => {
+                this.report.version = Some(0);
+                on_event(Event::Output(&msg));
+                this.report.error = Some(msg);
+                return this;
             }
+            Err(err) => {
+                let error = format!("failed to deserialize bytecode: {}", err);
+                on_event(Event::Output(&ast::Comment::new(error.clone()).to_string()));
+                this.report.error = Some(error);
+                return this;
+            }
+        };
+        this.report.version = Some(chunk.version);
+        let problems = report::time(&mut this.report.timings, "verify", || {
+            verifier::verify(&chunk)
+        });
+        // only problems outside of every function reject the whole chunk
+        if problems.iter().any(|problem| problem.function_id.is_none()) {
+            this.report.error = Some("bytecode failed verification".to_string());
+            on_event(Event::Output(
+                &ast::Block(
+                    std::iter::once("bytecode failed verification".to_string())
+                        .chain(problems.iter().map(|problem| problem.to_string()))
+                        .map(|text| ast::Comment::new(text).into())
+                        .collect(),
+                )
+                .to_string(),
+            ));
+            return this;
+        }
+        let mut invalid = FxHashMap::<_, Vec<_>>::default();
+        for problem in problems {
+            invalid
+                .entry(problem.function_id.unwrap())
+                .or_default()
+                .push(problem);
+        }
 
-            let (main, ..) = lifted.first().unwrap().clone();
-            let mut upvalues = lifted
-                .into_iter()
-                .map(|(ast_function, function, upvalues_in)| {
-                    use std::{backtrace::Backtrace, cell::RefCell, fmt::Write, panic};
-
-                    thread_local! {
-                        static BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
-                    }
-
-                    let function_id = function.id;
-                    let mut args = std::panic::AssertUnwindSafe(Some((
-                        ast_function.clone(),
-                        function,
-                        upvalues_in,
-                    )));
-
-                    let prev_hook = panic::take_hook();
-                    panic::set_hook(Box::new(|_| {
-                        let trace = Backtrace::capture();
-                        BACKTRACE.with(move |b| b.borrow_mut().replace(trace));
-                    }));
-                    let result = panic::catch_unwind(move || {
-                        let (ast_function, function, upvalues_in) = args.take().unwrap();
-                        decompile_function(ast_function, function, upvalues_in)
-                    });
-                    panic::set_hook(prev_hook);
-
-                    match result {
-                        Ok(r) => r,
-                        Err(e) => {
-                            let panic_information = match e.downcast::<String>() {
-                                Ok(v) => *v,
-                                Err(e) => match e.downcast::<&str>() {
-                                    Ok(v) => v.to_string(),
-                                    _ => "Unknown Source of Error".to_owned(),
-                                },
-                            };
-
-                            let mut message = String::new();
-                            writeln!(message, "failed to decompile").unwrap();
-                            // writeln!(message, "function {} panicked at '{}'", function_id, panic_information).unwrap();
-                            // if let Some(backtrace) = BACKTRACE.with(|b| b.borrow_mut().take()) {
-                            //     write!(message, "stack backtrace:\n{}", backtrace).unwrap();
-                            // }
-
-                            ast_function.lock().body.extend(
-                                message
-                                    .trim_end()
-                                    .split('\n')
-                                    .map(|s| ast::Comment::new(s.to_string()).into()),
-                            );
-                            (ByAddress(ast_function), Vec::new())
-                        }
-                    }
+        let lift_stopwatch = report::Stopwatch::start();
+        let mut lifted = VecDeque::new();
+        let mut rejected = Vec::new();
+        let mut stack = vec![(Arc::<Mutex<ast::Function>>::default(), chunk.main)];
+        while let Some((ast_func, func_id)) = stack.pop() {
+            let func = &chunk.functions[func_id];
+            ast_func.lock().metadata = Some(ast::FunctionMetadata {
+                id: Some(func_id),
+                line_defined: func.line_defined,
+                num_parameters: func.num_parameters,
+                num_upvalues: func.num_upvalues,
+                max_stack_size: func.max_stack_size,
+                bytecode_size: func.instructions.len(),
+            });
+            // the lifter relies on what failed verification, so the function isn't lifted
+            // and neither are the closures in it
+            if let Some(problems) = invalid.get(&func_id) {
+                rejected.push(reject_function(&chunk, &ast_func, func_id, problems));
+                continue;
+            }
+            let mut local_ids = ast::LocalIds::new(func_id);
+            let (function, upvalues, child_functions) = ast::with_local_ids(&mut local_ids, || {
+                Lifter::lift(&chunk.functions, &chunk.string_table, func_id)
+            });
+            // the first closure is decompiled first, so that the output can start early
+            stack.extend(child_functions.iter().rev().map(|(a, f)| (a.0.clone(), *f)));
+            lifted.push_back((ast_func, function, upvalues, child_functions, local_ids));
+        }
+        this.report.timings.push(PassTiming {
+            pass: "lift".to_string(),
+            milliseconds: lift_stopwatch.elapsed().as_secs_f64() * 1000.0,
+        });
+
+        let main = match lifted.front() {
+            Some((main, ..)) => main.clone(),
+            None => rejected.first().unwrap().0.clone(),
+        };
+        let mut output = Output {
+            main: ByAddress(main),
+            options: options.clone(),
+            total: lifted.len() + rejected.len(),
+            children: lifted
+                .iter()
+                .map(|(ast_function, _, _, child_functions, _)| {
+                    (
+                        ByAddress(ast_function.clone()),
+                        child_functions.iter().map(|(f, _)| f.clone()).collect(),
+                    )
                 })
-                .collect::<FxHashMap<_, _>>();
+                .collect(),
+            upvalues: FxHashMap::default(),
+            globals: referenced_globals(&mut lifted),
+            namer: None,
+            next_statement: 0,
+            timings: Vec::new(),
+        };
+        for (ast_function, function_report) in rejected {
+            output.upvalues.insert(ByAddress(ast_function), Vec::new());
+            this.report.functions.push(function_report);
+        }
+        output.flush(&mut on_event);
+        this.chunk = Some(chunk);
+        this.lifted = lifted;
+        this.output = Some(output);
+        this
+    }
+
+    /// Decompiles the next function and outputs the top-level statements that were waiting
+    /// for it. Returns whether there was a function left to decompile.
+    pub fn step(&mut self, mut on_event: impl FnMut(Event)) -> bool {
+        let (Some(chunk), Some(lifted)) = (&self.chunk, self.lifted.pop_front()) else {
+            return false;
+        };
+        let (function, function_report) = decompile_lifted(chunk, lifted, &self.budget);
+        self.report.functions.push(function_report);
+        self.output
+            .as_mut()
+            .unwrap()
+            .finish(function, &mut on_event);
+        true
+    }
+
+    // every function is decompiled on its own with its own local numbering, so the output
+    // doesn't depend on how many threads the current rayon pool has
+    fn decompile_in_parallel(&mut self, on_event: &mut (dyn FnMut(Event) + Send)) {
+        let Some(chunk) = &self.chunk else {
+            return;
+        };
+        let budget = &self.budget;
+        let output = Mutex::new((self.output.as_mut().unwrap(), on_event));
+        let function_reports = std::mem::take(&mut self.lifted)
+            .into_par_iter()
+            .map(|lifted| {
+                let (function, function_report) = decompile_lifted(chunk, lifted, budget);
+                let mut output = output.lock();
+                let (output, on_event) = &mut *output;
+                output.finish(function, *on_event);
+                function_report
+            })
+            .collect::<Vec<_>>();
+        self.report.functions.extend(function_reports);
+    }
+
+    /// Describes how every function was decompiled, of which there should be none left.
+    pub fn finish(mut self) -> DecompileReport {
+        if let Some(output) = self.output {
+            self.report.timings.extend(output.timings);
+        }
+        self.report
+            .functions
+            .sort_by_key(|function_report| function_report.id);
+        self.report.summarize();
+        self.report
+    }
+}
+
+// the globals that the lifted functions reference, which no local may be named after
+fn referenced_globals(lifted: &mut VecDeque<Lifted>) -> FxHashSet<String> {
+    let mut globals = FxHashSet::default();
+    for (_, function, ..) in lifted {
+        for statement in function.blocks_mut().flat_map(|block| block.iter_mut()) {
+            statement.post_traverse_values(&mut |value| -> Option<()> {
+                if let itertools::Either::Left(ast::LValue::Global(global))
+                | itertools::Either::Right(ast::RValue::Global(global)) = value
+                {
+                    globals.insert(String::from_utf8_lossy(&global.0).into_owned());
+                }
+                None
+            });
+        }
+    }
+    globals
+}
+
+type SharedFunction = ByAddress<Arc<Mutex<ast::Function>>>;
+
+// hands on the main function a top-level statement at a time, as soon as every function in the
+// statement is decompiled, since that is all that linking, naming and formatting it needs
+struct Output {
+    main: SharedFunction,
+    options: FormatOptions,
+    total: usize,
+    // the functions of the closures in each function
+    children: FxHashMap<SharedFunction, Vec<SharedFunction>>,
+    // the upvalues of every function that is finished
+    upvalues: FxHashMap<SharedFunction, Vec<ast::RcLocal>>,
+    // until the namer is created
+    globals: FxHashSet<String>,
+    namer: Option<StatementNamer>,
+    next_statement: usize,
+    timings: Vec<PassTiming>,
+}
+
+impl Output {
+    fn finish(
+        &mut self,
+        (function, upvalues): DecompiledFunction,
+        on_event: &mut dyn FnMut(Event),
+    ) {
+        self.upvalues.insert(function, upvalues);
+        on_event(Event::Progress {
+            finished: self.upvalues.len(),
+            total: self.total,
+        });
+        self.flush(on_event);
+    }
+
+    fn is_finished(&self, statement: &mut ast::Statement) -> bool {
+        let mut functions = Vec::new();
+        closures(statement, &mut functions);
+        while let Some(function) = functions.pop() {
+            if !self.upvalues.contains_key(&function) {
+                return false;
+            }
+            functions.extend(self.children.get(&function).into_iter().flatten().cloned());
+        }
+        true
+    }
+
+    fn flush(&mut self, on_event: &mut dyn FnMut(Event)) {
+        if !self.upvalues.contains_key(&self.main) {
+            return;
+        }
+        let mut main = self.main.lock();
+        if self.namer.is_none() {
+            if let Some(header) = main.header().filter(|_| self.options.function_headers) {
+                main.body.insert(0, ast::Comment::new(header).into());
+            }
+            self.namer = Some(StatementNamer::new(
+                &mut main.body,
+                true,
+                std::mem::take(&mut self.globals),
+            ));
+        }
+        while self.next_statement < main.body.len()
+            && self.is_finished(&mut main.body[self.next_statement])
+        {
+            let statement = &mut main.body[self.next_statement];
+            report::time(&mut self.timings, "link_upvalues", || {
+                link_statement_upvalues(statement, &mut self.upvalues)
+            });
+            let namer = self.namer.as_mut().unwrap();
+            report::time(&mut self.timings, "name_locals", || {
+                namer.name_statement(statement)
+            });
+            let output = report::time(&mut self.timings, "format", || {
+                Formatter::format_top_level_statement(
+                    &main.body,
+                    self.next_statement,
+                    self.options.clone(),
+                )
+                .unwrap()
+            });
+            on_event(Event::Output(&output));
+            self.next_statement += 1;
+        }
+    }
+}
+
+// decompiles a function at each degradation level until one of them works
+fn decompile_lifted(
+    chunk: &Chunk,
+    (ast_function, function, upvalues_in, child_functions, mut local_ids): Lifted,
+    budget: &Budget,
+) -> (DecompiledFunction, FunctionReport) {
+    use std::fmt::Write;
+
+    let function_id = function.id;
+    let mut function_report = {
+        let ast_function = ast_function.lock();
+        FunctionReport {
+            id: function_id,
+            name: ast_function.name.clone(),
+            line_defined: ast_function
+                .metadata
+                .as_ref()
+                .map_or(0, |metadata| metadata.line_defined),
+            status: FunctionStatus::Ok,
+            gotos: 0,
+            labels: 0,
+            timings: Vec::new(),
+            timed_out: false,
+        }
+    };
+    let parameters = function.parameters.clone();
+    let is_variadic = function.is_variadic;
+
+    // retry with fewer passes until one of them doesn't panic, or skip straight
+    // to the passes without a budget once it has been exceeded
+    let stopwatch = Stopwatch::start();
+    let mut failure = None;
+    let mut exceeded_budget = false;
+    let mut result = None;
+    // the passes change the locals in place, so every retry lifts the function
+    // again and decompiles into a new `ast::Function`, which is only moved into
+    // the shared one once it succeeds
+    let mut lifted = Some((function, upvalues_in));
+    for level in DegradationLevel::ALL {
+        if exceeded_budget && level != DegradationLevel::NoSsa {
+            continue;
+        }
+        let lifted = lifted.take();
+        match report::catch_panic(|| {
+            let mut timings = Vec::new();
+            let r = ast::with_local_ids(&mut local_ids, || {
+                let (function, upvalues_in) = lifted.unwrap_or_else(|| {
+                    Lifter::relift(
+                        &chunk.functions,
+                        &chunk.string_table,
+                        function_id,
+                        &child_functions,
+                    )
+                });
+                decompile_function(
+                    Arc::default(),
+                    function,
+                    upvalues_in,
+                    level,
+                    budget,
+                    &stopwatch,
+                    &mut timings,
+                )
+            });
+            (r, timings)
+        }) {
+            Ok((Ok((attempt, upvalues_in)), timings)) => {
+                let attempt = Arc::try_unwrap(attempt.0).unwrap().into_inner();
+                let mut shared = ast_function.lock();
+                shared.body = attempt.body;
+                shared.parameters = attempt.parameters;
+                shared.is_variadic = attempt.is_variadic;
+                drop(shared);
+                function_report.timings = timings;
+                result = Some(((ByAddress(ast_function.clone()), upvalues_in), level));
+                break;
+            }
+            Ok((Err(exceeded), _)) => {
+                exceeded_budget = true;
+                function_report.timed_out |= matches!(exceeded, Exceeded::Time(_));
+                failure.get_or_insert((exceeded.to_string(), None));
+            }
+            Err(panic) => {
+                failure.get_or_insert(panic);
+            }
+        }
+    }
+
+    if let Some((r, _)) = &result {
+        function_report.count_unstructured(&r.0.lock().body);
+    }
+    let r = match result {
+        Some((r, DegradationLevel::Full)) => r,
+        Some((r, level)) => {
+            let (reason, site) = failure.unwrap();
+            r.0.lock().body.insert(
+                0,
+                ast::Comment::new(format!("decompiled {} after it {}", level, reason)).into(),
+            );
+            function_report.status = FunctionStatus::Degraded {
+                level: level.to_string(),
+                reason,
+                site,
+            };
+            r
+        }
+        None => {
+            let (reason, site) = failure.unwrap();
+            let mut message = String::new();
+            writeln!(message, "failed to decompile").unwrap();
+            writeln!(message, "function {} {}", function_id, reason).unwrap();
+            function_report.status = FunctionStatus::Failed { reason, site };
+
+            // keep the signature so that callers still make sense and show
+            // what we couldn't decompile
+            let mut function = ast_function.lock();
+            function.parameters = parameters;
+            function.is_variadic = is_variadic;
+            function.body.extend(
+                message
+                    .trim_end()
+                    .split('\n')
+                    .map(|s| ast::Comment::new(s.to_string()).into()),
+            );
+            function.body.push(
+                ast::Comment::new(
+                    disassembler::disassemble_function(chunk, function_id)
+                        .unwrap()
+                        .trim_end()
+                        .to_string(),
+                )
+                .into(),
+            );
+            drop(function);
+            (ByAddress(ast_function), Vec::new())
+        }
+    };
+    (r, function_report)
+}
+
+/// Keeps the signature of a function that failed verification, so that callers still make
+/// sense, and shows the problems and its disassembly instead of the body.
+fn reject_function(
+    chunk: &Chunk,
+    ast_function: &Arc<Mutex<ast::Function>>,
+    function_id: usize,
+    problems: &[verifier::Problem],
+) -> (Arc<Mutex<ast::Function>>, FunctionReport) {
+    let function = &chunk.functions[function_id];
+    let mut ast_function_guard = ast_function.lock();
+    ast_function_guard.parameters =
+        ast::with_local_ids(&mut ast::LocalIds::new(function_id), || {
+            (0..function.num_parameters)
+                .map(|_| ast::RcLocal::default())
+                .collect()
+        });
+    ast_function_guard.is_variadic = function.is_vararg;
+    ast_function_guard.body.extend(
+        std::iter::once("failed verification".to_string())
+            .chain(problems.iter().map(|problem| problem.to_string()))
+            .chain(disassembler::disassemble_function(chunk, function_id))
+            .map(|text| ast::Comment::new(text.trim_end().to_string()).into()),
+    );
+    let function_report = FunctionReport {
+        id: function_id,
+        name: ast_function_guard.name.clone(),
+        line_defined: function.line_defined,
+        status: FunctionStatus::Failed {
+            reason: format!(
+                "its bytecode failed verification ({})",
+                problems
+                    .iter()
+                    .map(|problem| match problem.pc {
+                        Some(pc) => format!("pc {}: {}", pc, problem.message),
+                        None => problem.message.clone(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            ),
+            site: None,
+        },
+        gotos: 0,
+        labels: 0,
+        timings: Vec::new(),
+        timed_out: false,
+    };
+    drop(ast_function_guard);
+    (ast_function.clone(), function_report)
+}
+
+/// Which passes `decompile_function` runs, from all of them to as few as possible.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum DegradationLevel {
+    Full,
+    NoInlining,
+    /// Neither inlining nor structuring conditionals and jumps in SSA form.
+    NoStructuring,
+    /// Register-level code with gotos.
+    NoSsa,
+}
+
+impl DegradationLevel {
+    const ALL: [Self; 4] = [
+        Self::Full,
+        Self::NoInlining,
+        Self::NoStructuring,
+        Self::NoSsa,
+    ];
+}
 
-            let main = ByAddress(main);
-            upvalues.remove(&main);
-            let mut body = Arc::try_unwrap(main.0).unwrap().into_inner().body;
-            link_upvalues(&mut body, &mut upvalues);
-            name_locals(&mut body, true);
-            body.to_string()
+impl std::fmt::Display for DegradationLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let description = match self {
+            Self::Full => "with all passes",
+            Self::NoInlining => "without inlining",
+            Self::NoStructuring => "without inlining or structuring",
+            Self::NoSsa => "without SSA",
+        };
+        write!(f, "{} (degradation level {})", description, *self as u8)
+    }
+}
+
+/// Which part of the `Budget` a function ran out of.
+enum Exceeded {
+    Iterations(usize),
+    Time(std::time::Duration),
+}
+
+impl std::fmt::Display for Exceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Iterations(max_iterations) => write!(
+                f,
+                "didn't settle within the iteration limit of {}",
+                max_iterations
+            ),
+            Self::Time(time_limit) => write!(f, "took longer than {:?}", time_limit),
         }
     }
 }
 
+// the function and the upvalues it captures
+type DecompiledFunction = (ByAddress<Arc<Mutex<ast::Function>>>, Vec<ast::RcLocal>);
+
+/// Fails with the reason if the structuring passes exceed `budget`, which the
+/// `NoSsa` level doesn't run.
 fn decompile_function(
     ast_function: Arc<Mutex<ast::Function>>,
     mut function: Function,
     upvalues_in: Vec<ast::RcLocal>,
-) -> (ByAddress<Arc<Mutex<ast::Function>>>, Vec<ast::RcLocal>) {
+    level: DegradationLevel,
+    budget: &Budget,
+    stopwatch: &Stopwatch,
+    timings: &mut Vec<PassTiming>,
+) -> Result<DecompiledFunction, Exceeded> {
+    if level == DegradationLevel::NoSsa {
+        let params = function.parameters.clone();
+        let is_variadic = function.is_variadic;
+        let block = report::time(timings, "lift_unstructured", || {
+            restructure::lift_unstructured(function, &upvalues_in.iter().cloned().collect())
+        });
+        {
+            let mut ast_function = ast_function.lock();
+            ast_function.body = block;
+            ast_function.parameters = params;
+            ast_function.is_variadic = is_variadic;
+        }
+        return Ok((ByAddress(ast_function), upvalues_in));
+    }
+
     let (local_count, local_groups, upvalue_in_groups, upvalue_passed_groups) =
-        cfg::ssa::construct(&mut function, &upvalues_in);
+        report::time(timings, "ssa_construct", || {
+            cfg::ssa::construct(&mut function, &upvalues_in)
+        });
     let upvalue_to_group = upvalue_in_groups
         .into_iter()
         .chain(
@@ -173,15 +737,37 @@ fn decompile_function(
     // etc.
     // the macro could also maybe generate an optimal ordering?
     let mut changed = true;
+    let mut iterations = 0;
     while changed {
         changed = false;
+        iterations += 1;
+        if iterations > budget.max_iterations {
+            return Err(Exceeded::Iterations(budget.max_iterations));
+        }
+        if let Some(time_limit) = budget
+            .time_limit
+            .filter(|&time_limit| stopwatch.elapsed() > time_limit)
+        {
+            return Err(Exceeded::Time(time_limit));
+        }
 
-        let dominators = simple_fast(function.graph(), function.entry().unwrap());
-        changed |= structure_jumps(&mut function, &dominators);
+        if level < DegradationLevel::NoStructuring {
+            changed |= report::time(timings, "structure_jumps", || {
+                let dominators = simple_fast(function.graph(), function.entry().unwrap());
+                structure_jumps(&mut function, &dominators)
+            });
+        }
 
-        ssa::inline::inline(&mut function, &local_to_group, &upvalue_to_group);
+        if level < DegradationLevel::NoInlining {
+            report::time(timings, "inline", || {
+                ssa::inline::inline(&mut function, &local_to_group, &upvalue_to_group)
+            });
+        }
 
-        if structure_conditionals(&mut function)
+        if level < DegradationLevel::NoStructuring
+            && report::time(timings, "structure_conditionals", || {
+                structure_conditionals(&mut function)
+            })
         // || {
         //     let post_dominators = post_dominators(function.graph_mut());
         //     structure_for_loops(&mut function, &dominators, &post_dominators)
@@ -191,30 +777,37 @@ fn decompile_function(
         {
             changed = true;
         }
-        let mut local_map = FxHashMap::default();
-        // TODO: loop until returns false?
-        if ssa::construct::remove_unnecessary_params(&mut function, &mut local_map) {
-            changed = true;
-        }
-        ssa::construct::apply_local_map(&mut function, local_map);
+        report::time(timings, "remove_unnecessary_params", || {
+            let mut local_map = FxHashMap::default();
+            // TODO: loop until returns false?
+            if ssa::construct::remove_unnecessary_params(&mut function, &mut local_map) {
+                changed = true;
+            }
+            ssa::construct::apply_local_map(&mut function, local_map);
+        });
     }
     // cfg::dot::render_to(&function, &mut std::io::stdout()).unwrap();
-    ssa::Destructor::new(
-        &mut function,
-        upvalue_to_group,
-        upvalues_in.iter().cloned().collect(),
-        local_count,
-    )
-    .destruct();
+    report::time(timings, "ssa_destruct", || {
+        ssa::Destructor::new(
+            &mut function,
+            upvalue_to_group,
+            upvalues_in.iter().cloned().collect(),
+            local_count,
+        )
+        .destruct()
+    });
 
     let params = std::mem::take(&mut function.parameters);
     let is_variadic = function.is_variadic;
-    let block = Arc::new(restructure::lift(function).into());
-    LocalDeclarer::default().declare_locals(
-        // TODO: why does block.clone() not work?
-        Arc::clone(&block),
-        &upvalues_in.iter().chain(params.iter()).cloned().collect(),
-    );
+    let block =
+        Arc::new(report::time(timings, "restructure", || restructure::lift(function)).into());
+    report::time(timings, "declare_locals", || {
+        LocalDeclarer::default().declare_locals(
+            // TODO: why does block.clone() not work?
+            Arc::clone(&block),
+            &upvalues_in.iter().chain(params.iter()).cloned().collect(),
+        )
+    });
 
     {
         let mut ast_function = ast_function.lock();
@@ -222,7 +815,7 @@ fn decompile_function(
         ast_function.parameters = params;
         ast_function.is_variadic = is_variadic;
     }
-    (ByAddress(ast_function), upvalues_in)
+    Ok((ByAddress(ast_function), upvalues_in))
 }
 
 fn link_upvalues(
@@ -230,46 +823,78 @@ fn link_upvalues(
     upvalues: &mut FxHashMap<ByAddress<Arc<Mutex<ast::Function>>>, Vec<ast::RcLocal>>,
 ) {
     for stat in &mut body.0 {
-        stat.traverse_rvalues(&mut |rvalue| {
-            if let ast::RValue::Closure(closure) = rvalue {
-                let old_upvalues = &upvalues[&closure.function];
-                let mut function = closure.function.lock();
-                // TODO: inefficient, try constructing a map of all up -> new up first
-                // and then call replace_locals on main body
-                let mut local_map =
-                    FxHashMap::with_capacity_and_hasher(old_upvalues.len(), Default::default());
-                for (old, new) in
-                    old_upvalues
-                        .iter()
-                        .zip(closure.upvalues.iter().map(|u| match u {
-                            ast::Upvalue::Copy(l) | ast::Upvalue::Ref(l) => l,
-                        }))
-                {
-                    // println!("{} -> {}", old, new);
-                    local_map.insert(old.clone(), new.clone());
-                }
-                link_upvalues(&mut function.body, upvalues);
-                replace_locals(&mut function.body, &local_map);
-            }
-        });
-        match stat {
-            ast::Statement::If(r#if) => {
-                link_upvalues(&mut r#if.then_block.lock(), upvalues);
-                link_upvalues(&mut r#if.else_block.lock(), upvalues);
-            }
-            ast::Statement::While(r#while) => {
-                link_upvalues(&mut r#while.block.lock(), upvalues);
-            }
-            ast::Statement::Repeat(repeat) => {
-                link_upvalues(&mut repeat.block.lock(), upvalues);
-            }
-            ast::Statement::NumericFor(numeric_for) => {
-                link_upvalues(&mut numeric_for.block.lock(), upvalues);
-            }
-            ast::Statement::GenericFor(generic_for) => {
-                link_upvalues(&mut generic_for.block.lock(), upvalues);
+        link_statement_upvalues(stat, upvalues);
+    }
+}
+
+fn link_statement_upvalues(
+    stat: &mut ast::Statement,
+    upvalues: &mut FxHashMap<ByAddress<Arc<Mutex<ast::Function>>>, Vec<ast::RcLocal>>,
+) {
+    stat.traverse_rvalues(&mut |rvalue| {
+        if let ast::RValue::Closure(closure) = rvalue {
+            let old_upvalues = &upvalues[&closure.function];
+            let mut function = closure.function.lock();
+            // TODO: inefficient, try constructing a map of all up -> new up first
+            // and then call replace_locals on main body
+            let mut local_map =
+                FxHashMap::with_capacity_and_hasher(old_upvalues.len(), Default::default());
+            for (old, new) in old_upvalues
+                .iter()
+                .zip(closure.upvalues.iter().map(|u| match u {
+                    ast::Upvalue::Copy(l) | ast::Upvalue::Ref(l) => l,
+                }))
+            {
+                // println!("{} -> {}", old, new);
+                local_map.insert(old.clone(), new.clone());
             }
-            _ => {}
+            link_upvalues(&mut function.body, upvalues);
+            replace_locals(&mut function.body, &local_map);
+        }
+    });
+    match stat {
+        ast::Statement::If(r#if) => {
+            link_upvalues(&mut r#if.then_block.lock(), upvalues);
+            link_upvalues(&mut r#if.else_block.lock(), upvalues);
+        }
+        ast::Statement::While(r#while) => {
+            link_upvalues(&mut r#while.block.lock(), upvalues);
+        }
+        ast::Statement::Repeat(repeat) => {
+            link_upvalues(&mut repeat.block.lock(), upvalues);
+        }
+        ast::Statement::Do(r#do) => {
+            link_upvalues(&mut r#do.block.lock(), upvalues);
+        }
+        ast::Statement::NumericFor(numeric_for) => {
+            link_upvalues(&mut numeric_for.block.lock(), upvalues);
+        }
+        ast::Statement::GenericFor(generic_for) => {
+            link_upvalues(&mut generic_for.block.lock(), upvalues);
+        }
+        _ => {}
+    }
+}
+
+// the functions of the closures in `stat`, but not of the closures nested in them
+fn closures(stat: &mut ast::Statement, functions: &mut Vec<SharedFunction>) {
+    stat.traverse_rvalues(&mut |rvalue| {
+        if let ast::RValue::Closure(closure) = rvalue {
+            functions.push(closure.function.clone());
+        }
+    });
+    let blocks = match stat {
+        ast::Statement::If(r#if) => vec![r#if.then_block.clone(), r#if.else_block.clone()],
+        ast::Statement::While(r#while) => vec![r#while.block.clone()],
+        ast::Statement::Repeat(repeat) => vec![repeat.block.clone()],
+        ast::Statement::Do(r#do) => vec![r#do.block.clone()],
+        ast::Statement::NumericFor(numeric_for) => vec![numeric_for.block.clone()],
+        ast::Statement::GenericFor(generic_for) => vec![generic_for.block.clone()],
+        _ => Vec::new(),
+    };
+    for block in blocks {
+        for stat in block.lock().iter_mut() {
+            closures(stat, functions);
         }
     }
 }
@@ -27,7 +27,10 @@ pub struct Lifter<'a> {
     string_table: &'a Vec<Vec<u8>>,
     blocks: FxHashMap<usize, NodeIndex>,
     function: Function,
-    child_functions: FxHashMap<ByAddress<Arc<Mutex<ast::Function>>>, usize>,
+    // in the order they are created so that they are lifted in a deterministic order
+    child_functions: Vec<(ByAddress<Arc<Mutex<ast::Function>>>, usize)>,
+    // the child functions of an earlier lift of the same function, in the same order
+    previous_child_functions: &'a [(ByAddress<Arc<Mutex<ast::Function>>>, usize)],
     register_map: FxHashMap<usize, ast::RcLocal>,
     constant_map: FxHashMap<usize, ast::Literal>,
     current_node: Option<NodeIndex>,
@@ -42,14 +45,42 @@ impl<'a> Lifter<'a> {
     ) -> (
         Function,
         Vec<ast::RcLocal>,
-        FxHashMap<ByAddress<Arc<Mutex<ast::Function>>>, usize>,
+        Vec<(ByAddress<Arc<Mutex<ast::Function>>>, usize)>,
+    ) {
+        Self::lift_with_child_functions(f_list, str_list, function_id, &[])
+    }
+
+    /// Lifts a function again with new locals, but with the closures in it referring to
+    /// `child_functions` from the first lift, so that they are still linked to the
+    /// functions that are decompiled for them.
+    pub fn relift(
+        f_list: &'a Vec<BytecodeFunction>,
+        str_list: &'a Vec<Vec<u8>>,
+        function_id: usize,
+        child_functions: &'a [(ByAddress<Arc<Mutex<ast::Function>>>, usize)],
+    ) -> (Function, Vec<ast::RcLocal>) {
+        let (function, upvalues, _) =
+            Self::lift_with_child_functions(f_list, str_list, function_id, child_functions);
+        (function, upvalues)
+    }
+
+    fn lift_with_child_functions(
+        f_list: &'a Vec<BytecodeFunction>,
+        str_list: &'a Vec<Vec<u8>>,
+        function_id: usize,
+        previous_child_functions: &'a [(ByAddress<Arc<Mutex<ast::Function>>>, usize)],
+    ) -> (
+        Function,
+        Vec<ast::RcLocal>,
+        Vec<(ByAddress<Arc<Mutex<ast::Function>>>, usize)>,
     ) {
         let mut context = Self {
             function_list: f_list,
             string_table: str_list,
             blocks: FxHashMap::default(),
             function: Function::new(function_id),
-            child_functions: FxHashMap::default(),
+            child_functions: Vec::new(),
+            previous_child_functions,
             register_map: FxHashMap::default(),
             constant_map: FxHashMap::default(),
             current_node: None,
@@ -717,7 +748,7 @@ impl<'a> Lifter<'a> {
                             top = Some((vararg.into(), a));
                         }
                     }
-                    OpCode::LOP_NOP => {}
+                    OpCode::LOP_NOP | OpCode::LOP_NOP97 => {}
                     OpCode::LOP_SUBRK | OpCode::LOP_DIVRK => {
                         let op = match op_code {
                             OpCode::LOP_SUBRK => ast::BinaryOperation::Sub,
@@ -1248,10 +1279,19 @@ impl<'a> Lifter<'a> {
                             upvalues_passed.push(local);
                         }
 
-                        let function = Arc::<Mutex<_>>::default();
+                        let function = match self
+                            .previous_child_functions
+                            .get(self.child_functions.len())
+                        {
+                            Some((function, _)) => function.0.clone(),
+                            None => {
+                                let function = Arc::<Mutex<ast::Function>>::default();
+                                function.lock().name = func_name;
+                                function
+                            }
+                        };
                         self.child_functions
-                            .insert(ByAddress(function.clone()), func_index);
-                        function.lock().name = func_name;
+                            .push((ByAddress(function.clone()), func_index));
                         statements.push(
                             ast::Assign::new(
                                 vec![dest_local.into()],
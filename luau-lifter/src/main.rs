@@ -1,10 +1,28 @@
+use luau_lifter::op_code_decoder::OpcodeDecoder;
+
 fn main() {
-    let file_name = std::env::args().nth(1).expect("expected exactly one file");
-    let key = std::env::args()
-        .nth(2)
-        .or_else(|| None)
-        .map(|s| if s == "-e" { 203 } else { panic!() })
-        .unwrap_or(1);
+    let mut args = std::env::args().skip(1);
+    let file_name = args.next().expect("expected exactly one file");
     let bytecode = std::fs::read(file_name).expect("failed to read file");
-    println!("{}", luau_lifter::decompile_bytecode(&bytecode, key));
+    // -e: Roblox client key (203)
+    // -k <key>: op = op * key % 256
+    // -t <file>: opcode permutation table
+    // -a: detect the key
+    let decoder = match args.next().as_deref() {
+        None => OpcodeDecoder::default(),
+        Some("-e") => OpcodeDecoder::Multiplicative(203),
+        Some("-k") => OpcodeDecoder::Multiplicative(
+            args.next()
+                .and_then(|key| key.parse().ok())
+                .expect("expected a key between 0 and 255"),
+        ),
+        Some("-t") => OpcodeDecoder::load_table(args.next().expect("expected a table file"))
+            .expect("failed to load opcode table"),
+        Some("-a") => OpcodeDecoder::detect(&bytecode).unwrap_or_default(),
+        Some(option) => panic!("unknown option {}, expected -e, -k, -t or -a", option),
+    };
+    println!(
+        "{}",
+        luau_lifter::decompile_bytecode(&bytecode, &decoder, &Default::default())
+    );
 }
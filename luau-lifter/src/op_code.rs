@@ -367,4 +367,53 @@ pub enum OpCode {
 
     // Enum entry for number of opcodes, not a valid opcode by itself!
     LOP__COUNT,
+
+    // NOP97: not a Luau opcode, but found in some bytecode and lifted like NOP. It is kept apart
+    // from NOP so that the bytecode serializes back the way it was read
+    LOP_NOP97 = 97,
+}
+
+impl OpCode {
+    /// Whether the instruction is followed by an auxiliary word.
+    pub fn has_aux(self) -> bool {
+        matches!(
+            self,
+            OpCode::LOP_GETGLOBAL
+                | OpCode::LOP_SETGLOBAL
+                | OpCode::LOP_GETIMPORT
+                | OpCode::LOP_GETTABLEKS
+                | OpCode::LOP_SETTABLEKS
+                | OpCode::LOP_NAMECALL
+                | OpCode::LOP_JUMPIFEQ
+                | OpCode::LOP_JUMPIFLE
+                | OpCode::LOP_JUMPIFLT
+                | OpCode::LOP_JUMPIFNOTEQ
+                | OpCode::LOP_JUMPIFNOTLE
+                | OpCode::LOP_JUMPIFNOTLT
+                | OpCode::LOP_NEWTABLE
+                | OpCode::LOP_SETLIST
+                | OpCode::LOP_FORGLOOP
+                | OpCode::LOP_LOADKX
+                | OpCode::LOP_FASTCALL2
+                | OpCode::LOP_FASTCALL2K
+                | OpCode::LOP_FASTCALL3
+                | OpCode::LOP_JUMPXEQKNIL
+                | OpCode::LOP_JUMPXEQKB
+                | OpCode::LOP_JUMPXEQKN
+                | OpCode::LOP_JUMPXEQKS
+        )
+    }
+
+    /// The mnemonic used in disassembly, i.e. the variant name without the `LOP_` prefix.
+    pub fn mnemonic(self) -> String {
+        let name = format!("{:?}", self);
+        name.strip_prefix("LOP_").unwrap_or(&name).to_string()
+    }
+
+    pub fn from_mnemonic(mnemonic: &str) -> Option<Self> {
+        (0..OpCode::LOP__COUNT as u8)
+            .chain([OpCode::LOP_NOP97 as u8])
+            .filter_map(|op| OpCode::try_from(op).ok())
+            .find(|op| op.mnemonic().eq_ignore_ascii_case(mnemonic))
+    }
 }
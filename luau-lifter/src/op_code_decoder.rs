@@ -0,0 +1,143 @@
+use std::fmt;
+
+use crate::{
+    deserializer::{self, bytecode::Bytecode, function::Function},
+    op_code::OpCode,
+};
+
+/// Maps the opcode byte stored in an instruction word to the actual opcode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OpcodeDecoder {
+    /// op = op * key % 256
+    /// For Roblox client bytecode, use 203
+    Multiplicative(u8),
+    /// Entry `i` is the opcode that the stored byte `i` decodes to.
+    Table(Box<[u8; 256]>),
+}
+
+impl Default for OpcodeDecoder {
+    fn default() -> Self {
+        Self::Multiplicative(1)
+    }
+}
+
+impl From<u8> for OpcodeDecoder {
+    fn from(key: u8) -> Self {
+        Self::Multiplicative(key)
+    }
+}
+
+impl fmt::Display for OpcodeDecoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Multiplicative(key) => write!(f, "key {}", key),
+            Self::Table(_) => write!(f, "opcode table"),
+        }
+    }
+}
+
+impl OpcodeDecoder {
+    pub fn decode(&self, op_code: u8) -> u8 {
+        match self {
+            Self::Multiplicative(key) => op_code.wrapping_mul(*key),
+            Self::Table(table) => table[op_code as usize],
+        }
+    }
+
+    /// The byte that decodes to `op_code`, if there is one.
+    pub fn encode(&self, op_code: u8) -> Option<u8> {
+        match self {
+            Self::Multiplicative(key) => {
+                // only odd keys have an inverse
+                let inverse = (0..=u8::MAX).find(|&k| k.wrapping_mul(*key) == 1)?;
+                Some(op_code.wrapping_mul(inverse))
+            }
+            Self::Table(table) => table
+                .iter()
+                .position(|&entry| entry == op_code)
+                .map(|i| i as u8),
+        }
+    }
+
+    /// Parses a permutation table: 256 distinct numbers separated by whitespace or commas,
+    /// where the `i`th number is the opcode that the stored byte `i` decodes to.
+    /// Numbers may be written in hex with a `0x` prefix and `#` starts a comment.
+    pub fn parse_table(source: &str) -> Result<Self, String> {
+        let entries = source
+            .lines()
+            .map(|line| line.split('#').next().unwrap())
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                match entry.strip_prefix("0x") {
+                    Some(hex) => u8::from_str_radix(hex, 16),
+                    None => entry.parse(),
+                }
+                .map_err(|_| format!("invalid table entry: {}", entry))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let table: Box<[u8; 256]> =
+            entries
+                .into_boxed_slice()
+                .try_into()
+                .map_err(|entries: Box<[u8]>| {
+                    format!("expected 256 table entries, got {}", entries.len())
+                })?;
+        // otherwise some opcodes couldn't be encoded and others would be ambiguous
+        let mut seen = [false; 256];
+        for &entry in table.iter() {
+            if std::mem::replace(&mut seen[entry as usize], true) {
+                return Err(format!("table entry {} appears more than once", entry));
+            }
+        }
+        Ok(Self::Table(table))
+    }
+
+    pub fn load_table(path: impl AsRef<std::path::Path>) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        Self::parse_table(&source)
+    }
+
+    /// Guesses the multiplicative key the bytecode was encoded with by deserializing it with
+    /// every invertible key and counting the functions whose instructions look sane.
+    /// Returns `None` if no key produces a single valid function.
+    pub fn detect(bytecode: &[u8]) -> Option<Self> {
+        // try the common keys first so that they win ties
+        let keys = [1, 203].into_iter().chain(
+            (1..=u8::MAX)
+                .step_by(2)
+                .filter(|&key| key != 1 && key != 203),
+        );
+        let mut best = None;
+        let mut best_score = 0;
+        for key in keys {
+            let decoder = Self::Multiplicative(key);
+            let score = match deserializer::deserialize(bytecode, &decoder) {
+                Ok(Bytecode::Chunk(chunk)) => chunk
+                    .functions
+                    .iter()
+                    .filter(|function| validate(function))
+                    .count(),
+                _ => 0,
+            };
+            if score > best_score {
+                best_score = score;
+                best = Some(decoder);
+            }
+        }
+        best
+    }
+}
+
+fn validate(function: &Function) -> bool {
+    let instructions = &function.instructions;
+    // the deserializer puts a NOP where each aux word was, nothing can jump there
+    let is_aux = |pc: usize| pc > 0 && instructions[pc - 1].aux().is_some();
+    matches!(instructions.last(), Some(last) if last.op_code() == OpCode::LOP_RETURN)
+        && instructions.iter().enumerate().all(|(pc, instruction)| {
+            instruction.jump_offset().is_none_or(|offset| {
+                let target = pc as i64 + 1 + offset as i64;
+                (0..instructions.len() as i64).contains(&target) && !is_aux(target as usize)
+            })
+        })
+}
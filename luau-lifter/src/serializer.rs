@@ -0,0 +1,179 @@
+use crate::{
+    deserializer::{
+        bytecode::Bytecode,
+        chunk::Chunk,
+        constant::*,
+        function::{DebugInfo, Function},
+    },
+    instruction::Instruction,
+    op_code_decoder::OpcodeDecoder,
+};
+
+/// Writes bytecode in the format read by [`crate::deserializer::deserialize`].
+/// Opcodes are encoded so that deserializing with the same `decoder` yields the original
+/// instructions, which fails if the decoder cannot produce an opcode that is used.
+pub fn serialize(bytecode: &Bytecode, decoder: &OpcodeDecoder) -> Result<Vec<u8>, String> {
+    let mut output = Vec::new();
+    match bytecode {
+        Bytecode::Error(msg) => {
+            output.push(0);
+            output.extend_from_slice(msg.as_bytes());
+        }
+        Bytecode::Chunk(chunk) => write_chunk(&mut output, chunk, decoder)?,
+    }
+    Ok(output)
+}
+
+fn write_leb128(output: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            output.push(byte);
+            break;
+        }
+        output.push(byte | 0x80);
+    }
+}
+
+fn write_list<T>(output: &mut Vec<u8>, list: &[T], mut writer: impl FnMut(&mut Vec<u8>, &T)) {
+    write_leb128(output, list.len());
+    for item in list {
+        writer(output, item);
+    }
+}
+
+fn write_chunk(output: &mut Vec<u8>, chunk: &Chunk, decoder: &OpcodeDecoder) -> Result<(), String> {
+    output.push(chunk.version);
+    if chunk.version >= 4 {
+        output.push(chunk.types_version);
+    }
+    write_list(output, &chunk.string_table, |output, string| {
+        write_leb128(output, string.len());
+        output.extend_from_slice(string);
+    });
+    if chunk.types_version == 3 {
+        for &userdata_type in &chunk.userdata_types {
+            write_leb128(output, userdata_type);
+        }
+        output.push(0);
+    }
+    write_leb128(output, chunk.functions.len());
+    for function in &chunk.functions {
+        write_function(output, function, decoder)?;
+    }
+    write_leb128(output, chunk.main);
+    Ok(())
+}
+
+fn write_instructions(
+    output: &mut Vec<u8>,
+    instructions: &[Instruction],
+    decoder: &OpcodeDecoder,
+) -> Result<(), String> {
+    write_leb128(output, instructions.len());
+    let mut iter = instructions.iter();
+    while let Some(instruction) = iter.next() {
+        output.extend_from_slice(&instruction.encode(decoder)?.to_le_bytes());
+        if let Some(aux) = instruction.aux() {
+            output.extend_from_slice(&aux.to_le_bytes());
+            // the deserializer puts a NOP where the aux word was to keep pcs intact
+            iter.next();
+        }
+    }
+    Ok(())
+}
+
+fn write_constant(output: &mut Vec<u8>, constant: &Constant) {
+    match constant {
+        Constant::Nil => output.push(CONSTANT_NIL),
+        Constant::Boolean(value) => {
+            output.push(CONSTANT_BOOLEAN);
+            output.push(*value as u8);
+        }
+        Constant::Number(value) => {
+            output.push(CONSTANT_NUMBER);
+            output.extend_from_slice(&value.to_le_bytes());
+        }
+        Constant::String(string_index) => {
+            output.push(CONSTANT_STRING);
+            write_leb128(output, *string_index);
+        }
+        Constant::Import(import_index) => {
+            output.push(CONSTANT_IMPORT);
+            output.extend_from_slice(&(*import_index as u32).to_le_bytes());
+        }
+        Constant::Table(keys) => {
+            output.push(CONSTANT_TABLE);
+            write_list(output, keys, |output, &key| write_leb128(output, key));
+        }
+        Constant::Closure(f_id) => {
+            output.push(CONSTANT_CLOSURE);
+            write_leb128(output, *f_id);
+        }
+        Constant::Vector(x, y, z, w) => {
+            output.push(CONSTANT_VECTOR);
+            for component in [x, y, z, w] {
+                output.extend_from_slice(&component.to_le_bytes());
+            }
+        }
+    }
+}
+
+fn write_debug_info(output: &mut Vec<u8>, debug_info: &DebugInfo) {
+    write_list(output, &debug_info.locals, |output, local| {
+        write_leb128(output, local.name);
+        write_leb128(output, local.start_pc);
+        write_leb128(output, local.end_pc);
+        output.push(local.register);
+    });
+    write_list(output, &debug_info.upvalues, |output, &name| {
+        write_leb128(output, name)
+    });
+}
+
+fn write_function(
+    output: &mut Vec<u8>,
+    function: &Function,
+    decoder: &OpcodeDecoder,
+) -> Result<(), String> {
+    output.push(function.max_stack_size);
+    output.push(function.num_parameters);
+    output.push(function.num_upvalues);
+    output.push(function.is_vararg as u8);
+    output.push(function.flags);
+    write_list(output, &function.type_info, |output, &byte| {
+        output.push(byte)
+    });
+    write_instructions(output, &function.instructions, decoder)?;
+    write_list(output, &function.constants, write_constant);
+    write_list(output, &function.functions, |output, &f_id| {
+        write_leb128(output, f_id)
+    });
+    write_leb128(output, function.line_defined);
+    write_leb128(output, function.function_name);
+    match (
+        function.line_gap_log2,
+        &function.line_info_delta,
+        &function.abs_line_info_delta,
+    ) {
+        (Some(line_gap_log2), Some(line_info_delta), Some(abs_line_info_delta)) => {
+            output.push(1);
+            output.push(line_gap_log2);
+            output.extend_from_slice(line_info_delta);
+            for abs_line_info in abs_line_info_delta {
+                output.extend_from_slice(&abs_line_info.to_le_bytes());
+            }
+        }
+        (None, None, None) => output.push(0),
+        _ => return Err("function has incomplete line info".to_string()),
+    }
+    match &function.debug_info {
+        Some(debug_info) => {
+            output.push(1);
+            write_debug_info(output, debug_info);
+        }
+        None => output.push(0),
+    }
+    Ok(())
+}
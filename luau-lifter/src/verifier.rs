@@ -0,0 +1,515 @@
+use std::fmt;
+
+use crate::{
+    deserializer::{chunk::Chunk, constant::Constant, function::Function},
+    instruction::Instruction,
+    op_code::OpCode,
+};
+
+/// Something in the bytecode that the lifter can't cope with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub function_id: Option<usize>,
+    pub pc: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.function_id, self.pc) {
+            (Some(function_id), Some(pc)) => {
+                write!(f, "function {}, pc {}: {}", function_id, pc, self.message)
+            }
+            (Some(function_id), None) => write!(f, "function {}: {}", function_id, self.message),
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Checks the structural invariants the lifter relies on and returns every violation found.
+pub fn verify(chunk: &Chunk) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    if chunk.main >= chunk.functions.len() {
+        problems.push(Problem {
+            function_id: None,
+            pc: None,
+            message: format!("main function {} does not exist", chunk.main),
+        });
+    }
+    for (function_id, function) in chunk.functions.iter().enumerate() {
+        FunctionVerifier {
+            chunk,
+            function_id,
+            function,
+            problems: &mut problems,
+        }
+        .verify();
+    }
+    problems
+}
+
+struct FunctionVerifier<'a> {
+    chunk: &'a Chunk,
+    function_id: usize,
+    function: &'a Function,
+    problems: &'a mut Vec<Problem>,
+}
+
+impl<'a> FunctionVerifier<'a> {
+    fn report(&mut self, pc: Option<usize>, message: String) {
+        self.problems.push(Problem {
+            function_id: Some(self.function_id),
+            pc,
+            message,
+        });
+    }
+
+    fn verify(mut self) {
+        if self.function.function_name > self.chunk.string_table.len() {
+            self.report(
+                None,
+                format!("name {} is out of range", self.function.function_name),
+            );
+        }
+        if self.function.num_parameters > self.function.max_stack_size {
+            self.report(
+                None,
+                format!(
+                    "{} parameters do not fit in {} registers",
+                    self.function.num_parameters, self.function.max_stack_size
+                ),
+            );
+        }
+        for &child in &self.function.functions {
+            if child >= self.chunk.functions.len() {
+                self.report(None, format!("child function {} does not exist", child));
+            }
+        }
+        for index in 0..self.function.constants.len() {
+            self.verify_constant(index);
+        }
+        if self.function.instructions.is_empty() {
+            self.report(None, "function has no instructions".to_string());
+        }
+
+        let mut pending_captures = 0;
+        for (pc, instruction) in self.function.instructions.iter().enumerate() {
+            if self.is_aux(pc) {
+                continue;
+            }
+            if instruction.op_code() == OpCode::LOP_CAPTURE {
+                if pending_captures == 0 {
+                    self.report(Some(pc), "CAPTURE does not follow a closure".to_string());
+                } else {
+                    pending_captures -= 1;
+                }
+                self.verify_capture(pc, instruction);
+                continue;
+            }
+            if pending_captures != 0 {
+                self.report(
+                    Some(pc),
+                    format!("expected {} more CAPTURE instructions", pending_captures),
+                );
+                pending_captures = 0;
+            }
+            self.verify_instruction(pc, instruction);
+            if let Some(child) = self.closure_child(instruction) {
+                pending_captures = self.chunk.functions[child].num_upvalues;
+            }
+        }
+        if pending_captures != 0 {
+            self.report(
+                None,
+                format!(
+                    "expected {} more CAPTURE instructions at the end",
+                    pending_captures
+                ),
+            );
+        }
+    }
+
+    fn verify_constant(&mut self, index: usize) {
+        match &self.function.constants[index] {
+            &Constant::String(string) if string == 0 || string > self.chunk.string_table.len() => {
+                self.report(
+                    None,
+                    format!("constant {} refers to invalid string {}", index, string),
+                );
+            }
+            &Constant::Import(import) => {
+                let count = import >> 30;
+                if !(1..=3).contains(&count) {
+                    self.report(
+                        None,
+                        format!("constant {} is an import with length {}", index, count),
+                    );
+                }
+                for i in 0..count.min(3) {
+                    let key = (import >> (20 - i * 10)) & 1023;
+                    match self.function.constants.get(key) {
+                        Some(Constant::String(_)) => {}
+                        Some(_) => self.report(
+                            None,
+                            format!(
+                                "constant {} imports constant {}, which is not a string",
+                                index, key
+                            ),
+                        ),
+                        None => self.report(
+                            None,
+                            format!("constant {} imports invalid constant {}", index, key),
+                        ),
+                    }
+                }
+            }
+            Constant::Table(keys) => {
+                for &key in keys {
+                    if key >= self.function.constants.len() {
+                        self.report(
+                            None,
+                            format!("constant {} has invalid key constant {}", index, key),
+                        );
+                    }
+                }
+            }
+            &Constant::Closure(child) if child >= self.chunk.functions.len() => {
+                self.report(
+                    None,
+                    format!("constant {} refers to invalid function {}", index, child),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    // the deserializer puts a NOP where each aux word was
+    fn is_aux(&self, pc: usize) -> bool {
+        pc > 0 && self.function.instructions[pc - 1].aux().is_some()
+    }
+
+    fn closure_child(&self, instruction: &Instruction) -> Option<usize> {
+        let child = match *instruction {
+            Instruction::AD {
+                op_code: OpCode::LOP_NEWCLOSURE,
+                d,
+                ..
+            } => *self.function.functions.get(d as u16 as usize)?,
+            Instruction::AD {
+                op_code: OpCode::LOP_DUPCLOSURE,
+                d,
+                ..
+            } => match self.function.constants.get(d as u16 as usize)? {
+                &Constant::Closure(child) => child,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        (child < self.chunk.functions.len()).then_some(child)
+    }
+
+    fn register(&mut self, pc: usize, register: u8) {
+        if register >= self.function.max_stack_size {
+            self.report(
+                Some(pc),
+                format!(
+                    "register {} is out of range (stack size {})",
+                    register, self.function.max_stack_size
+                ),
+            );
+        }
+    }
+
+    // `count` is encoded with a bias of one, zero meaning "up to the top of the stack"
+    fn registers(&mut self, pc: usize, first: u8, count: u8) {
+        if count > 1 {
+            match first.checked_add(count - 2) {
+                Some(last) => self.register(pc, last),
+                None => self.report(Some(pc), "register range is out of range".to_string()),
+            }
+        }
+    }
+
+    fn upvalue(&mut self, pc: usize, upvalue: u8) {
+        if upvalue >= self.function.num_upvalues {
+            self.report(
+                Some(pc),
+                format!(
+                    "upvalue {} is out of range ({} upvalues)",
+                    upvalue, self.function.num_upvalues
+                ),
+            );
+        }
+    }
+
+    fn constant(&mut self, pc: usize, index: usize) -> Option<&'a Constant> {
+        let constant = self.function.constants.get(index);
+        if constant.is_none() {
+            self.report(Some(pc), format!("constant {} is out of range", index));
+        }
+        constant
+    }
+
+    fn typed_constant(
+        &mut self,
+        pc: usize,
+        index: usize,
+        kind: &str,
+        is_kind: fn(&Constant) -> bool,
+    ) {
+        match self.constant(pc, index) {
+            Some(constant) if !is_kind(constant) => {
+                self.report(Some(pc), format!("constant {} is not {}", index, kind))
+            }
+            _ => {}
+        }
+    }
+
+    fn string_constant(&mut self, pc: usize, index: usize) {
+        self.typed_constant(pc, index, "a string", |constant| {
+            matches!(constant, Constant::String(_))
+        });
+    }
+
+    // the fallback of a FASTCALL is the CALL `skip` instructions after it
+    fn fastcall_skip(&mut self, pc: usize, skip: u8) {
+        let target = pc + 1 + skip as usize;
+        match self.function.instructions.get(target) {
+            Some(instruction)
+                if instruction.op_code() == OpCode::LOP_CALL && !self.is_aux(target) => {}
+            Some(_) => self.report(
+                Some(pc),
+                format!("FASTCALL skips to {}, which is not a CALL", target),
+            ),
+            None => self.report(
+                Some(pc),
+                format!("FASTCALL skip target {} is out of range", target),
+            ),
+        }
+    }
+
+    fn verify_capture(&mut self, pc: usize, instruction: &Instruction) {
+        if let Instruction::BC {
+            a: capture_type,
+            b: source,
+            ..
+        } = *instruction
+        {
+            match capture_type {
+                0 | 1 => self.register(pc, source),
+                2 => self.upvalue(pc, source),
+                _ => self.report(Some(pc), format!("unknown capture type {}", capture_type)),
+            }
+        }
+    }
+
+    fn verify_instruction(&mut self, pc: usize, instruction: &Instruction) {
+        let instructions = &self.function.instructions;
+        if let Some(offset) = instruction.jump_offset() {
+            let target = pc as i64 + 1 + offset as i64;
+            if !(0..instructions.len() as i64).contains(&target) {
+                self.report(Some(pc), format!("jump target {} is out of range", target));
+            } else if self.is_aux(target as usize) {
+                self.report(Some(pc), format!("jump target {} is an aux word", target));
+            }
+        }
+        if instruction.aux().is_some()
+            && !matches!(
+                instructions.get(pc + 1),
+                Some(Instruction::BC {
+                    op_code: OpCode::LOP_NOP,
+                    ..
+                })
+            )
+        {
+            self.report(Some(pc), "missing aux word".to_string());
+        }
+
+        match *instruction {
+            Instruction::BC {
+                op_code,
+                a,
+                b,
+                c,
+                aux,
+            } => match op_code {
+                OpCode::LOP_LOADNIL | OpCode::LOP_LOADB | OpCode::LOP_NEWTABLE => {
+                    self.register(pc, a)
+                }
+                OpCode::LOP_GETGLOBAL | OpCode::LOP_SETGLOBAL => {
+                    self.register(pc, a);
+                    self.string_constant(pc, aux as usize);
+                }
+                OpCode::LOP_GETUPVAL | OpCode::LOP_SETUPVAL => {
+                    self.register(pc, a);
+                    self.upvalue(pc, b);
+                }
+                OpCode::LOP_LOADKX => {
+                    self.register(pc, a);
+                    self.constant(pc, aux as usize);
+                }
+                OpCode::LOP_MOVE
+                | OpCode::LOP_GETTABLEN
+                | OpCode::LOP_SETTABLEN
+                | OpCode::LOP_NOT
+                | OpCode::LOP_MINUS
+                | OpCode::LOP_LENGTH => {
+                    self.register(pc, a);
+                    self.register(pc, b);
+                }
+                OpCode::LOP_GETTABLEKS | OpCode::LOP_SETTABLEKS => {
+                    self.register(pc, a);
+                    self.register(pc, b);
+                    self.string_constant(pc, aux as usize);
+                }
+                OpCode::LOP_NAMECALL => {
+                    self.registers(pc, a, 3);
+                    self.register(pc, b);
+                    self.string_constant(pc, aux as usize);
+                }
+                OpCode::LOP_GETTABLE
+                | OpCode::LOP_SETTABLE
+                | OpCode::LOP_ADD
+                | OpCode::LOP_SUB
+                | OpCode::LOP_MUL
+                | OpCode::LOP_DIV
+                | OpCode::LOP_IDIV
+                | OpCode::LOP_MOD
+                | OpCode::LOP_POW
+                | OpCode::LOP_AND
+                | OpCode::LOP_OR
+                | OpCode::LOP_CONCAT => {
+                    self.register(pc, a);
+                    self.register(pc, b);
+                    self.register(pc, c);
+                }
+                OpCode::LOP_ADDK
+                | OpCode::LOP_SUBK
+                | OpCode::LOP_MULK
+                | OpCode::LOP_DIVK
+                | OpCode::LOP_IDIVK
+                | OpCode::LOP_MODK
+                | OpCode::LOP_POWK
+                | OpCode::LOP_ANDK
+                | OpCode::LOP_ORK => {
+                    self.register(pc, a);
+                    self.register(pc, b);
+                    self.constant(pc, c as usize);
+                }
+                OpCode::LOP_SUBRK | OpCode::LOP_DIVRK => {
+                    self.register(pc, a);
+                    self.constant(pc, b as usize);
+                    self.register(pc, c);
+                }
+                OpCode::LOP_CALL => {
+                    self.register(pc, a);
+                    if let Some(first_argument) = a.checked_add(1) {
+                        self.registers(pc, first_argument, b);
+                    }
+                    self.registers(pc, a, c);
+                }
+                OpCode::LOP_RETURN | OpCode::LOP_GETVARARGS => self.registers(pc, a, b),
+                OpCode::LOP_SETLIST => {
+                    self.register(pc, a);
+                    self.registers(pc, b, c);
+                }
+                OpCode::LOP_FASTCALL => self.fastcall_skip(pc, c),
+                OpCode::LOP_FASTCALL1 => {
+                    self.register(pc, b);
+                    self.fastcall_skip(pc, c);
+                }
+                OpCode::LOP_FASTCALL2 => {
+                    self.register(pc, b);
+                    self.register(pc, aux as u8);
+                    self.fastcall_skip(pc, c);
+                }
+                OpCode::LOP_FASTCALL2K => {
+                    self.register(pc, b);
+                    self.constant(pc, aux as usize);
+                    self.fastcall_skip(pc, c);
+                }
+                OpCode::LOP_FASTCALL3 => {
+                    self.register(pc, b);
+                    self.register(pc, aux as u8);
+                    self.register(pc, (aux >> 8) as u8);
+                    self.fastcall_skip(pc, c);
+                }
+                OpCode::LOP_CLOSEUPVALS => self.register(pc, a),
+                OpCode::LOP_PREPVARARGS => {
+                    if !self.function.is_vararg {
+                        self.report(
+                            Some(pc),
+                            "PREPVARARGS in a function without varargs".to_string(),
+                        );
+                    }
+                    if a != self.function.num_parameters {
+                        self.report(
+                            Some(pc),
+                            format!(
+                                "PREPVARARGS expects {} parameters, but the function has {}",
+                                a, self.function.num_parameters
+                            ),
+                        );
+                    }
+                }
+                _ => {}
+            },
+            Instruction::AD { op_code, a, d, aux } => match op_code {
+                OpCode::LOP_LOADN
+                | OpCode::LOP_JUMPIF
+                | OpCode::LOP_JUMPIFNOT
+                | OpCode::LOP_JUMPXEQKNIL
+                | OpCode::LOP_JUMPXEQKB => self.register(pc, a),
+                OpCode::LOP_LOADK => {
+                    self.register(pc, a);
+                    self.constant(pc, d as u16 as usize);
+                }
+                OpCode::LOP_GETIMPORT => {
+                    self.register(pc, a);
+                    self.typed_constant(pc, d as u16 as usize, "an import", |constant| {
+                        matches!(constant, Constant::Import(_))
+                    });
+                }
+                OpCode::LOP_DUPTABLE => {
+                    self.register(pc, a);
+                    self.typed_constant(pc, d as u16 as usize, "a table", |constant| {
+                        matches!(constant, Constant::Table(_))
+                    });
+                }
+                OpCode::LOP_NEWCLOSURE => {
+                    self.register(pc, a);
+                    if d as u16 as usize >= self.function.functions.len() {
+                        self.report(Some(pc), format!("child function {} is out of range", d));
+                    }
+                }
+                OpCode::LOP_DUPCLOSURE => {
+                    self.register(pc, a);
+                    self.typed_constant(pc, d as u16 as usize, "a closure", |constant| {
+                        matches!(constant, Constant::Closure(_))
+                    });
+                }
+                OpCode::LOP_JUMPIFEQ
+                | OpCode::LOP_JUMPIFLE
+                | OpCode::LOP_JUMPIFLT
+                | OpCode::LOP_JUMPIFNOTEQ
+                | OpCode::LOP_JUMPIFNOTLE
+                | OpCode::LOP_JUMPIFNOTLT => {
+                    self.register(pc, a);
+                    self.register(pc, aux as u8);
+                }
+                OpCode::LOP_JUMPXEQKN | OpCode::LOP_JUMPXEQKS => {
+                    self.register(pc, a);
+                    self.constant(pc, (aux & ((1 << 24) - 1)) as usize);
+                }
+                OpCode::LOP_FORNPREP
+                | OpCode::LOP_FORNLOOP
+                | OpCode::LOP_FORGPREP
+                | OpCode::LOP_FORGPREP_INEXT
+                | OpCode::LOP_FORGPREP_NEXT
+                | OpCode::LOP_FORGLOOP => self.registers(pc, a, 4),
+                _ => {}
+            },
+            Instruction::E { .. } => {}
+        }
+    }
+}
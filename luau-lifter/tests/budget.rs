@@ -0,0 +1,124 @@
+use ast::report::FunctionStatus;
+use luau_lifter::{
+    assembler::assemble_bytecode, decompile_report, op_code_decoder::OpcodeDecoder, Budget,
+};
+
+const LOOPS: &str = include_str!("loops.luauasm");
+
+#[test]
+fn functions_over_budget_are_decompiled_without_ssa() {
+    let bytecode = assemble_bytecode(LOOPS, &OpcodeDecoder::default()).unwrap();
+    let budget = Budget {
+        max_iterations: 1,
+        ..Default::default()
+    };
+    let report = decompile_report(
+        &bytecode,
+        &OpcodeDecoder::default(),
+        &Default::default(),
+        &budget,
+    );
+    assert!(report.error.is_none());
+    assert!(report.counts.degraded > 0);
+    for function in &report.functions {
+        if let FunctionStatus::Degraded { level, reason, .. } = &function.status {
+            assert!(level.starts_with("without SSA"));
+            assert_eq!(reason, "didn't settle within the iteration limit of 1");
+        }
+    }
+    assert!(report
+        .source
+        .contains("after it didn't settle within the iteration limit of 1"));
+    // running out of iterations turns out the same every time
+    assert!(!report.timed_out());
+}
+
+#[test]
+fn functions_out_of_time_are_marked() {
+    let bytecode = assemble_bytecode(LOOPS, &OpcodeDecoder::default()).unwrap();
+    let budget = Budget {
+        time_limit: Some(std::time::Duration::ZERO),
+        ..Default::default()
+    };
+    let report = decompile_report(
+        &bytecode,
+        &OpcodeDecoder::default(),
+        &Default::default(),
+        &budget,
+    );
+    assert!(report.timed_out());
+    for function in &report.functions {
+        if let FunctionStatus::Degraded { reason, .. } = &function.status {
+            assert_eq!(reason, "took longer than 0ns");
+            assert!(function.timed_out);
+        }
+    }
+}
+
+#[test]
+fn the_default_budget_is_enough() {
+    let bytecode = assemble_bytecode(LOOPS, &OpcodeDecoder::default()).unwrap();
+    let report = decompile_report(
+        &bytecode,
+        &OpcodeDecoder::default(),
+        &Default::default(),
+        &Budget::default(),
+    );
+    assert_eq!(report.counts.ok, report.counts.functions);
+}
+
+#[test]
+fn loops_without_ssa_jump_on_their_condition() {
+    let bytecode = assemble_bytecode(LOOPS, &OpcodeDecoder::default()).unwrap();
+    let budget = Budget {
+        max_iterations: 1,
+        ..Default::default()
+    };
+    let source = decompile_report(
+        &bytecode,
+        &OpcodeDecoder::default(),
+        &Default::default(),
+        &budget,
+    )
+    .source;
+    for statement in [
+        "NumForInit",
+        "NumForNext",
+        "GenericForInit",
+        "GenericForNext",
+    ] {
+        assert!(!source.contains(statement), "{}", source);
+    }
+    // the back edges of both loops are kept
+    assert!(
+        source.contains(
+            "\nv_u_4 = v_u_4 + v3\n\
+             if v3 > 0 and v_u_4 <= v2 or v3 <= 0 and v_u_4 >= v2 then\n\
+             \tgoto l1\nelse\n\tgoto l2\nend\n"
+        ),
+        "{}",
+        source
+    );
+    assert!(
+        source.contains(
+            "\nv_u_4, v5 = v7(v2, v3)\n\
+             if v_u_4 ~= nil then\n\tv3 = v_u_4\n\tgoto l6\nelse\n\tgoto l9\nend\n"
+        ),
+        "{}",
+        source
+    );
+    // every goto has a label to go to
+    let labels = source
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("::"))
+        .collect::<Vec<_>>();
+    for line in source.lines() {
+        if let Some(target) = line.trim().strip_prefix("goto ") {
+            assert!(
+                labels.contains(&format!("{}::", target).as_str()),
+                "{}",
+                source
+            );
+        }
+    }
+}
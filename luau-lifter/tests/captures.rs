@@ -0,0 +1,50 @@
+use luau_lifter::{
+    assembler::assemble_bytecode, decompile_report, op_code_decoder::OpcodeDecoder, Budget,
+};
+
+// a numeric for loop whose body stores a closure capturing a local of the iteration
+fn decompile(budget: &Budget) -> String {
+    let bytecode =
+        assemble_bytecode(include_str!("captures.luauasm"), &OpcodeDecoder::default()).unwrap();
+    decompile_report(
+        &bytecode,
+        &OpcodeDecoder::default(),
+        &Default::default(),
+        budget,
+    )
+    .source
+}
+
+#[test]
+fn captured_loop_locals_are_declared_in_the_body() {
+    let source = decompile(&Budget::default());
+    assert!(
+        source.contains("for i = 1, 3 do\n\tlocal v_u_2 = i\n\tv1[i] = function()"),
+        "{}",
+        source
+    );
+}
+
+#[test]
+fn captured_loop_locals_without_ssa_are_declared_in_a_scope() {
+    let source = decompile(&Budget {
+        max_iterations: 1,
+        ..Default::default()
+    });
+    // every iteration gets its own local instead of sharing the one declared up front
+    assert!(
+        source.contains("\nlocal v1, v2, v3, v4, v5\n"),
+        "{}",
+        source
+    );
+    assert!(
+        source.contains("\n::l1::\ndo\n\tlocal v_u_6 = v4\n\tv5 = function()"),
+        "{}",
+        source
+    );
+    assert!(
+        source.contains("\n\tv1[v4] = v5\nend\ngoto l3\n"),
+        "{}",
+        source
+    );
+}
@@ -0,0 +1,58 @@
+use std::thread;
+
+use luau_lifter::{
+    assembler::assemble_bytecode, decompile_bytecode, op_code_decoder::OpcodeDecoder,
+};
+use rayon::ThreadPoolBuilder;
+
+const RUNS: usize = 50;
+const THREADS: usize = 8;
+const LOOPS: &str = include_str!("loops.luauasm");
+
+fn decompile(bytecode: &[u8]) -> String {
+    decompile_bytecode(bytecode, &OpcodeDecoder::default(), &Default::default())
+}
+
+// keeps allocations of different sizes alive so that every run gets different addresses
+fn decompile_repeatedly(bytecode: &[u8], expected: &str) {
+    let mut padding = Vec::new();
+    for run in 0..RUNS {
+        padding.push(vec![0u8; run * 24 + 8]);
+        assert_eq!(decompile(bytecode), expected, "run {} differs", run);
+    }
+}
+
+#[test]
+fn single_threaded() {
+    let bytecode = assemble_bytecode(LOOPS, &OpcodeDecoder::default()).unwrap();
+    let expected = decompile(&bytecode);
+    assert!(!expected.contains("UNNAMED"));
+    decompile_repeatedly(&bytecode, &expected);
+}
+
+#[test]
+fn multi_threaded() {
+    let bytecode = assemble_bytecode(LOOPS, &OpcodeDecoder::default()).unwrap();
+    let expected = decompile(&bytecode);
+    thread::scope(|scope| {
+        for _ in 0..THREADS {
+            scope.spawn(|| decompile_repeatedly(&bytecode, &expected));
+        }
+    });
+}
+
+#[test]
+fn parallel_matches_sequential() {
+    let bytecode = assemble_bytecode(LOOPS, &OpcodeDecoder::default()).unwrap();
+    let decompile_on = |threads| {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        pool.install(|| decompile(&bytecode))
+    };
+    let expected = decompile_on(1);
+    for _ in 0..RUNS {
+        assert_eq!(decompile_on(THREADS), expected);
+    }
+}
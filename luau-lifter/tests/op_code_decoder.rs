@@ -0,0 +1,82 @@
+use std::fs;
+
+use luau_lifter::{assembler, op_code_decoder::OpcodeDecoder, serializer};
+
+fn identity() -> Vec<u8> {
+    (0..=u8::MAX).collect()
+}
+
+fn source(entries: &[u8]) -> String {
+    entries
+        .iter()
+        .map(|entry| entry.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[test]
+fn tables_decode_and_encode() {
+    let mut entries = identity();
+    entries.swap(1, 2);
+    let decoder = OpcodeDecoder::parse_table(&source(&entries)).unwrap();
+    assert_eq!(decoder.decode(1), 2);
+    assert_eq!(decoder.decode(2), 1);
+    assert_eq!(decoder.encode(2), Some(1));
+}
+
+#[test]
+fn tables_accept_hex_commas_and_comments() {
+    let source = identity()
+        .chunks(16)
+        .map(|row| {
+            row.iter()
+                .map(|entry| format!("{:#x}", entry))
+                .collect::<Vec<_>>()
+                .join(", ")
+                + " # row"
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let decoder = OpcodeDecoder::parse_table(&source).unwrap();
+    assert!((0..=u8::MAX).all(|op| decoder.decode(op) == op));
+}
+
+#[test]
+fn malformed_tables_are_rejected() {
+    assert_eq!(
+        OpcodeDecoder::parse_table(&source(&identity()[..255])).unwrap_err(),
+        "expected 256 table entries, got 255"
+    );
+    assert!(OpcodeDecoder::parse_table(&(source(&identity()) + " 256")).is_err());
+    assert!(OpcodeDecoder::parse_table(&source(&identity()).replace(" 7 ", " x ")).is_err());
+
+    let mut entries = identity();
+    entries[3] = 4;
+    assert_eq!(
+        OpcodeDecoder::parse_table(&source(&entries)).unwrap_err(),
+        "table entry 4 appears more than once"
+    );
+}
+
+#[test]
+fn tables_load_from_files() {
+    let path = std::env::temp_dir().join(format!("opcode-table-{}.txt", std::process::id()));
+    fs::write(&path, source(&identity())).unwrap();
+    let decoder = OpcodeDecoder::load_table(&path);
+    fs::remove_file(&path).unwrap();
+    assert!(matches!(decoder, Ok(OpcodeDecoder::Table(_))));
+    assert!(OpcodeDecoder::load_table(&path).is_err());
+}
+
+#[test]
+fn keys_are_detected() {
+    let chunk = assembler::assemble(include_str!("loops.luauasm")).unwrap();
+    for key in [1, 203, 7] {
+        let bytecode = serializer::serialize(&chunk, &OpcodeDecoder::Multiplicative(key)).unwrap();
+        assert_eq!(
+            OpcodeDecoder::detect(&bytecode),
+            Some(OpcodeDecoder::Multiplicative(key))
+        );
+    }
+    assert_eq!(OpcodeDecoder::detect(b"\x06garbage"), None);
+}
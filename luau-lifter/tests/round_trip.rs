@@ -0,0 +1,80 @@
+use std::{fs, path::Path};
+
+use luau_lifter::{
+    assembler,
+    deserializer::{self, bytecode::Bytecode},
+    disassembler,
+    op_code_decoder::OpcodeDecoder,
+    serializer,
+};
+
+fn assert_round_trips(bytecode: &[u8], decoder: &OpcodeDecoder, name: &str) {
+    let deserialized = deserializer::deserialize(bytecode, decoder).unwrap();
+    assert_eq!(
+        serializer::serialize(&deserialized, decoder).unwrap(),
+        bytecode,
+        "{} changed when serialized again",
+        name
+    );
+}
+
+#[test]
+fn fuzz_corpus_round_trips() {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("../fuzz/corpus");
+    for directory in ["luau_deserialize", "decompile"] {
+        for entry in fs::read_dir(corpus.join(directory)).unwrap() {
+            let path = entry.unwrap().path();
+            let bytecode = fs::read(&path).unwrap();
+            // the decompile corpus has Lua 5.1 bytecode too
+            if bytecode.starts_with(b"\x1BLua") {
+                continue;
+            }
+            let decoder = OpcodeDecoder::detect(&bytecode).unwrap_or_default();
+            assert_round_trips(&bytecode, &decoder, &path.display().to_string());
+        }
+    }
+}
+
+#[test]
+fn fixtures_round_trip() {
+    let chunk = assembler::assemble(include_str!("loops.luauasm")).unwrap();
+    for key in [1, 203] {
+        let decoder = OpcodeDecoder::Multiplicative(key);
+        let bytecode = serializer::serialize(&chunk, &decoder).unwrap();
+        assert_round_trips(
+            &bytecode,
+            &decoder,
+            &format!("loops.luauasm with key {}", key),
+        );
+    }
+}
+
+#[test]
+fn unknown_nop_keeps_its_opcode() {
+    let source = include_str!("loops.luauasm").replacen(
+        "GETUPVAL A=0 B=0 C=0",
+        "NOP97 A=1 B=2 C=3\nGETUPVAL A=0 B=0 C=0",
+        1,
+    );
+    // the line info has an entry per instruction
+    assert!(!source.contains("line_gap_log2"));
+    let bytecode = assembler::assemble_bytecode(&source, &OpcodeDecoder::default()).unwrap();
+    assert_round_trips(&bytecode, &OpcodeDecoder::default(), "NOP97");
+    let Bytecode::Chunk(chunk) =
+        deserializer::deserialize(&bytecode, &OpcodeDecoder::default()).unwrap()
+    else {
+        unreachable!()
+    };
+    let listing = disassembler::disassemble_function(&chunk, 0).unwrap();
+    assert!(listing
+        .lines()
+        .any(|line| line.contains("NOP97") && line.ends_with("A=1 B=2 C=3")));
+}
+
+#[test]
+fn disassembling_a_missing_function_fails() {
+    let Bytecode::Chunk(chunk) = assembler::assemble(include_str!("loops.luauasm")).unwrap() else {
+        unreachable!()
+    };
+    assert!(disassembler::disassemble_function(&chunk, chunk.functions.len()).is_none());
+}
@@ -0,0 +1,88 @@
+use luau_lifter::{
+    assembler::assemble_bytecode, decompile_bytecode, decompile_streaming, decompile_to_writer,
+    op_code_decoder::OpcodeDecoder, Budget, Decompilation, Event,
+};
+
+const LOOPS: &str = include_str!("loops.luauasm");
+
+#[test]
+fn parts_add_up_to_the_whole_output() {
+    let bytecode = assemble_bytecode(LOOPS, &OpcodeDecoder::default()).unwrap();
+    let mut parts = Vec::new();
+    let mut progress = Vec::new();
+    let report = decompile_streaming(
+        &bytecode,
+        &OpcodeDecoder::default(),
+        &Default::default(),
+        &Budget::default(),
+        |event| match event {
+            Event::Progress { finished, total } => progress.push((finished, total)),
+            Event::Output(output) => parts.push(output.to_string()),
+        },
+    );
+    assert!(report.source.is_empty());
+    assert!(parts.len() > 1);
+    assert_eq!(
+        parts.concat(),
+        decompile_bytecode(&bytecode, &OpcodeDecoder::default(), &Default::default())
+    );
+    let total = report.functions.len();
+    assert_eq!(
+        progress,
+        (1..=total).map(|n| (n, total)).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn writers_get_the_whole_output() {
+    let bytecode = assemble_bytecode(LOOPS, &OpcodeDecoder::default()).unwrap();
+    let mut output = Vec::new();
+    decompile_to_writer(
+        &bytecode,
+        &OpcodeDecoder::default(),
+        &Default::default(),
+        &Budget::default(),
+        &mut output,
+    )
+    .unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        decompile_bytecode(&bytecode, &OpcodeDecoder::default(), &Default::default())
+    );
+}
+
+#[test]
+fn statements_are_output_once_their_functions_are_decompiled() {
+    // the main function stores two closures in two top-level statements
+    let bytecode =
+        assemble_bytecode(include_str!("closures.luauasm"), &OpcodeDecoder::default()).unwrap();
+    let mut parts = Vec::new();
+    let mut decompilation = Decompilation::new(
+        &bytecode,
+        &OpcodeDecoder::default(),
+        &Default::default(),
+        &Budget::default(),
+        |_| unreachable!("nothing is decompiled yet"),
+    );
+    let mut steps = 0;
+    while decompilation.step(|event| {
+        if let Event::Output(output) = event {
+            parts.push((steps, output.to_string()));
+        }
+    }) {
+        steps += 1;
+    }
+    let report = decompilation.finish();
+    assert_eq!(report.functions.len(), 3);
+    // the table is declared after the main function, and the statements with a closure and
+    // the ones up to the next closure after its function
+    assert_eq!(
+        parts.iter().map(|(step, _)| *step).collect::<Vec<_>>(),
+        [0, 1, 1, 2]
+    );
+    assert!(parts[0].1.starts_with("local v1 = {}"), "{:?}", parts);
+    assert_eq!(
+        parts.into_iter().map(|(_, part)| part).collect::<String>(),
+        decompile_bytecode(&bytecode, &OpcodeDecoder::default(), &Default::default())
+    );
+}
@@ -0,0 +1,262 @@
+use luau_lifter::{
+    assembler, decompile_report,
+    deserializer::bytecode::Bytecode,
+    op_code_decoder::OpcodeDecoder,
+    verifier::{self, Problem},
+    Budget,
+};
+
+// calls `print` from a vararg main function with two registers
+const CHUNK: &str = "
+version 6
+types_version 3
+string 1 \"print\"
+
+function 0
+max_stack_size 2
+num_parameters 0
+num_upvalues 0
+is_vararg true
+flags 0
+type_info
+line_defined 0
+function_name 0
+functions
+constant 0 string 1
+constant 1 import 0x40000000
+{constants}
+code
+{code}
+end
+
+main 0
+";
+
+const CALL_PRINT: &str = "
+PREPVARARGS A=0 B=0 C=0
+FASTCALL A=0 B=0 C=2
+GETIMPORT A=0 D=1 AUX=0x40000000
+CALL A=0 B=1 C=1
+RETURN A=0 B=1 C=0
+";
+
+fn verify(constants: &str, code: &str) -> Vec<Problem> {
+    let source = CHUNK
+        .replace("{constants}", constants)
+        .replace("{code}", code.trim());
+    match assembler::assemble(&source).unwrap() {
+        Bytecode::Chunk(chunk) => verifier::verify(&chunk),
+        Bytecode::Error(_) => unreachable!(),
+    }
+}
+
+fn assert_rejected(constants: &str, code: &str, pc: Option<usize>, message: &str) {
+    let problems = verify(constants, code);
+    assert!(
+        problems
+            .iter()
+            .any(|problem| problem.pc == pc && problem.message == message),
+        "expected {:?} at {:?}, got {:?}",
+        message,
+        pc,
+        problems
+    );
+}
+
+fn with_instruction(pc: usize, instruction: &str) -> String {
+    let mut lines = CALL_PRINT.trim().lines().collect::<Vec<_>>();
+    lines.insert(pc, instruction);
+    lines.join("\n")
+}
+
+#[test]
+fn valid_functions_pass() {
+    assert_eq!(verify("", CALL_PRINT), Vec::new());
+}
+
+#[test]
+fn registers_must_be_in_range() {
+    assert_rejected(
+        "",
+        &with_instruction(1, "LOADN A=2 D=0"),
+        Some(1),
+        "register 2 is out of range (stack size 2)",
+    );
+    assert_rejected(
+        "",
+        &with_instruction(1, "CLOSEUPVALS A=2 B=0 C=0"),
+        Some(1),
+        "register 2 is out of range (stack size 2)",
+    );
+}
+
+#[test]
+fn upvalues_must_be_in_range() {
+    assert_rejected(
+        "",
+        &with_instruction(1, "GETUPVAL A=0 B=0 C=0"),
+        Some(1),
+        "upvalue 0 is out of range (0 upvalues)",
+    );
+}
+
+#[test]
+fn jumps_must_land_on_instructions() {
+    assert_rejected(
+        "",
+        &with_instruction(1, "JUMP D=10"),
+        Some(1),
+        "jump target 12 is out of range",
+    );
+    // the GETIMPORT after the jump is followed by its aux word
+    assert_rejected(
+        "",
+        &with_instruction(2, "JUMP D=1"),
+        Some(2),
+        "jump target 4 is an aux word",
+    );
+}
+
+#[test]
+fn fastcalls_must_skip_to_a_call() {
+    assert_rejected(
+        "",
+        &CALL_PRINT.replace("C=2\n", "C=1\n"),
+        Some(1),
+        "FASTCALL skips to 3, which is not a CALL",
+    );
+    assert_rejected(
+        "",
+        &CALL_PRINT.replace("C=2\n", "C=9\n"),
+        Some(1),
+        "FASTCALL skip target 11 is out of range",
+    );
+}
+
+#[test]
+fn prepvarargs_must_match_the_signature() {
+    assert_rejected(
+        "",
+        &CALL_PRINT.replace("PREPVARARGS A=0", "PREPVARARGS A=1"),
+        Some(0),
+        "PREPVARARGS expects 1 parameters, but the function has 0",
+    );
+    let source = CHUNK
+        .replace("is_vararg true", "is_vararg false")
+        .replace("{constants}", "")
+        .replace("{code}", CALL_PRINT.trim());
+    let Bytecode::Chunk(chunk) = assembler::assemble(&source).unwrap() else {
+        unreachable!()
+    };
+    assert!(verifier::verify(&chunk)
+        .iter()
+        .any(|problem| problem.message == "PREPVARARGS in a function without varargs"));
+}
+
+#[test]
+fn constants_must_have_the_right_kind() {
+    assert_rejected(
+        "",
+        &with_instruction(1, "GETGLOBAL A=0 B=0 C=0 AUX=1"),
+        Some(1),
+        "constant 1 is not a string",
+    );
+    assert_rejected(
+        "",
+        &with_instruction(1, "LOADK A=0 D=5"),
+        Some(1),
+        "constant 5 is out of range",
+    );
+    assert_rejected(
+        "",
+        &with_instruction(1, "GETIMPORT A=0 D=0 AUX=0"),
+        Some(1),
+        "constant 0 is not an import",
+    );
+}
+
+#[test]
+fn imports_must_name_strings() {
+    assert_rejected(
+        "constant 2 import 0x40100000",
+        CALL_PRINT,
+        None,
+        "constant 2 imports constant 1, which is not a string",
+    );
+    assert_rejected(
+        "constant 2 import 0x40900000",
+        CALL_PRINT,
+        None,
+        "constant 2 imports invalid constant 9",
+    );
+    assert_rejected(
+        "constant 2 import 0x00000000",
+        CALL_PRINT,
+        None,
+        "constant 2 is an import with length 0",
+    );
+}
+
+#[test]
+fn captures_must_follow_closures() {
+    assert_rejected(
+        "",
+        &with_instruction(1, "CAPTURE A=0 B=0 C=0"),
+        Some(1),
+        "CAPTURE does not follow a closure",
+    );
+    assert_rejected(
+        "",
+        &with_instruction(1, "NEWCLOSURE A=0 D=0"),
+        Some(1),
+        "child function 0 is out of range",
+    );
+}
+
+#[test]
+fn functions_must_return() {
+    assert_rejected("", "", None, "function has no instructions");
+}
+
+#[test]
+fn only_invalid_functions_are_rejected() {
+    // a second function that is valid except for a register, and a main that returns it
+    let source = CHUNK
+        .replace("{constants}", "")
+        .replace("{code}", "LOADN A=5 D=0\nRETURN A=0 B=1 C=0")
+        .replace(
+            "main 0",
+            "
+function 1
+max_stack_size 1
+num_parameters 0
+num_upvalues 0
+is_vararg true
+flags 0
+type_info
+line_defined 0
+function_name 0
+functions 0
+code
+PREPVARARGS A=0 B=0 C=0
+NEWCLOSURE A=0 D=0
+RETURN A=0 B=2 C=0
+end
+
+main 1
+",
+        );
+    let bytecode = assembler::assemble_bytecode(&source, &OpcodeDecoder::default()).unwrap();
+    let report = decompile_report(
+        &bytecode,
+        &OpcodeDecoder::default(),
+        &Default::default(),
+        &Budget::default(),
+    );
+    assert_eq!(report.error, None);
+    assert_eq!((report.counts.ok, report.counts.failed), (1, 1));
+    assert!(report.source.contains("return function(...)"));
+    assert!(report
+        .source
+        .contains("function 0, pc 0: register 5 is out of range (stack size 2)"));
+}
@@ -0,0 +1,332 @@
+use std::{
+    io::Read,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use ast::report::DecompileReport;
+use cache::{Cache, Key};
+use luau_lifter::{op_code_decoder::OpcodeDecoder, Budget};
+use rayon::ThreadPool;
+use serde::Serialize;
+use tiny_http::Method;
+
+use crate::query;
+
+/// Everything requests are handled with, so that tests can choose their own limits.
+pub struct Config {
+    pub workers: usize,
+    pub max_size: usize,
+    pub timeout: Duration,
+    pub budget: Budget,
+    pub pool: Arc<ThreadPool>,
+    pub cache: Option<Arc<Cache>>,
+    /// How many decompilations are running, including ones whose requests timed out.
+    pub running: Arc<AtomicUsize>,
+}
+
+#[derive(Debug, Serialize)]
+struct Error {
+    #[serde(skip)]
+    status: u16,
+    code: &'static str,
+    message: String,
+}
+
+impl Error {
+    fn new(status: u16, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: Error,
+}
+
+// a decompilation that is still running, which might outlive its request if it timed out
+struct Slot(Arc<AtomicUsize>);
+
+impl Slot {
+    fn acquire(running: &Arc<AtomicUsize>, limit: usize) -> Option<Self> {
+        running
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (n < limit).then_some(n + 1)
+            })
+            .ok()
+            .map(|_| Self(running.clone()))
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// The parts of a request that are handled, which is a `tiny_http::Request` outside of tests.
+pub struct Request<'a, R> {
+    pub method: &'a Method,
+    pub url: &'a str,
+    /// The length the client declared, if it did.
+    pub body_length: Option<usize>,
+    pub body: R,
+}
+
+impl Config {
+    /// The status, content type and body to answer `request` with.
+    pub fn respond(&self, request: Request<impl Read>) -> (u16, &'static str, String) {
+        match self.decompile(request) {
+            Ok((content_type, body)) => (200, content_type, body),
+            Err(error) => (
+                error.status,
+                "application/json",
+                serde_json::to_string(&ErrorResponse { error }).unwrap(),
+            ),
+        }
+    }
+
+    fn decompile(&self, request: Request<impl Read>) -> Result<(&'static str, String), Error> {
+        let (path, query) = request.url.split_once('?').unwrap_or((request.url, ""));
+        if path != "/decompile" {
+            return Err(Error::new(
+                404,
+                "not_found",
+                format!("no route for {}", path),
+            ));
+        }
+        if *request.method != Method::Post {
+            return Err(Error::new(
+                405,
+                "method_not_allowed",
+                "/decompile only accepts POST",
+            ));
+        }
+        let query = query::parse(query).map_err(|err| Error::new(400, "invalid_query", err))?;
+
+        let too_large = || {
+            Error::new(
+                413,
+                "too_large",
+                format!("the bytecode must be at most {} bytes", self.max_size),
+            )
+        };
+        if request.body_length.is_some_and(|len| len > self.max_size) {
+            return Err(too_large());
+        }
+        let mut bytecode = Vec::new();
+        request
+            .body
+            .take(self.max_size as u64 + 1)
+            .read_to_end(&mut bytecode)
+            .map_err(|err| Error::new(400, "invalid_body", err.to_string()))?;
+        if bytecode.len() > self.max_size {
+            return Err(too_large());
+        }
+        if bytecode.is_empty() {
+            return Err(Error::new(400, "invalid_body", "expected bytecode"));
+        }
+
+        let respond = move |report: DecompileReport| {
+            if query.report {
+                ("application/json", serde_json::to_string(&report).unwrap())
+            } else {
+                ("text/plain; charset=utf-8", report.source)
+            }
+        };
+        let key = Key::new(
+            &bytecode,
+            query.decoder.as_ref(),
+            &query.options,
+            &self.budget,
+        );
+        if let Some(report) = self.cache.as_ref().and_then(|cache| cache.get(&key)) {
+            return Ok(respond(report));
+        }
+
+        // decompilations that timed out keep their slot until they actually finish
+        let slot = Slot::acquire(&self.running, self.workers)
+            .ok_or_else(|| Error::new(503, "busy", "too many decompilations are running"))?;
+        let cache = self.cache.clone();
+        let budget = self.budget.clone();
+        let pool = self.pool.clone();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _slot = slot;
+            let decoder = query
+                .decoder
+                .clone()
+                .unwrap_or_else(|| OpcodeDecoder::detect(&bytecode).unwrap_or_default());
+            let report = pool.install(|| {
+                luau_lifter::decompile_report(&bytecode, &decoder, &query.options, &budget)
+            });
+            // cached even if the request timed out, so that retrying it succeeds
+            if let Some(Err(err)) = cache.map(|cache| cache.insert(key, &report)) {
+                eprintln!("failed to cache {}: {}", key, err);
+            }
+            let _ = sender.send(respond(report));
+        });
+        receiver
+            .recv_timeout(self.timeout)
+            .map_err(|err| match err {
+                RecvTimeoutError::Timeout => Error::new(
+                    504,
+                    "timeout",
+                    format!("decompilation took longer than {:?}", self.timeout),
+                ),
+                RecvTimeoutError::Disconnected => {
+                    Error::new(500, "internal", "the decompiler panicked")
+                }
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use luau_lifter::assembler::assemble_bytecode;
+    use rayon::ThreadPoolBuilder;
+
+    use super::*;
+
+    const LOOPS: &str = include_str!("../../luau-lifter/tests/loops.luauasm");
+
+    fn config() -> Config {
+        Config {
+            workers: 1,
+            max_size: 1024,
+            timeout: Duration::from_secs(30),
+            budget: Budget::default(),
+            pool: Arc::new(ThreadPoolBuilder::new().num_threads(1).build().unwrap()),
+            cache: None,
+            running: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    fn post<'a>(url: &'a str, body: &'a [u8]) -> Request<'a, &'a [u8]> {
+        Request {
+            method: &Method::Post,
+            url,
+            body_length: Some(body.len()),
+            body,
+        }
+    }
+
+    // the status and the code of the JSON error
+    fn error(config: &Config, request: Request<&[u8]>) -> (u16, String) {
+        let (status, content_type, body) = config.respond(request);
+        assert_eq!(content_type, "application/json");
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        (status, body["error"]["code"].as_str().unwrap().to_string())
+    }
+
+    #[test]
+    fn bytecode_is_decompiled() {
+        let bytecode = assemble_bytecode(LOOPS, &OpcodeDecoder::default()).unwrap();
+        let (status, content_type, source) = config().respond(post("/decompile", &bytecode));
+        assert_eq!(status, 200);
+        assert_eq!(content_type, "text/plain; charset=utf-8");
+        assert!(source.contains("for"), "{}", source);
+
+        let (status, content_type, report) =
+            config().respond(post("/decompile?report=true", &bytecode));
+        assert_eq!(status, 200);
+        assert_eq!(content_type, "application/json");
+        let report: DecompileReport = serde_json::from_str(&report).unwrap();
+        assert_eq!(report.source, source);
+    }
+
+    #[test]
+    fn only_posts_to_decompile_are_routed() {
+        let config = config();
+        assert_eq!(
+            error(&config, post("/other", b"bytecode")),
+            (404, "not_found".to_string())
+        );
+        let get = Request {
+            method: &Method::Get,
+            ..post("/decompile", b"bytecode")
+        };
+        assert_eq!(error(&config, get), (405, "method_not_allowed".to_string()));
+    }
+
+    #[test]
+    fn invalid_requests_are_rejected() {
+        let config = config();
+        assert_eq!(
+            error(&config, post("/decompile?indent=wide", b"bytecode")),
+            (400, "invalid_query".to_string())
+        );
+        assert_eq!(
+            error(&config, post("/decompile", b"")),
+            (400, "invalid_body".to_string())
+        );
+    }
+
+    #[test]
+    fn bodies_over_the_size_limit_are_rejected() {
+        let config = Config {
+            max_size: 4,
+            ..config()
+        };
+        assert_eq!(
+            error(&config, post("/decompile", b"12345")),
+            (413, "too_large".to_string())
+        );
+        // without a length the body is only read up to the limit
+        let unknown_length = Request {
+            body_length: None,
+            ..post("/decompile", b"12345")
+        };
+        assert_eq!(
+            error(&config, unknown_length),
+            (413, "too_large".to_string())
+        );
+        // or with a length that is too short
+        let short_length = Request {
+            body_length: Some(1),
+            ..post("/decompile", b"12345")
+        };
+        assert_eq!(error(&config, short_length), (413, "too_large".to_string()));
+    }
+
+    #[test]
+    fn requests_over_the_worker_limit_are_busy() {
+        let bytecode = assemble_bytecode(LOOPS, &OpcodeDecoder::default()).unwrap();
+        let config = Config {
+            workers: 0,
+            ..config()
+        };
+        assert_eq!(
+            error(&config, post("/decompile", &bytecode)),
+            (503, "busy".to_string())
+        );
+    }
+
+    #[test]
+    fn slow_decompilations_time_out_and_keep_their_slot_until_they_finish() {
+        let bytecode = assemble_bytecode(LOOPS, &OpcodeDecoder::default()).unwrap();
+        let config = Config {
+            timeout: Duration::ZERO,
+            ..config()
+        };
+        assert_eq!(
+            error(&config, post("/decompile", &bytecode)),
+            (504, "timeout".to_string())
+        );
+        // the decompilation goes on without its request
+        let start = std::time::Instant::now();
+        while config.running.load(Ordering::Acquire) != 0 {
+            assert!(start.elapsed() < Duration::from_secs(30));
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
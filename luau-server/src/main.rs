@@ -0,0 +1,148 @@
+mod handler;
+mod query;
+
+use std::{
+    io::Cursor,
+    num::NonZeroUsize,
+    path::PathBuf,
+    sync::{atomic::AtomicUsize, Arc},
+    thread,
+    time::Duration,
+};
+
+use cache::Cache;
+use clap::Parser;
+use handler::Config;
+use luau_lifter::Budget;
+use rayon::ThreadPoolBuilder;
+use tiny_http::{Header, Request, Response, Server};
+
+/// Decompiles the Luau bytecode in the body of `POST /decompile` requests.
+#[derive(Parser)]
+struct Args {
+    #[arg(long, default_value = "127.0.0.1:1234")]
+    address: String,
+    /// How many requests are handled at once [default: number of CPUs]
+    #[arg(long)]
+    workers: Option<usize>,
+    /// How many functions are decompiled at once, across all requests [default: number of CPUs]
+    #[arg(long, default_value_t = 0, hide_default_value = true)]
+    threads: usize,
+    /// The largest body accepted, in bytes
+    #[arg(long, default_value_t = 8 * 1024 * 1024)]
+    max_size: usize,
+    /// How many seconds a decompilation may take
+    #[arg(long, default_value_t = 30)]
+    timeout: u64,
+    /// How many times the structuring passes may run for each function
+    #[arg(long, default_value_t = Budget::default().max_iterations)]
+    max_iterations: usize,
+    /// How many seconds the structuring passes may take for each function [default: --timeout]
+    #[arg(long)]
+    function_timeout: Option<u64>,
+    /// How many decompilations are kept in memory, 0 disables the cache
+    #[arg(long, default_value_t = 1024)]
+    cache_size: usize,
+    /// Where to keep every decompilation, so that they survive restarts
+    #[arg(long)]
+    cache_dir: Option<PathBuf>,
+}
+
+impl Args {
+    fn budget(&self) -> Budget {
+        Budget {
+            max_iterations: self.max_iterations,
+            // a function that would take longer than the request is cut short, so that it
+            // doesn't keep its slot long after its request timed out
+            time_limit: Some(Duration::from_secs(
+                self.function_timeout.unwrap_or(self.timeout),
+            )),
+        }
+    }
+}
+
+fn respond(mut request: Request, config: &Config) {
+    let method = request.method().clone();
+    let url = request.url().to_string();
+    let (status, content_type, body) = config.respond(handler::Request {
+        method: &method,
+        url: &url,
+        body_length: request.body_length(),
+        body: request.as_reader(),
+    });
+    let response = Response::new(
+        status.into(),
+        vec![Header::from_bytes("Content-Type", content_type).unwrap()],
+        Cursor::new(body.into_bytes()),
+        None,
+        None,
+    );
+    if let Err(err) = request.respond(response) {
+        eprintln!("failed to respond: {}", err);
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+    let config = Arc::new(Config {
+        workers: args.workers.unwrap_or_else(|| {
+            thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1)
+        }),
+        max_size: args.max_size,
+        timeout: Duration::from_secs(args.timeout),
+        budget: args.budget(),
+        pool: Arc::new(
+            ThreadPoolBuilder::new()
+                .num_threads(args.threads)
+                .build()
+                .expect("failed to start the threads"),
+        ),
+        cache: NonZeroUsize::new(args.cache_size).map(|capacity| {
+            let cache = Cache::new(capacity);
+            Arc::new(match args.cache_dir {
+                Some(directory) => cache.with_directory(directory),
+                None => cache,
+            })
+        }),
+        running: Arc::new(AtomicUsize::new(0)),
+    });
+    let server = Arc::new(Server::http(&args.address).expect("failed to start the server"));
+    println!("listening on {}", args.address);
+
+    let workers = (0..config.workers)
+        .map(|_| {
+            let server = server.clone();
+            let config = config.clone();
+            thread::spawn(move || {
+                while let Ok(request) = server.recv() {
+                    respond(request, &config);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn functions_time_out_with_their_request_by_default() {
+        let budget = |args: &[&str]| {
+            Args::parse_from(std::iter::once("luau-server").chain(args.iter().copied())).budget()
+        };
+        assert_eq!(
+            budget(&["--timeout", "5"]).time_limit,
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            budget(&["--timeout", "5", "--function-timeout", "1"]).time_limit,
+            Some(Duration::from_secs(1))
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use ast::formatter::{
+    Dialect, FormatOptions, IndentationMode, QuoteStyle, SemicolonPolicy, TableSeparator,
+};
+use luau_lifter::op_code_decoder::OpcodeDecoder;
+
+/// What a request asked for. A missing `decoder` means the key should be detected.
+pub struct Query {
+    pub decoder: Option<OpcodeDecoder>,
+    pub options: FormatOptions,
+    /// Whether to answer with a JSON `DecompileReport` instead of the source.
+    pub report: bool,
+}
+
+fn parse_value<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {}: {}", name, value))
+}
+
+/// Parses the query string of a `/decompile` request, e.g.
+/// `key=203&indent=tab&quotes=single&max_line_width=100`.
+pub fn parse(query: &str) -> Result<Query, String> {
+    let mut decoder = None;
+    let mut report = false;
+    let mut options = FormatOptions {
+        dialect: Dialect::Luau,
+        // server.py replaced tabs with four spaces
+        indentation_mode: IndentationMode::Spaces(4),
+        ..Default::default()
+    };
+    for (name, value) in form_urlencoded::parse(query.as_bytes()) {
+        match (&*name, &*value) {
+            ("key", "auto") => decoder = None,
+            ("key", key) => decoder = Some(OpcodeDecoder::Multiplicative(parse_value(&name, key)?)),
+            ("indent", "tab") => options.indentation_mode = IndentationMode::Tab,
+            ("indent", spaces) => {
+                options.indentation_mode = IndentationMode::Spaces(parse_value(&name, spaces)?)
+            }
+            ("max_line_width", "none") => options.max_line_width = None,
+            ("max_line_width", width) => options.max_line_width = Some(parse_value(&name, width)?),
+            ("quotes", "double") => options.quote_style = QuoteStyle::Double,
+            ("quotes", "single") => options.quote_style = QuoteStyle::Single,
+            ("table_separator", "comma") => options.table_separator = TableSeparator::Comma,
+            ("table_separator", "semicolon") => options.table_separator = TableSeparator::Semicolon,
+            ("trailing_separator", value) => {
+                options.trailing_separator = parse_value(&name, value)?
+            }
+            ("semicolons", "ambiguous") => options.semicolons = SemicolonPolicy::WhenAmbiguous,
+            ("semicolons", "always") => options.semicolons = SemicolonPolicy::Always,
+            ("blank_lines", lines) => {
+                options.blank_lines_between_functions = parse_value(&name, lines)?
+            }
+            ("function_headers", value) => options.function_headers = parse_value(&name, value)?,
+            ("report", value) => report = parse_value(&name, value)?,
+            ("quotes" | "table_separator" | "semicolons", value) => {
+                return Err(format!("invalid value for {}: {}", name, value))
+            }
+            (name, _) => return Err(format!("unknown query parameter: {}", name)),
+        }
+    }
+    Ok(Query {
+        decoder,
+        options,
+        report,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_match_the_old_server() {
+        let query = parse("").unwrap();
+        assert_eq!(query.decoder, None);
+        assert!(!query.report);
+        assert_eq!(query.options.dialect, Dialect::Luau);
+        assert_eq!(query.options.indentation_mode, IndentationMode::Spaces(4));
+    }
+
+    #[test]
+    fn options_are_parsed() {
+        let query = parse(
+            "key=203&indent=tab&quotes=single&max_line_width=100&table_separator=semicolon\
+             &trailing_separator=true&semicolons=always&blank_lines=2&function_headers=true\
+             &report=true",
+        )
+        .unwrap();
+        assert_eq!(query.decoder, Some(OpcodeDecoder::Multiplicative(203)));
+        assert!(query.report);
+        assert_eq!(
+            query.options,
+            FormatOptions {
+                dialect: Dialect::Luau,
+                indentation_mode: IndentationMode::Tab,
+                quote_style: QuoteStyle::Single,
+                max_line_width: Some(100),
+                table_separator: TableSeparator::Semicolon,
+                trailing_separator: true,
+                semicolons: SemicolonPolicy::Always,
+                blank_lines_between_functions: 2,
+                function_headers: true,
+            }
+        );
+        assert_eq!(parse("key=203&key=auto").unwrap().decoder, None);
+        assert_eq!(
+            parse("indent=2&max_line_width=none").unwrap().options,
+            FormatOptions {
+                dialect: Dialect::Luau,
+                indentation_mode: IndentationMode::Spaces(2),
+                max_line_width: None,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_queries_are_rejected() {
+        for (query, error) in [
+            ("key=256", "invalid value for key: 256"),
+            ("indent=wide", "invalid value for indent: wide"),
+            ("quotes=backtick", "invalid value for quotes: backtick"),
+            ("report=yes", "invalid value for report: yes"),
+            ("colour=red", "unknown query parameter: colour"),
+        ] {
+            assert_eq!(parse(query).err().as_deref(), Some(error));
+        }
+    }
+}
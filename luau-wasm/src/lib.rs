@@ -0,0 +1,61 @@
+use ast::formatter::{Dialect, FormatOptions};
+use luau_lifter::{
+    decompile_report, deserializer, disassembler, op_code_decoder::OpcodeDecoder, Budget,
+};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+/// The second argument of `decompile`, e.g. `{key: 203, format: {max_line_width: 100}}`.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct Options {
+    /// The opcode key, which is detected when missing.
+    key: Option<u8>,
+    /// How many times the structuring passes may run for each function.
+    max_iterations: Option<usize>,
+    format: FormatOptions,
+}
+
+fn decoder(bytecode: &[u8], key: Option<u8>) -> OpcodeDecoder {
+    key.map(OpcodeDecoder::Multiplicative)
+        .unwrap_or_else(|| OpcodeDecoder::detect(bytecode).unwrap_or_default())
+}
+
+/// Decompiles Luau bytecode into a `DecompileReport`, whose `source` is the decompilation and
+/// whose `error` says why there is none. Only throws when the options are malformed.
+///
+/// There is no clock, so functions are only limited by iterations, and a panic can't be caught,
+/// so it takes down the whole instance instead of a single function.
+#[wasm_bindgen]
+pub fn decompile(bytecode: &[u8], options: JsValue) -> Result<JsValue, JsError> {
+    console_error_panic_hook::set_once();
+
+    let options: Options = if options.is_undefined() || options.is_null() {
+        Options::default()
+    } else {
+        serde_wasm_bindgen::from_value(options)?
+    };
+    let format = FormatOptions {
+        dialect: Dialect::Luau,
+        ..options.format
+    };
+    let budget = Budget {
+        max_iterations: options
+            .max_iterations
+            .unwrap_or(Budget::default().max_iterations),
+        time_limit: None,
+    };
+    let report = decompile_report(bytecode, &decoder(bytecode, options.key), &format, &budget);
+    // plain objects instead of `Map`s, so that the result can go through `JSON.stringify`
+    Ok(report.serialize(&serde_wasm_bindgen::Serializer::json_compatible())?)
+}
+
+/// Lists the instructions of every function in the format that `luau_lifter::assembler` reads.
+#[wasm_bindgen]
+pub fn disassemble(bytecode: &[u8], key: Option<u8>) -> Result<String, JsError> {
+    console_error_panic_hook::set_once();
+
+    deserializer::deserialize(bytecode, &decoder(bytecode, key))
+        .map(|bytecode| disassembler::disassemble(&bytecode))
+        .map_err(|err| JsError::new(&err))
+}
@@ -0,0 +1,67 @@
+// run with `wasm-pack test --node luau-wasm`
+#![cfg(target_arch = "wasm32")]
+
+use ast::{
+    formatter::{Dialect, FormatOptions},
+    report::DecompileReport,
+};
+use js_sys::JSON;
+use luau_lifter::{
+    assembler::assemble_bytecode, decompile_bytecode, op_code_decoder::OpcodeDecoder,
+};
+use wasm_bindgen::JsValue;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+const LOOPS: &str = include_str!("../../luau-lifter/tests/loops.luauasm");
+
+fn luau() -> FormatOptions {
+    FormatOptions {
+        dialect: Dialect::Luau,
+        ..Default::default()
+    }
+}
+
+#[wasm_bindgen_test]
+fn decompiles_to_a_report() {
+    let bytecode = assemble_bytecode(LOOPS, &OpcodeDecoder::Multiplicative(203)).unwrap();
+    let report: DecompileReport = serde_wasm_bindgen::from_value(
+        luau_wasm::decompile(&bytecode, JsValue::UNDEFINED).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(report.error, None);
+    assert_eq!(report.key, Some(203));
+    assert_eq!(
+        report.source,
+        decompile_bytecode(&bytecode, &OpcodeDecoder::Multiplicative(203), &luau())
+    );
+}
+
+#[wasm_bindgen_test]
+fn options_are_read_from_an_object() {
+    let bytecode = assemble_bytecode(LOOPS, &OpcodeDecoder::Multiplicative(1)).unwrap();
+    let options = JSON::parse(r#"{"key": 1, "format": {"function_headers": true}}"#).unwrap();
+    let report: DecompileReport =
+        serde_wasm_bindgen::from_value(luau_wasm::decompile(&bytecode, options).unwrap()).unwrap();
+    let format = FormatOptions {
+        function_headers: true,
+        ..luau()
+    };
+    assert_eq!(
+        report.source,
+        decompile_bytecode(&bytecode, &OpcodeDecoder::default(), &format)
+    );
+
+    let options = JSON::parse(r#"{"key": "one"}"#).unwrap();
+    assert!(luau_wasm::decompile(&bytecode, options).is_err());
+}
+
+#[wasm_bindgen_test]
+fn disassembly_assembles_to_the_same_bytecode() {
+    let bytecode = assemble_bytecode(LOOPS, &OpcodeDecoder::Multiplicative(203)).unwrap();
+    let listing = luau_wasm::disassemble(&bytecode, None).unwrap();
+    assert_eq!(
+        assemble_bytecode(&listing, &OpcodeDecoder::Multiplicative(203)).unwrap(),
+        bytecode
+    );
+    assert!(luau_wasm::disassemble(b"\x06garbage", Some(1)).is_err());
+}
@@ -1,23 +1,40 @@
+mod protocol;
+mod routes;
+
 use futures_util::StreamExt;
 extern crate console_error_panic_hook;
 
-use base64::prelude::*;
-use luau_lifter::decompile_bytecode;
-use serde::{Deserialize, Serialize};
+use protocol::{Bindings, Error, ErrorResponse, PartialResponse};
+use routes::HttpRequest;
+use std::time::Duration;
 use worker::*;
 
-const AUTH_SECRET: &str = "ymjKH2O3BbO3bDSsKmpo3ek3vHxIWYLQfj0";
+impl Bindings for Env {
+    fn get(&self, name: &str) -> Option<String> {
+        self.secret(name)
+            .or_else(|_| self.var(name))
+            .ok()
+            .map(|binding| binding.to_string())
+    }
+}
+
+impl HttpRequest for Request {
+    fn header(&self, name: &str) -> Option<String> {
+        self.headers().get(name).ok().flatten()
+    }
 
-#[derive(Deserialize)]
-struct DecompileMessage {
-    id: String,
-    encoded_bytecode: String,
+    fn query(&self, name: &str) -> Option<String> {
+        self.url()
+            .ok()?
+            .query_pairs()
+            .find(|(pair, _)| pair == name)
+            .map(|(_, value)| value.into_owned())
+    }
 }
 
-#[derive(Serialize)]
-struct DecompileResponse {
-    id: String,
-    decompilation: String,
+fn error_response(error: Error) -> Result<Response> {
+    let status = error.status;
+    Ok(Response::from_json(&ErrorResponse { error })?.with_status(status))
 }
 
 #[event(fetch, respond_with_errors)]
@@ -26,61 +43,53 @@ pub async fn main(req: Request, env: Env, _ctx: worker::Context) -> Result<Respo
 
     let router = Router::new();
     router
-        .get_async("/decompile_ws", |req, _ctx| async move {
-            let license = req
-                .headers()
-                .get("Authorization")
-                .unwrap_or_default()
-                .expect("authorization header is required");
-
-            if license != AUTH_SECRET {
-                return Response::error("invalid license", 403);
-            }
+        .get_async("/decompile_ws", |req, ctx| async move {
+            let config = match routes::open_socket(&ctx.env, &req) {
+                Ok(config) => config,
+                Err(error) => return error_response(error),
+            };
 
             let pair = WebSocketPair::new()?;
             let server = pair.server;
             server.accept()?;
 
             wasm_bindgen_futures::spawn_local(async move {
-                let mut event_stream = server.events().expect("could not open stream");
-                while let Some(event) = event_stream.next().await {
-                    if let WebsocketEvent::Message(msg) =
-                        event.expect("received error in websocket")
-                    {
-                        let msg = msg
-                            .json::<DecompileMessage>()
-                            .expect("malformed decompile message");
-                        let bytecode = BASE64_STANDARD
-                            .decode(msg.encoded_bytecode)
-                            .expect("bytecode must be base64 encoded");
-                        let resp = DecompileResponse {
-                            id: msg.id,
-                            decompilation: decompile_bytecode(&bytecode, 1),
+                let Ok(mut event_stream) = server.events() else {
+                    return;
+                };
+                while let Some(Ok(event)) = event_stream.next().await {
+                    if let WebsocketEvent::Message(msg) = event {
+                        // a socket that was closed is noticed once the response is sent
+                        let send = |partial: PartialResponse| {
+                            let _ = server.send_with_str(serde_json::to_string(&partial).unwrap());
                         };
-                        server
+                        let mut answer =
+                            routes::answer_message(&config, msg.text().as_deref(), send);
+                        let resp = loop {
+                            if let Some(resp) = answer.step(send) {
+                                break resp;
+                            }
+                            // decompiling blocks the isolate, so the parts are only sent
+                            // while it waits for the next task
+                            Delay::from(Duration::ZERO).await;
+                        };
+                        if server
                             .send_with_str(serde_json::to_string(&resp).unwrap())
-                            .unwrap();
+                            .is_err()
+                        {
+                            break;
+                        }
                     }
                 }
             });
 
             Response::from_websocket(pair.client)
         })
-        .post_async("/decompile", |mut req, _ctx| async move {
-            let license = req
-                .headers()
-                .get("Authorization")
-                .unwrap_or_default()
-                .expect("authorization header is required");
-
-            if license != AUTH_SECRET {
-                return Response::error("invalid license", 403);
-            }
-
+        .post_async("/decompile", |mut req, ctx| async move {
             let encoded_bytecode = req.bytes().await?;
-            match BASE64_STANDARD.decode(encoded_bytecode) {
-                Ok(bytecode) => Response::ok(decompile_bytecode(&bytecode, 203)),
-                Err(_) => Response::error("invalid bytecode", 400),
+            match routes::decompile(&ctx.env, &req, &encoded_bytecode) {
+                Ok(decompilation) => Response::ok(decompilation),
+                Err(error) => error_response(error),
             }
         })
         .run(req, env)
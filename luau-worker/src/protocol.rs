@@ -0,0 +1,680 @@
+use std::{num::NonZeroUsize, sync::OnceLock};
+
+use ast::{
+    formatter::{Dialect, FormatOptions},
+    report::DecompileReport,
+};
+use base64::prelude::*;
+use cache::{Cache, Key};
+use luau_lifter::{op_code_decoder::OpcodeDecoder, Budget, Decompilation, Event};
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_MAX_BYTECODE_SIZE: usize = 4 * 1024 * 1024;
+const DEFAULT_CACHE_SIZE: usize = 256;
+
+// isolates handle many requests, so the cache outlives the config read for each one
+static CACHE: OnceLock<Option<Cache>> = OnceLock::new();
+
+/// Where the configuration comes from, which is the worker's `Env` outside of tests.
+pub trait Bindings {
+    /// The secret or variable bound to `name`.
+    fn get(&self, name: &str) -> Option<String>;
+}
+
+#[cfg(test)]
+impl Bindings for std::collections::HashMap<&'static str, &'static str> {
+    fn get(&self, name: &str) -> Option<String> {
+        std::collections::HashMap::get(self, name).map(|value| value.to_string())
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Error {
+    #[serde(skip)]
+    pub status: u16,
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl Error {
+    pub fn new(status: u16, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn misconfigured(message: &str) -> Self {
+        Self::new(500, "misconfigured", message)
+    }
+}
+
+#[derive(Serialize)]
+pub struct ErrorResponse {
+    pub error: Error,
+}
+
+#[derive(Deserialize)]
+pub struct DecompileMessage {
+    pub id: String,
+    pub encoded_bytecode: String,
+    /// The opcode key, which is detected when missing.
+    #[serde(default)]
+    pub key: Option<u8>,
+    #[serde(default)]
+    pub options: FormatOptions,
+    /// Whether to answer with a `DecompileReport` along with the decompilation.
+    #[serde(default)]
+    pub report: bool,
+    /// Whether to send `PartialResponse`s before the response, which then leaves out the
+    /// decompilation.
+    #[serde(default)]
+    pub stream: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Partial {
+    /// Another function was decompiled, `finished` of `total` so far.
+    Progress { finished: usize, total: usize },
+    /// The next part of the decompilation.
+    Output(String),
+}
+
+/// Part of the answer to a `stream` message, e.g. `{"id": "a", "output": "local v1 = 1"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PartialResponse {
+    pub id: String,
+    #[serde(flatten)]
+    pub partial: Partial,
+}
+
+#[derive(Serialize)]
+pub struct DecompileResponse {
+    /// Missing when the message couldn't be parsed.
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decompilation: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub report: Option<DecompileReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<Error>,
+}
+
+impl DecompileResponse {
+    pub fn error(id: Option<String>, error: Error) -> Self {
+        Self {
+            id,
+            decompilation: None,
+            report: None,
+            error: Some(error),
+        }
+    }
+}
+
+fn list(value: Option<String>) -> Vec<String> {
+    value
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// compares every byte so that the time taken doesn't reveal how much of a secret matched
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+pub struct Config {
+    /// The secrets of every license that hasn't been revoked.
+    secrets: Vec<String>,
+    max_bytecode_size: usize,
+    budget: Budget,
+    cache: Option<&'static Cache>,
+}
+
+impl Config {
+    /// Reads `AUTH_KEYS`, a comma separated list of `name:secret` licenses, `REVOKED_KEYS`,
+    /// the names of licenses that are no longer accepted, `MAX_BYTECODE_SIZE` in bytes,
+    /// `MAX_ITERATIONS` of the structuring passes for each function and `CACHE_SIZE`,
+    /// how many decompilations to remember, which only takes effect once.
+    pub fn from_bindings(bindings: &impl Bindings) -> Result<Self, Error> {
+        let revoked = list(bindings.get("REVOKED_KEYS"));
+        let mut secrets = Vec::new();
+        for license in
+            list(Some(bindings.get("AUTH_KEYS").ok_or_else(|| {
+                Error::misconfigured("AUTH_KEYS is not bound")
+            })?))
+        {
+            let (name, secret) = license
+                .split_once(':')
+                .ok_or_else(|| Error::misconfigured("AUTH_KEYS must contain name:secret pairs"))?;
+            if !revoked.iter().any(|revoked| revoked == name.trim()) {
+                secrets.push(secret.trim().to_string());
+            }
+        }
+        let max_bytecode_size = match bindings.get("MAX_BYTECODE_SIZE") {
+            Some(size) => size
+                .trim()
+                .parse()
+                .map_err(|_| Error::misconfigured("MAX_BYTECODE_SIZE must be a number of bytes"))?,
+            None => DEFAULT_MAX_BYTECODE_SIZE,
+        };
+        let max_iterations = match bindings.get("MAX_ITERATIONS") {
+            Some(iterations) => iterations
+                .trim()
+                .parse()
+                .map_err(|_| Error::misconfigured("MAX_ITERATIONS must be a number"))?,
+            None => Budget::default().max_iterations,
+        };
+        let cache_size = match bindings.get("CACHE_SIZE") {
+            Some(size) => size
+                .trim()
+                .parse()
+                .map_err(|_| Error::misconfigured("CACHE_SIZE must be a number of entries"))?,
+            None => DEFAULT_CACHE_SIZE,
+        };
+        Ok(Self {
+            secrets,
+            max_bytecode_size,
+            // there is no clock on wasm, so only the iterations can be limited
+            budget: Budget {
+                max_iterations,
+                time_limit: None,
+            },
+            cache: CACHE
+                .get_or_init(|| NonZeroUsize::new(cache_size).map(Cache::new))
+                .as_ref(),
+        })
+    }
+
+    fn start_decompiling(
+        &self,
+        bytecode: &[u8],
+        key: Option<u8>,
+        options: &FormatOptions,
+        mut send: impl FnMut(Partial),
+    ) -> Decompiling {
+        let options = FormatOptions {
+            dialect: Dialect::Luau,
+            ..options.clone()
+        };
+        let decoder = key.map(OpcodeDecoder::Multiplicative);
+        let key = Key::new(bytecode, decoder.as_ref(), &options, &self.budget);
+        if let Some(report) = self.cache.and_then(|cache| cache.get(&key)) {
+            return Decompiling::Cached(report);
+        }
+        let decoder =
+            decoder.unwrap_or_else(|| OpcodeDecoder::detect(bytecode).unwrap_or_default());
+        let mut source = String::new();
+        let decompilation = Box::new(Decompilation::new(
+            bytecode,
+            &decoder,
+            &options,
+            &self.budget,
+            |event| forward(event, &mut source, &mut send),
+        ));
+        Decompiling::Running {
+            decompilation,
+            key,
+            source,
+        }
+    }
+
+    fn decompile(
+        &self,
+        bytecode: &[u8],
+        key: Option<u8>,
+        options: &FormatOptions,
+        mut send: impl FnMut(Partial),
+    ) -> DecompileReport {
+        let mut decompiling = self.start_decompiling(bytecode, key, options, &mut send);
+        loop {
+            if let Some(report) = decompiling.step(self.cache, &mut send) {
+                return report;
+            }
+        }
+    }
+
+    /// Checks the `Authorization` header of a request.
+    pub fn authorize(&self, authorization: Option<&str>) -> Result<(), Error> {
+        let authorization = authorization.ok_or_else(|| {
+            Error::new(401, "unauthorized", "the Authorization header is required")
+        })?;
+        if self.secrets.iter().fold(false, |matched, secret| {
+            constant_time_eq(secret.as_bytes(), authorization.as_bytes()) | matched
+        }) {
+            Ok(())
+        } else {
+            Err(Error::new(403, "forbidden", "invalid license"))
+        }
+    }
+
+    fn decode_bytecode(&self, encoded_bytecode: &[u8]) -> Result<Vec<u8>, Error> {
+        let too_large = || {
+            Error::new(
+                413,
+                "too_large",
+                format!(
+                    "the bytecode must be at most {} bytes",
+                    self.max_bytecode_size
+                ),
+            )
+        };
+        // every 4 base64 characters encode 3 bytes
+        if encoded_bytecode.len() / 4 * 3 > self.max_bytecode_size + 2 {
+            return Err(too_large());
+        }
+        let bytecode = BASE64_STANDARD
+            .decode(encoded_bytecode)
+            .map_err(|_| Error::new(400, "invalid_bytecode", "bytecode must be base64 encoded"))?;
+        if bytecode.len() > self.max_bytecode_size {
+            return Err(too_large());
+        }
+        Ok(bytecode)
+    }
+
+    /// Starts answering a websocket message, which is a JSON `DecompileMessage`. `send` gets
+    /// the partial responses to messages that asked to be streamed.
+    pub fn answer(&self, message: &str, mut send: impl FnMut(PartialResponse)) -> Answer<'_> {
+        let mut message = match serde_json::from_str::<DecompileMessage>(message) {
+            Ok(message) => message,
+            Err(err) => return self.reject(Error::new(400, "malformed_message", err.to_string())),
+        };
+        match self.decode_bytecode(message.encoded_bytecode.as_bytes()) {
+            Ok(bytecode) => {
+                message.encoded_bytecode = String::new();
+                let decompiling =
+                    self.start_decompiling(&bytecode, message.key, &message.options, |partial| {
+                        message.send(partial, &mut send)
+                    });
+                Answer {
+                    config: self,
+                    state: AnswerState::Decompiling {
+                        message,
+                        decompiling,
+                    },
+                }
+            }
+            Err(error) => Answer::answered(self, DecompileResponse::error(Some(message.id), error)),
+        }
+    }
+
+    /// Answers a websocket message with `error` instead of reading it.
+    pub fn reject(&self, error: Error) -> Answer<'_> {
+        Answer::answered(self, DecompileResponse::error(None, error))
+    }
+
+    /// Handles `POST /decompile`, whose body is the base64 encoded bytecode. The key can be
+    /// given with the `key` query parameter.
+    pub fn handle_request(
+        &self,
+        authorization: Option<&str>,
+        key: Option<&str>,
+        body: &[u8],
+    ) -> Result<String, Error> {
+        self.authorize(authorization)?;
+        let key = key
+            .map(|key| {
+                key.parse()
+                    .map_err(|_| Error::new(400, "invalid_key", format!("invalid key: {}", key)))
+            })
+            .transpose()?;
+        let bytecode = self.decode_bytecode(body)?;
+        Ok(self
+            .decompile(&bytecode, key, &FormatOptions::default(), |_| {})
+            .source)
+    }
+}
+
+// hands an event on as a partial response and keeps the output for the report
+fn forward(event: Event, source: &mut String, send: &mut impl FnMut(Partial)) {
+    send(match event {
+        Event::Progress { finished, total } => Partial::Progress { finished, total },
+        Event::Output(output) => {
+            source.push_str(output);
+            Partial::Output(output.to_string())
+        }
+    })
+}
+
+impl DecompileMessage {
+    fn send(&self, partial: Partial, send: &mut impl FnMut(PartialResponse)) {
+        if self.stream {
+            send(PartialResponse {
+                id: self.id.clone(),
+                partial,
+            })
+        }
+    }
+}
+
+enum Decompiling {
+    // a cached decompilation is sent as a single part
+    Cached(DecompileReport),
+    Running {
+        decompilation: Box<Decompilation>,
+        key: Key,
+        source: String,
+    },
+}
+
+impl Decompiling {
+    // decompiles the next function and returns the report once every function is decompiled,
+    // which is cached then
+    fn step(
+        &mut self,
+        cache: Option<&Cache>,
+        mut send: impl FnMut(Partial),
+    ) -> Option<DecompileReport> {
+        match self {
+            Self::Cached(report) => {
+                send(Partial::Output(report.source.clone()));
+                Some(std::mem::take(report))
+            }
+            Self::Running {
+                decompilation,
+                source,
+                ..
+            } => {
+                if decompilation.step(|event| forward(event, source, &mut send)) {
+                    return None;
+                }
+                let Self::Running {
+                    decompilation,
+                    key,
+                    source,
+                } = std::mem::replace(self, Self::Cached(DecompileReport::default()))
+                else {
+                    unreachable!()
+                };
+                let mut report = decompilation.finish();
+                report.source = source;
+                if let Some(cache) = cache {
+                    // only kept in memory, so caching can't fail
+                    cache.insert(key, &report).unwrap();
+                }
+                Some(report)
+            }
+        }
+    }
+}
+
+/// A websocket message that is answered a function at a time with `step`, so that the
+/// partial responses can be sent in between instead of once everything is decompiled.
+pub struct Answer<'a> {
+    config: &'a Config,
+    state: AnswerState,
+}
+
+enum AnswerState {
+    // until the response is taken
+    Answered(Option<DecompileResponse>),
+    Decompiling {
+        message: DecompileMessage,
+        decompiling: Decompiling,
+    },
+}
+
+impl<'a> Answer<'a> {
+    fn answered(config: &'a Config, response: DecompileResponse) -> Self {
+        Self {
+            config,
+            state: AnswerState::Answered(Some(response)),
+        }
+    }
+
+    /// Decompiles the next function and returns the response once the message is answered.
+    pub fn step(&mut self, mut send: impl FnMut(PartialResponse)) -> Option<DecompileResponse> {
+        match &mut self.state {
+            AnswerState::Answered(response) => response.take(),
+            AnswerState::Decompiling {
+                message,
+                decompiling,
+            } => {
+                let mut report = decompiling.step(self.config.cache, |partial| {
+                    message.send(partial, &mut send)
+                })?;
+                let source = std::mem::take(&mut report.source);
+                let response = DecompileResponse {
+                    id: Some(message.id.clone()),
+                    decompilation: (!message.stream).then_some(source),
+                    report: message.report.then_some(report),
+                    error: None,
+                };
+                self.state = AnswerState::Answered(None);
+                Some(response)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use luau_lifter::assembler::assemble_bytecode;
+
+    use super::*;
+
+    fn config(bindings: &[(&'static str, &'static str)]) -> Result<Config, Error> {
+        Config::from_bindings(&bindings.iter().copied().collect::<HashMap<_, _>>())
+    }
+
+    // answers a websocket message all at once
+    fn handle_message(
+        config: &Config,
+        message: &str,
+        mut send: impl FnMut(PartialResponse),
+    ) -> DecompileResponse {
+        let mut answer = config.answer(message, &mut send);
+        loop {
+            if let Some(response) = answer.step(&mut send) {
+                return response;
+            }
+        }
+    }
+
+    fn encoded_bytecode(key: u8) -> String {
+        let source = include_str!("../../luau-lifter/tests/loops.luauasm");
+        BASE64_STANDARD
+            .encode(assemble_bytecode(source, &OpcodeDecoder::Multiplicative(key)).unwrap())
+    }
+
+    #[test]
+    fn revoked_licenses_are_rejected() {
+        let config = config(&[
+            ("AUTH_KEYS", "alice:first, bob:second"),
+            ("REVOKED_KEYS", "bob"),
+        ])
+        .unwrap();
+        assert!(config.authorize(Some("first")).is_ok());
+        assert_eq!(config.authorize(Some("second")).unwrap_err().status, 403);
+        assert_eq!(config.authorize(Some("firs")).unwrap_err().status, 403);
+        assert_eq!(config.authorize(None).unwrap_err().status, 401);
+    }
+
+    #[test]
+    fn missing_licenses_are_a_configuration_error() {
+        assert_eq!(config(&[]).err().unwrap().code, "misconfigured");
+        assert_eq!(
+            config(&[("AUTH_KEYS", "secret")]).err().unwrap().code,
+            "misconfigured"
+        );
+    }
+
+    #[test]
+    fn malformed_messages_get_an_error_response() {
+        let config = config(&[("AUTH_KEYS", "alice:first")]).unwrap();
+        let response = handle_message(&config, "{\"id\": 1}", |_| {});
+        assert!(response.id.is_none());
+        assert_eq!(response.error.unwrap().code, "malformed_message");
+
+        let response = handle_message(&config, r#"{"id": "a", "encoded_bytecode": "!"}"#, |_| {});
+        assert_eq!(response.id.as_deref(), Some("a"));
+        assert_eq!(response.error.unwrap().code, "invalid_bytecode");
+    }
+
+    #[test]
+    fn large_bytecode_is_rejected() {
+        let config = config(&[("AUTH_KEYS", "alice:first"), ("MAX_BYTECODE_SIZE", "16")]).unwrap();
+        let message = serde_json::json!({ "id": "a", "encoded_bytecode": encoded_bytecode(1) });
+        let response = handle_message(&config, &message.to_string(), |_| {});
+        assert_eq!(response.error.unwrap().status, 413);
+    }
+
+    #[test]
+    fn messages_choose_the_key_and_options() {
+        let config = config(&[("AUTH_KEYS", "alice:first")]).unwrap();
+        let message = serde_json::json!({
+            "id": "a",
+            "encoded_bytecode": encoded_bytecode(203),
+            "key": 203,
+            "options": { "quote_style": "single" },
+        });
+        let response = handle_message(&config, &message.to_string(), |_| {});
+        assert!(response.error.is_none());
+        assert!(response.decompilation.unwrap().contains("'number'"));
+
+        // the key is detected when it is missing
+        let message = serde_json::json!({ "id": "a", "encoded_bytecode": encoded_bytecode(203) });
+        let response = handle_message(&config, &message.to_string(), |_| {});
+        assert!(response.decompilation.unwrap().contains("\"number\""));
+    }
+
+    #[test]
+    fn reports_are_only_sent_when_asked_for() {
+        let config = config(&[("AUTH_KEYS", "alice:first")]).unwrap();
+        let message = serde_json::json!({ "id": "a", "encoded_bytecode": encoded_bytecode(203) });
+        let response = handle_message(&config, &message.to_string(), |_| {});
+        assert!(response.report.is_none());
+
+        let message = serde_json::json!({
+            "id": "a",
+            "encoded_bytecode": encoded_bytecode(203),
+            "report": true,
+        });
+        let response =
+            serde_json::to_value(handle_message(&config, &message.to_string(), |_| {})).unwrap();
+        assert_eq!(response["report"]["key"], 203);
+        assert_eq!(response["report"]["functions"][0]["status"], "ok");
+        // the source is only sent once
+        assert!(response["report"].get("source").is_none());
+    }
+
+    #[test]
+    fn streamed_messages_are_answered_in_parts() {
+        let config = config(&[("AUTH_KEYS", "alice:first")]).unwrap();
+        // options no other test uses, so that nothing is cached yet
+        let options = serde_json::json!({ "function_headers": true });
+        let message = serde_json::json!({
+            "id": "a",
+            "encoded_bytecode": encoded_bytecode(203),
+            "options": options,
+        });
+        let whole = handle_message(&config, &message.to_string(), |_| {})
+            .decompilation
+            .unwrap();
+
+        let message = serde_json::json!({
+            "id": "b",
+            "encoded_bytecode": encoded_bytecode(203),
+            "key": 203,
+            "options": options,
+            "stream": true,
+        });
+        let mut partials = Vec::new();
+        let response = handle_message(&config, &message.to_string(), |partial| {
+            partials.push(partial)
+        });
+        assert!(response.decompilation.is_none());
+        assert!(partials.iter().all(|partial| partial.id == "b"));
+        assert!(partials.iter().any(|partial| matches!(
+            partial.partial,
+            Partial::Progress {
+                finished: 2,
+                total: 2
+            }
+        )));
+        let output = partials
+            .iter()
+            .filter_map(|partial| match &partial.partial {
+                Partial::Output(output) => Some(output.as_str()),
+                _ => None,
+            })
+            .collect::<String>();
+        assert_eq!(output, whole);
+        assert_eq!(
+            serde_json::to_value(&partials[0]).unwrap(),
+            serde_json::json!({ "id": "b", "progress": { "finished": 1, "total": 2 } })
+        );
+    }
+
+    #[test]
+    fn answers_send_parts_between_functions() {
+        let config = config(&[("AUTH_KEYS", "alice:first")]).unwrap();
+        // options no other test uses, so that nothing is cached yet
+        let message = serde_json::json!({
+            "id": "a",
+            "encoded_bytecode": encoded_bytecode(203),
+            "options": { "indentation_mode": { "spaces": 3 } },
+            "stream": true,
+        });
+        let mut partials = Vec::new();
+        let mut answer = config.answer(&message.to_string(), |partial| partials.push(partial));
+        // the main function is decompiled first, and the other one is still left
+        assert!(answer.step(|partial| partials.push(partial)).is_none());
+        assert_eq!(
+            partials
+                .iter()
+                .map(|partial| &partial.partial)
+                .collect::<Vec<_>>()[..1],
+            [&Partial::Progress {
+                finished: 1,
+                total: 2
+            }]
+        );
+        let mut steps = 1;
+        let response = loop {
+            if let Some(response) = answer.step(|partial| partials.push(partial)) {
+                break response;
+            }
+            steps += 1;
+        };
+        assert_eq!(steps, 2);
+        assert!(response.error.is_none());
+        assert!(partials.iter().any(|partial| matches!(
+            partial.partial,
+            Partial::Progress {
+                finished: 2,
+                total: 2
+            }
+        )));
+    }
+
+    #[test]
+    fn requests_are_authorized_before_decoding() {
+        let config = config(&[("AUTH_KEYS", "alice:first")]).unwrap();
+        let body = encoded_bytecode(203);
+        assert_eq!(
+            config
+                .handle_request(None, None, body.as_bytes())
+                .unwrap_err()
+                .status,
+            401
+        );
+        assert_eq!(
+            config
+                .handle_request(Some("first"), Some("256"), body.as_bytes())
+                .unwrap_err()
+                .code,
+            "invalid_key"
+        );
+        assert!(config
+            .handle_request(Some("first"), Some("203"), body.as_bytes())
+            .is_ok());
+    }
+}
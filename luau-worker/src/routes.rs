@@ -0,0 +1,148 @@
+use crate::protocol::{Answer, Bindings, Config, Error, PartialResponse};
+
+/// The parts of a request that the routes read, which is the worker's `Request` outside of
+/// tests.
+pub trait HttpRequest {
+    fn header(&self, name: &str) -> Option<String>;
+    /// The value of the query parameter `name`.
+    fn query(&self, name: &str) -> Option<String>;
+}
+
+/// `POST /decompile`, see `Config::handle_request`.
+pub fn decompile(
+    bindings: &impl Bindings,
+    request: &impl HttpRequest,
+    body: &[u8],
+) -> Result<String, Error> {
+    let config = Config::from_bindings(bindings)?;
+    config.handle_request(
+        request.header("Authorization").as_deref(),
+        request.query("key").as_deref(),
+        body,
+    )
+}
+
+/// `GET /decompile_ws`, which is answered with a websocket once the request is authorized.
+pub fn open_socket(bindings: &impl Bindings, request: &impl HttpRequest) -> Result<Config, Error> {
+    let config = Config::from_bindings(bindings)?;
+    config.authorize(request.header("Authorization").as_deref())?;
+    Ok(config)
+}
+
+/// Starts answering a message on the websocket, of which only text is understood.
+pub fn answer_message<'a>(
+    config: &'a Config,
+    message: Option<&str>,
+    send: impl FnMut(PartialResponse),
+) -> Answer<'a> {
+    match message {
+        Some(message) => config.answer(message, send),
+        None => config.reject(Error::new(
+            400,
+            "malformed_message",
+            "expected a text message",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use base64::prelude::*;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct MockRequest {
+        headers: HashMap<&'static str, &'static str>,
+        query: HashMap<&'static str, &'static str>,
+    }
+
+    impl HttpRequest for MockRequest {
+        fn header(&self, name: &str) -> Option<String> {
+            self.headers.get(name).map(|value| value.to_string())
+        }
+
+        fn query(&self, name: &str) -> Option<String> {
+            self.query.get(name).map(|value| value.to_string())
+        }
+    }
+
+    fn bindings() -> HashMap<&'static str, &'static str> {
+        HashMap::from([("AUTH_KEYS", "alice:first")])
+    }
+
+    fn licensed(query: &[(&'static str, &'static str)]) -> MockRequest {
+        MockRequest {
+            headers: HashMap::from([("Authorization", "first")]),
+            query: query.iter().copied().collect(),
+        }
+    }
+
+    // a chunk that holds a compile error, which is decompiled to the error itself
+    fn compile_error() -> String {
+        BASE64_STANDARD.encode(b"\0:1: expected expression")
+    }
+
+    #[test]
+    fn requests_are_decompiled_with_the_key_in_the_query() {
+        let body = compile_error();
+        assert_eq!(
+            decompile(&bindings(), &licensed(&[("key", "203")]), body.as_bytes()).unwrap(),
+            ":1: expected expression"
+        );
+        let error =
+            decompile(&bindings(), &licensed(&[("key", "x")]), body.as_bytes()).unwrap_err();
+        assert_eq!((error.status, error.code), (400, "invalid_key"));
+    }
+
+    #[test]
+    fn routes_need_a_license() {
+        let body = compile_error();
+        let unlicensed = MockRequest::default();
+        let error = decompile(&bindings(), &unlicensed, body.as_bytes()).unwrap_err();
+        assert_eq!((error.status, error.code), (401, "unauthorized"));
+        let error = open_socket(&bindings(), &unlicensed).err().unwrap();
+        assert_eq!((error.status, error.code), (401, "unauthorized"));
+
+        let revoked = MockRequest {
+            headers: HashMap::from([("Authorization", "second")]),
+            ..Default::default()
+        };
+        let error = open_socket(&bindings(), &revoked).err().unwrap();
+        assert_eq!((error.status, error.code), (403, "forbidden"));
+        assert!(open_socket(&bindings(), &licensed(&[])).is_ok());
+    }
+
+    #[test]
+    fn routes_fail_without_licenses_to_check() {
+        let error = decompile(&HashMap::new(), &licensed(&[]), b"").unwrap_err();
+        assert_eq!((error.status, error.code), (500, "misconfigured"));
+        let error = open_socket(&HashMap::new(), &licensed(&[])).err().unwrap();
+        assert_eq!((error.status, error.code), (500, "misconfigured"));
+    }
+
+    #[test]
+    fn socket_messages_must_be_text() {
+        let config = open_socket(&bindings(), &licensed(&[])).unwrap();
+        let mut answer = answer_message(&config, None, |_| unreachable!());
+        let response = answer.step(|_| unreachable!()).unwrap();
+        assert!(response.id.is_none());
+        let error = response.error.unwrap();
+        assert_eq!((error.status, error.code), (400, "malformed_message"));
+
+        let message = serde_json::json!({ "id": "a", "encoded_bytecode": compile_error() });
+        let mut answer = answer_message(&config, Some(&message.to_string()), |_| {});
+        let response = loop {
+            if let Some(response) = answer.step(|_| {}) {
+                break response;
+            }
+        };
+        assert_eq!(response.id.as_deref(), Some("a"));
+        assert_eq!(
+            response.decompilation.as_deref(),
+            Some(":1: expected expression")
+        );
+    }
+}
@@ -0,0 +1,10 @@
+fn main() {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    // the checked in header is compared against this one by tests/header.rs
+    cbindgen::generate(&crate_dir)
+        .expect("failed to generate the C header")
+        .write_to_file(format!("{}/medal.h", out_dir));
+}
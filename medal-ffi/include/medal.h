@@ -0,0 +1,50 @@
+#ifndef MEDAL_H
+#define MEDAL_H
+
+/* Generated by cbindgen from medal-ffi/src/lib.rs, don't edit it by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// What `medal_decompile` accepts besides the bytecode. A null pointer means the defaults.
+typedef struct MedalOptions {
+  // The opcode key of Luau bytecode, or 0 to detect it.
+  uint8_t key;
+  // Whether to annotate each function with the bytecode it was lifted from.
+  bool function_headers;
+  // Argument lists and table constructors that would make a line longer than this are
+  // split over multiple lines, 0 never splits them.
+  size_t max_line_width;
+} MedalOptions;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Decompiles `len` bytes of Lua 5.1 or Luau bytecode. Returns 0 and points `out` at the
+// source on success, or returns 1 and points `err` at why it failed. Either string must be
+// released with `medal_free`, and the other pointer is set to null.
+//
+// # Safety
+// `bytecode` must point to `len` readable bytes, `options` must be null or valid, and `out`
+// and `err` must be valid for writes.
+int medal_decompile(const uint8_t *bytecode,
+                    size_t len,
+                    const struct MedalOptions *options,
+                    char **out,
+                    char **err);
+
+// Releases a string returned by `medal_decompile`. Does nothing when `string` is null.
+//
+// # Safety
+// `string` must be null or come from `medal_decompile`, and not have been released before.
+void medal_free(char *string);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* MEDAL_H */
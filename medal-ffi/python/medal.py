@@ -0,0 +1,77 @@
+"""Decompiles Lua 5.1 and Luau bytecode in-process through the C ABI of medal-ffi.
+
+Build the library with `cargo build --release -p medal-ffi` and point `MEDAL_LIBRARY` at
+it, e.g. `target/release/libmedal_ffi.so`, unless it is next to this file.
+"""
+
+import ctypes
+import os
+import sys
+
+__all__ = ["DecompileError", "decompile"]
+
+
+class DecompileError(Exception):
+    pass
+
+
+class _Options(ctypes.Structure):
+    _fields_ = [
+        ("key", ctypes.c_uint8),
+        ("function_headers", ctypes.c_bool),
+        ("max_line_width", ctypes.c_size_t),
+    ]
+
+
+def _load():
+    path = os.environ.get("MEDAL_LIBRARY")
+    if path is None:
+        name = {
+            "win32": "medal_ffi.dll",
+            "darwin": "libmedal_ffi.dylib",
+        }.get(sys.platform, "libmedal_ffi.so")
+        path = os.path.join(os.path.dirname(os.path.abspath(__file__)), name)
+    library = ctypes.CDLL(path)
+    # the strings are returned as void pointers so that ctypes doesn't copy and lose them
+    library.medal_decompile.argtypes = [
+        ctypes.c_char_p,
+        ctypes.c_size_t,
+        ctypes.POINTER(_Options),
+        ctypes.POINTER(ctypes.c_void_p),
+        ctypes.POINTER(ctypes.c_void_p),
+    ]
+    library.medal_decompile.restype = ctypes.c_int
+    library.medal_free.argtypes = [ctypes.c_void_p]
+    library.medal_free.restype = None
+    return library
+
+
+_library = _load()
+
+
+def _take(pointer):
+    try:
+        return ctypes.string_at(pointer).decode("utf-8")
+    finally:
+        _library.medal_free(pointer)
+
+
+def decompile(bytecode, key=None, function_headers=False, max_line_width=None):
+    """Decompiles `bytecode`, detecting the Luau opcode key unless `key` is given.
+
+    Raises `DecompileError` when the bytecode can't be decompiled.
+    """
+    options = _Options(key or 0, function_headers, max_line_width or 0)
+    out = ctypes.c_void_p()
+    err = ctypes.c_void_p()
+    status = _library.medal_decompile(
+        bytecode, len(bytecode), ctypes.byref(options), ctypes.byref(out), ctypes.byref(err)
+    )
+    if status != 0:
+        raise DecompileError(_take(err))
+    return _take(out)
+
+
+if __name__ == "__main__":
+    with open(sys.argv[1], "rb") as file:
+        print(decompile(file.read()))
@@ -0,0 +1,110 @@
+//! A C ABI for embedding the decompiler, declared in `include/medal.h`.
+
+use std::{
+    ffi::{c_char, c_int, CString},
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+};
+
+use medal::{Format, FormatOptions};
+
+/// What `medal_decompile` accepts besides the bytecode. A null pointer means the defaults.
+#[repr(C)]
+#[derive(Debug, Clone, Default)]
+pub struct MedalOptions {
+    /// The opcode key of Luau bytecode, or 0 to detect it.
+    pub key: u8,
+    /// Whether to annotate each function with the bytecode it was lifted from.
+    pub function_headers: bool,
+    /// Argument lists and table constructors that would make a line longer than this are
+    /// split over multiple lines, 0 never splits them.
+    pub max_line_width: usize,
+}
+
+fn decompile(bytecode: &[u8], options: &MedalOptions) -> Result<String, String> {
+    let mut format = Format::detect(bytecode)?;
+    match &mut format {
+        Format::Luau { decoder, .. } if options.key != 0 => *decoder = options.key.into(),
+        _ => {}
+    }
+    let report = format.decompile_report(
+        bytecode,
+        &FormatOptions {
+            function_headers: options.function_headers,
+            max_line_width: Some(options.max_line_width).filter(|&width| width != 0),
+            ..Default::default()
+        },
+    );
+    match report.error {
+        Some(error) => Err(error),
+        None => Ok(report.source),
+    }
+}
+
+// C strings end at the first NUL, so any in the output are escaped like in Lua strings
+fn into_raw(string: String) -> *mut c_char {
+    CString::new(string.replace('\0', "\\0"))
+        .unwrap()
+        .into_raw()
+}
+
+/// Decompiles `len` bytes of Lua 5.1 or Luau bytecode. Returns 0 and points `out` at the
+/// source on success, or returns 1 and points `err` at why it failed. Either string must be
+/// released with `medal_free`, and the other pointer is set to null.
+///
+/// # Safety
+/// `bytecode` must point to `len` readable bytes, `options` must be null or valid, and `out`
+/// and `err` must be valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn medal_decompile(
+    bytecode: *const u8,
+    len: usize,
+    options: *const MedalOptions,
+    out: *mut *mut c_char,
+    err: *mut *mut c_char,
+) -> c_int {
+    // a null pointer can't be turned into a slice, even an empty one
+    let bytecode = if len == 0 {
+        &[]
+    } else {
+        unsafe { slice::from_raw_parts(bytecode, len) }
+    };
+    let options = unsafe { options.as_ref() }.cloned().unwrap_or_default();
+    // unwinding into C is undefined behaviour
+    let result = panic::catch_unwind(AssertUnwindSafe(|| decompile(bytecode, &options)))
+        .unwrap_or_else(|panic| {
+            let message = panic
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown cause");
+            Err(format!("the decompiler panicked: {}", message))
+        });
+    match result {
+        Ok(source) => {
+            unsafe {
+                *out = into_raw(source);
+                *err = ptr::null_mut();
+            }
+            0
+        }
+        Err(error) => {
+            unsafe {
+                *out = ptr::null_mut();
+                *err = into_raw(error);
+            }
+            1
+        }
+    }
+}
+
+/// Releases a string returned by `medal_decompile`. Does nothing when `string` is null.
+///
+/// # Safety
+/// `string` must be null or come from `medal_decompile`, and not have been released before.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn medal_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(unsafe { CString::from_raw(string) });
+    }
+}
@@ -0,0 +1,80 @@
+use std::{
+    ffi::{c_char, CStr},
+    ptr,
+};
+
+use luau_lifter::{assembler::assemble_bytecode, op_code_decoder::OpcodeDecoder};
+use medal::FormatOptions;
+use medal_ffi::{medal_decompile, medal_free, MedalOptions};
+
+const LOOPS: &str = include_str!("../../luau-lifter/tests/loops.luauasm");
+
+/// Calls `medal_decompile` and takes ownership of whichever string it returned.
+fn decompile(bytecode: &[u8], options: Option<&MedalOptions>) -> Result<String, String> {
+    let mut out: *mut c_char = ptr::null_mut();
+    let mut err: *mut c_char = ptr::null_mut();
+    let status = unsafe {
+        medal_decompile(
+            bytecode.as_ptr(),
+            bytecode.len(),
+            options.map_or(ptr::null(), |options| options as *const _),
+            &mut out,
+            &mut err,
+        )
+    };
+    let take = |string: *mut c_char| {
+        let owned = unsafe { CStr::from_ptr(string) }
+            .to_str()
+            .unwrap()
+            .to_string();
+        unsafe { medal_free(string) };
+        owned
+    };
+    if status == 0 {
+        assert!(err.is_null());
+        Ok(take(out))
+    } else {
+        assert!(out.is_null());
+        Err(take(err))
+    }
+}
+
+#[test]
+fn decompiles_like_the_library() {
+    let bytecode = assemble_bytecode(LOOPS, &OpcodeDecoder::Multiplicative(203)).unwrap();
+    assert_eq!(
+        decompile(&bytecode, None).unwrap(),
+        medal::decompile(&bytecode, &FormatOptions::default())
+            .unwrap()
+            .source
+    );
+}
+
+#[test]
+fn options_are_applied() {
+    let bytecode = assemble_bytecode(LOOPS, &OpcodeDecoder::Multiplicative(1)).unwrap();
+    let options = MedalOptions {
+        key: 1,
+        function_headers: true,
+        max_line_width: 0,
+    };
+    assert_eq!(
+        decompile(&bytecode, Some(&options)).unwrap(),
+        medal::decompile(
+            &bytecode,
+            &FormatOptions {
+                function_headers: true,
+                ..Default::default()
+            }
+        )
+        .unwrap()
+        .source
+    );
+}
+
+#[test]
+fn failures_are_returned_as_errors() {
+    assert_eq!(decompile(&[], None).unwrap_err(), "empty input");
+    assert!(decompile(b"\x06garbage", None).is_err());
+    unsafe { medal_free(ptr::null_mut()) };
+}
@@ -0,0 +1,11 @@
+/// The header is checked in so that it can be used without building first.
+#[test]
+fn checked_in_header_is_current() {
+    let generated = include_str!(concat!(env!("OUT_DIR"), "/medal.h"));
+    let checked_in = include_str!("../include/medal.h");
+    assert!(
+        generated == checked_in,
+        "include/medal.h is out of date, replace it with {}/medal.h",
+        env!("OUT_DIR")
+    );
+}
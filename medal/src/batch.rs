@@ -0,0 +1,385 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom},
+    path::{Component, Path, PathBuf},
+    time::Instant,
+};
+
+use ast::report::{Counts, FunctionStatus};
+use base64::prelude::*;
+use rayon::prelude::*;
+use serde::Serialize;
+use walkdir::WalkDir;
+
+use crate::{Cache, FormatOptions};
+
+/// Bytecode found in the inputs. It's only read when it's decompiled, so that a large batch
+/// doesn't have to fit in memory.
+pub struct Input {
+    /// Where the output goes, relative to the output directory and without an extension.
+    pub path: PathBuf,
+    /// Where the bytecode was found, like `dump.zip/a.luac`.
+    pub source: PathBuf,
+    contents: Contents,
+}
+
+enum Contents {
+    File(PathBuf),
+    /// Stored as is in a file, like the entries of a tar archive.
+    Range {
+        path: PathBuf,
+        start: u64,
+        length: u64,
+    },
+    ZipEntry {
+        archive: PathBuf,
+        index: usize,
+    },
+    /// A line of base64 in a file.
+    Base64 {
+        path: PathBuf,
+        start: u64,
+        length: u64,
+    },
+    Unreadable(String),
+}
+
+impl Input {
+    /// Reads the bytecode, or why it couldn't be read.
+    pub fn read(&self) -> Result<Vec<u8>, String> {
+        match &self.contents {
+            Contents::File(path) => fs::read(path).map_err(|err| err.to_string()),
+            Contents::Range {
+                path,
+                start,
+                length,
+            } => read_range(path, *start, *length).map_err(|err| err.to_string()),
+            Contents::ZipEntry { archive, index } => {
+                let mut archive =
+                    zip::ZipArchive::new(File::open(archive).map_err(|err| err.to_string())?)
+                        .map_err(|err| err.to_string())?;
+                let mut file = archive.by_index(*index).map_err(|err| err.to_string())?;
+                let mut bytecode = Vec::new();
+                file.read_to_end(&mut bytecode)
+                    .map_err(|err| err.to_string())?;
+                Ok(bytecode)
+            }
+            Contents::Base64 {
+                path,
+                start,
+                length,
+            } => {
+                let line = read_range(path, *start, *length).map_err(|err| err.to_string())?;
+                BASE64_STANDARD
+                    .decode(line.trim_ascii())
+                    .map_err(|err| format!("invalid base64: {}", err))
+            }
+            Contents::Unreadable(err) => Err(err.clone()),
+        }
+    }
+}
+
+fn read_range(path: &Path, start: u64, length: u64) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(start))?;
+    let mut contents = Vec::new();
+    file.take(length).read_to_end(&mut contents)?;
+    if contents.len() as u64 != length {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(contents)
+}
+
+/// Finds the bytecode in `paths`: every file below a directory, every file in a `.zip` or
+/// `.tar` archive, every line of a `.b64` file of newline-delimited base64, or else the file
+/// itself. The paths mirror the structure of the input, with archives and `.b64` files
+/// becoming directories. Inputs that would end up at the same path keep their extensions
+/// instead, and any that still collide fail.
+pub fn collect(paths: &[PathBuf]) -> Result<Vec<Input>, String> {
+    let mut inputs = Vec::new();
+    for path in paths {
+        collect_path(path, &mut inputs)?;
+    }
+    let mut collisions = BTreeMap::<_, usize>::new();
+    for input in &inputs {
+        *collisions.entry(input.path.clone()).or_default() += 1;
+    }
+    let mut taken = BTreeSet::new();
+    for input in &mut inputs {
+        // like `a.luac` next to `a.luau`, or `dump.zip` next to `dump/`
+        if collisions[&input.path] > 1 {
+            input.path.clone_from(&input.source);
+        }
+        if !taken.insert(input.path.clone()) {
+            input.contents =
+                Contents::Unreadable("another input has the same output path".to_string());
+        }
+    }
+    Ok(inputs)
+}
+
+fn collect_path(path: &Path, inputs: &mut Vec<Input>) -> Result<(), String> {
+    let name = PathBuf::from(
+        path.file_name()
+            .ok_or_else(|| format!("{} has no file name", path.display()))?,
+    );
+    if path.is_dir() {
+        for entry in WalkDir::new(path).sort_by_file_name() {
+            let entry = entry.map_err(|err| err.to_string())?;
+            if entry.file_type().is_file() {
+                let relative = entry.path().strip_prefix(path).unwrap();
+                collect_file(entry.path(), name.join(relative), inputs)?;
+            }
+        }
+        Ok(())
+    } else {
+        collect_file(path, name, inputs)
+    }
+}
+
+fn collect_file(path: &Path, relative: PathBuf, inputs: &mut Vec<Input>) -> Result<(), String> {
+    let error = |err: &dyn fmt::Display| format!("{}: {}", path.display(), err);
+    let directory = relative.with_extension("");
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("zip") => {
+            let mut archive = zip::ZipArchive::new(File::open(path).map_err(|err| error(&err))?)
+                .map_err(|err| error(&err))?;
+            for index in 0..archive.len() {
+                let file = archive.by_index_raw(index).map_err(|err| error(&err))?;
+                // entries that would escape the output directory are skipped
+                let Some(name) = file.enclosed_name().filter(|_| file.is_file()) else {
+                    continue;
+                };
+                inputs.push(Input {
+                    path: directory.join(&name).with_extension(""),
+                    source: relative.join(name),
+                    contents: Contents::ZipEntry {
+                        archive: path.to_path_buf(),
+                        index,
+                    },
+                });
+            }
+        }
+        Some("tar") => {
+            let mut archive = tar::Archive::new(File::open(path).map_err(|err| error(&err))?);
+            for entry in archive.entries().map_err(|err| error(&err))? {
+                let entry = entry.map_err(|err| error(&err))?;
+                if !entry.header().entry_type().is_file() {
+                    continue;
+                }
+                // entries that would escape the output directory are skipped, like in zips
+                let mut name = PathBuf::new();
+                for component in entry.path().map_err(|err| error(&err))?.components() {
+                    match component {
+                        Component::Normal(component) => name.push(component),
+                        Component::CurDir => {}
+                        _ => {
+                            name.clear();
+                            break;
+                        }
+                    }
+                }
+                if name.as_os_str().is_empty() {
+                    continue;
+                }
+                inputs.push(Input {
+                    path: directory.join(&name).with_extension(""),
+                    source: relative.join(name),
+                    contents: Contents::Range {
+                        path: path.to_path_buf(),
+                        start: entry.raw_file_position(),
+                        length: entry.size(),
+                    },
+                });
+            }
+        }
+        Some("b64") => {
+            let mut lines = BufReader::new(File::open(path).map_err(|err| error(&err))?);
+            let mut line = Vec::new();
+            let mut start = 0;
+            // named after their line so that the outputs can be matched up with the input
+            for number in 1.. {
+                line.clear();
+                let length = lines
+                    .read_until(b'\n', &mut line)
+                    .map_err(|err| error(&err))?;
+                if length == 0 {
+                    break;
+                }
+                if !line.trim_ascii().is_empty() {
+                    inputs.push(Input {
+                        path: directory.join(number.to_string()),
+                        source: relative.join(number.to_string()),
+                        contents: Contents::Base64 {
+                            path: path.to_path_buf(),
+                            start,
+                            length: length as u64,
+                        },
+                    });
+                }
+                start += length as u64;
+            }
+        }
+        _ => inputs.push(Input {
+            path: relative.with_extension(""),
+            contents: Contents::File(path.to_path_buf()),
+            source: relative,
+        }),
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileSummary {
+    pub path: PathBuf,
+    pub format: Option<String>,
+    /// Why nothing was decompiled.
+    pub error: Option<String>,
+    pub counts: Counts,
+    pub milliseconds: f64,
+}
+
+/// How many functions panicked at the same place or exceeded their budget the same way.
+#[derive(Debug, Clone, Serialize)]
+pub struct Failures {
+    pub site: String,
+    pub functions: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    pub files: Vec<FileSummary>,
+    pub failed_files: usize,
+    pub counts: Counts,
+    /// The most common first.
+    pub failures: Vec<Failures>,
+    pub milliseconds: f64,
+}
+
+/// Decompiles every input on the current rayon pool and writes the output to a `.lua` file
+/// at its path below `output`.
+pub fn decompile(
+    inputs: Vec<Input>,
+    output: &Path,
+    options: &FormatOptions,
+    cache: Option<&Cache>,
+) -> Summary {
+    let start = Instant::now();
+    let mut failures = BTreeMap::new();
+    let files = inputs
+        .into_par_iter()
+        .map(|input| {
+            let start = Instant::now();
+            let result = input.read().and_then(|bytecode| match cache {
+                Some(cache) => crate::decompile_cached(&bytecode, options, cache),
+                None => crate::decompile_report(&bytecode, options),
+            });
+            let mut summary = FileSummary {
+                path: input.path,
+                format: None,
+                error: None,
+                counts: Counts::default(),
+                milliseconds: 0.0,
+            };
+            let mut sites = Vec::new();
+            match result {
+                Ok((format, report)) => {
+                    // appended so that the rest of a name like `a.b` is kept
+                    let mut path = output.join(&summary.path).into_os_string();
+                    path.push(".lua");
+                    let path = PathBuf::from(path);
+                    if let Err(err) = fs::create_dir_all(path.parent().unwrap())
+                        .and_then(|()| fs::write(&path, &report.source))
+                    {
+                        summary.error =
+                            Some(format!("failed to write {}: {}", path.display(), err));
+                    }
+                    summary.format = Some(format.to_string());
+                    summary.error = summary.error.take().or(report.error);
+                    summary.counts = report.counts;
+                    sites.extend(report.functions.into_iter().filter_map(|function| {
+                        match function.status {
+                            FunctionStatus::Ok => None,
+                            FunctionStatus::Degraded { reason, site, .. }
+                            | FunctionStatus::Failed { reason, site } => {
+                                Some(site.unwrap_or(reason))
+                            }
+                        }
+                    }));
+                }
+                Err(err) => summary.error = Some(err),
+            }
+            summary.milliseconds = start.elapsed().as_secs_f64() * 1000.0;
+            (summary, sites)
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .map(|(summary, sites)| {
+            for site in sites {
+                *failures.entry(site).or_insert(0) += 1;
+            }
+            summary
+        })
+        .collect::<Vec<_>>();
+
+    let mut counts = Counts::default();
+    for file in &files {
+        counts.add(&file.counts);
+    }
+    let mut failures = failures
+        .into_iter()
+        .map(|(site, functions)| Failures { site, functions })
+        .collect::<Vec<_>>();
+    failures.sort_by(|a, b| {
+        b.functions
+            .cmp(&a.functions)
+            .then_with(|| a.site.cmp(&b.site))
+    });
+    Summary {
+        failed_files: files.iter().filter(|file| file.error.is_some()).count(),
+        files,
+        counts,
+        failures,
+        milliseconds: start.elapsed().as_secs_f64() * 1000.0,
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let counts = &self.counts;
+        writeln!(
+            f,
+            "files      {} ({} failed)",
+            self.files.len(),
+            self.failed_files
+        )?;
+        writeln!(
+            f,
+            "functions  {} ({} ok, {} degraded, {} failed)",
+            counts.functions, counts.ok, counts.degraded, counts.failed
+        )?;
+        writeln!(
+            f,
+            "gotos      {} (to {} labels)",
+            counts.gotos, counts.labels
+        )?;
+        write!(f, "time       {:.2}s", self.milliseconds / 1000.0)?;
+        if !self.failures.is_empty() {
+            write!(f, "\n\nfailures by site")?;
+            for failures in &self.failures {
+                write!(f, "\n{:>8}  {}", failures.functions, failures.site)?;
+            }
+        }
+        if self.failed_files != 0 {
+            write!(f, "\n\nfailed files")?;
+            for file in &self.files {
+                if let Some(error) = &file.error {
+                    write!(f, "\n  {}: {}", file.path.display(), error)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
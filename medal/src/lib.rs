@@ -0,0 +1,129 @@
+use std::fmt;
+
+pub mod batch;
+
+use cache::Key;
+use luau_lifter::{op_code_decoder::OpcodeDecoder, Budget};
+
+pub use ast::{
+    formatter::{
+        Dialect, FormatOptions, IndentationMode, QuoteStyle, SemicolonPolicy, TableSeparator,
+    },
+    report::DecompileReport,
+};
+pub use cache::Cache;
+
+const LUA_SIGNATURE: &[u8] = b"\x1BLua";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Format {
+    Lua51,
+    /// `version` 0 means the chunk holds a compile error instead of bytecode.
+    Luau {
+        version: u8,
+        decoder: OpcodeDecoder,
+    },
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Lua51 => write!(f, "Lua 5.1"),
+            Self::Luau { version: 0, .. } => write!(f, "Luau compile error"),
+            Self::Luau { version, decoder } => write!(f, "Luau version {} ({})", version, decoder),
+        }
+    }
+}
+
+impl Format {
+    /// Sniffs the header and, for Luau, guesses the opcode encoding.
+    pub fn detect(bytecode: &[u8]) -> Result<Self, String> {
+        if let Some(rest) = bytecode.strip_prefix(LUA_SIGNATURE) {
+            return match rest.first() {
+                Some(0x51) => Ok(Self::Lua51),
+                Some(version) => Err(format!(
+                    "unsupported Lua version {}.{}",
+                    version >> 4,
+                    version & 0xF
+                )),
+                None => Err("truncated Lua header".to_string()),
+            };
+        }
+        match bytecode.first() {
+            Some(0) => Ok(Self::Luau {
+                version: 0,
+                decoder: OpcodeDecoder::default(),
+            }),
+            Some(&version @ 4..=6) => Ok(Self::Luau {
+                version,
+                decoder: OpcodeDecoder::detect(bytecode).unwrap_or_default(),
+            }),
+            Some(version) => Err(format!("unrecognized bytecode version {}", version)),
+            None => Err("empty input".to_string()),
+        }
+    }
+
+    pub fn dialect(&self) -> Dialect {
+        match self {
+            Self::Lua51 => Dialect::Lua51,
+            Self::Luau { .. } => Dialect::Luau,
+        }
+    }
+
+    /// `options.dialect` is ignored, the output targets the dialect of the bytecode.
+    pub fn decompile(&self, bytecode: &[u8], options: &FormatOptions) -> String {
+        self.decompile_report(bytecode, options).source
+    }
+
+    /// Like `decompile`, but also describes how each function was decompiled.
+    pub fn decompile_report(&self, bytecode: &[u8], options: &FormatOptions) -> DecompileReport {
+        let options = FormatOptions {
+            dialect: self.dialect(),
+            ..options.clone()
+        };
+        match self {
+            Self::Lua51 => lua51_lifter::decompile_report(bytecode, &options),
+            Self::Luau { decoder, .. } => {
+                luau_lifter::decompile_report(bytecode, decoder, &options, &Budget::default())
+            }
+        }
+    }
+}
+
+pub struct Decompiled {
+    pub format: Format,
+    pub source: String,
+}
+
+/// Decompiles Lua 5.1 or Luau bytecode without the caller having to know which one it is.
+pub fn decompile(bytecode: &[u8], options: &FormatOptions) -> Result<Decompiled, String> {
+    let format = Format::detect(bytecode)?;
+    let source = format.decompile(bytecode, options);
+    Ok(Decompiled { format, source })
+}
+
+/// Like `decompile`, but returns the full report, which serializes to JSON.
+pub fn decompile_report(
+    bytecode: &[u8],
+    options: &FormatOptions,
+) -> Result<(Format, DecompileReport), String> {
+    let format = Format::detect(bytecode)?;
+    let report = format.decompile_report(bytecode, options);
+    Ok((format, report))
+}
+
+/// Like `decompile_report`, but reuses the report in `cache` if the same bytecode was
+/// decompiled with the same options before.
+pub fn decompile_cached(
+    bytecode: &[u8],
+    options: &FormatOptions,
+    cache: &Cache,
+) -> Result<(Format, DecompileReport), String> {
+    let format = Format::detect(bytecode)?;
+    // the decoder is left out because it was detected from the bytecode
+    let key = Key::new(bytecode, None, options, &Budget::default());
+    let report = cache
+        .get_or_insert_with(key, || format.decompile_report(bytecode, options))
+        .map_err(|err| format!("failed to cache the decompilation: {}", err))?;
+    Ok((format, report))
+}
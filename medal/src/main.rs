@@ -0,0 +1,89 @@
+use std::{num::NonZeroUsize, path::PathBuf};
+
+use medal::{Cache, FormatOptions};
+use rayon::ThreadPoolBuilder;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut paths = Vec::new();
+    let mut output = None;
+    let mut summary_json = false;
+    let mut options = FormatOptions::default();
+    let mut json = false;
+    let mut cache = None;
+    let mut threads = 0;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            // annotate each function with the bytecode it was lifted from
+            "--function-headers" => options.function_headers = true,
+            // print the source along with per-function status and pass timings
+            "--json" => json = true,
+            // reuse decompilations of unchanged bytecode from earlier runs
+            "--cache-dir" => {
+                let directory = args.next().expect("expected a cache directory");
+                cache = Some(Cache::new(NonZeroUsize::MIN).with_directory(directory));
+            }
+            // how many functions are decompiled at once, 0 for one per CPU
+            "--threads" => {
+                threads = args
+                    .next()
+                    .and_then(|threads| threads.parse().ok())
+                    .expect("expected a number of threads")
+            }
+            // decompile every file in the inputs to this directory
+            "--output" => output = Some(PathBuf::from(args.next().expect("expected a directory"))),
+            // how to print the summary of a batch
+            "--summary" => {
+                summary_json = match args.next().as_deref() {
+                    Some("text") => false,
+                    Some("json") => true,
+                    _ => panic!("expected text or json"),
+                }
+            }
+            _ if arg.starts_with("--") => panic!("unknown option {}", arg),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .expect("failed to start the threads");
+    if let Some(output) = output {
+        let inputs = match medal::batch::collect(&paths) {
+            Ok(inputs) => inputs,
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        };
+        let summary =
+            pool.install(|| medal::batch::decompile(inputs, &output, &options, cache.as_ref()));
+        if summary_json {
+            println!("{}", serde_json::to_string_pretty(&summary).unwrap());
+        } else {
+            println!("{}", summary);
+        }
+        return;
+    }
+    let [file_name] = paths.as_slice() else {
+        panic!("expected a file, or --output to decompile several");
+    };
+    let bytecode = std::fs::read(file_name).expect("failed to read file");
+    let result = pool.install(|| match &cache {
+        Some(cache) => medal::decompile_cached(&bytecode, &options, cache),
+        None => medal::decompile_report(&bytecode, &options),
+    });
+    match result {
+        Ok((_, report)) if json => {
+            println!("{}", serde_json::to_string_pretty(&report).unwrap())
+        }
+        Ok((format, report)) => {
+            println!("-- {}", format);
+            println!("{}", report.source);
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
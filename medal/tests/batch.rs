@@ -0,0 +1,221 @@
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use luau_lifter::{assembler::assemble_bytecode, op_code_decoder::OpcodeDecoder};
+use medal::{batch, FormatOptions};
+
+/// A chunk that holds a compile error instead of bytecode.
+const COMPILE_ERROR: &[u8] = b"\0:1: expected expression";
+
+/// `return`
+const RETURN: &str = "version 6
+types_version 3
+
+function 0
+max_stack_size 1
+num_parameters 0
+num_upvalues 0
+is_vararg true
+flags 0
+type_info
+line_defined 0
+function_name 0
+functions
+code
+PREPVARARGS A=0 B=0 C=0
+RETURN A=0 B=1 C=0
+end
+
+main 0
+";
+
+struct Directory(PathBuf);
+
+impl Directory {
+    fn new(name: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("medal-batch-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    fn write(&self, path: &str, contents: &[u8]) {
+        let path = self.0.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+}
+
+impl Drop for Directory {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut archive = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for (name, contents) in files {
+        archive
+            .start_file(*name, zip::write::SimpleFileOptions::default())
+            .unwrap();
+        archive.write_all(contents).unwrap();
+    }
+    archive.finish().unwrap().into_inner()
+}
+
+fn tar(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut archive = tar::Builder::new(Vec::new());
+    for (name, contents) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        archive.append_data(&mut header, name, *contents).unwrap();
+    }
+    archive.into_inner().unwrap()
+}
+
+fn paths(inputs: &[batch::Input]) -> Vec<(String, String)> {
+    inputs
+        .iter()
+        .map(|input| {
+            (
+                input.path.display().to_string(),
+                input.source.display().to_string(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn inputs_mirror_the_structure_of_the_input() {
+    let directory = Directory::new("collect");
+    let input = directory.0.join("input");
+    fs::create_dir(&input).unwrap();
+    directory.write("input/a.luac", COMPILE_ERROR);
+    directory.write("input/nested/b.c.luac", COMPILE_ERROR);
+    directory.write("input/scripts.zip", &zip(&[("d.luac", COMPILE_ERROR)]));
+    directory.write(
+        "input/dump.tar",
+        &tar(&[("e.luac", COMPILE_ERROR), ("nested/f.luac", b"\x06")]),
+    );
+    directory.write("input/lines.b64", b"AA==\n\nAA==\n");
+
+    let inputs = batch::collect(&[input]).unwrap();
+    assert_eq!(
+        paths(&inputs),
+        [
+            ("input/a", "input/a.luac"),
+            ("input/dump/e", "input/dump.tar/e.luac"),
+            ("input/dump/nested/f", "input/dump.tar/nested/f.luac"),
+            ("input/lines/1", "input/lines.b64/1"),
+            ("input/lines/3", "input/lines.b64/3"),
+            ("input/nested/b.c", "input/nested/b.c.luac"),
+            ("input/scripts/d", "input/scripts.zip/d.luac"),
+        ]
+        .map(|(path, source)| (path.to_string(), source.to_string()))
+    );
+    let contents = inputs
+        .iter()
+        .map(|input| input.read().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        contents,
+        [
+            COMPILE_ERROR,
+            COMPILE_ERROR,
+            b"\x06",
+            &[0],
+            &[0],
+            COMPILE_ERROR,
+            COMPILE_ERROR,
+        ]
+    );
+}
+
+#[test]
+fn colliding_inputs_keep_their_extensions() {
+    let directory = Directory::new("collisions");
+    let input = directory.0.join("input");
+    fs::create_dir(&input).unwrap();
+    directory.write("input/a.luac", COMPILE_ERROR);
+    directory.write("input/a.luau", COMPILE_ERROR);
+    directory.write("input/dump.zip", &zip(&[("b.luac", COMPILE_ERROR)]));
+    directory.write("input/dump/b.luac", COMPILE_ERROR);
+
+    let inputs = batch::collect(std::slice::from_ref(&input)).unwrap();
+    assert_eq!(
+        paths(&inputs),
+        [
+            ("input/a.luac", "input/a.luac"),
+            ("input/a.luau", "input/a.luau"),
+            ("input/dump/b.luac", "input/dump/b.luac"),
+            ("input/dump.zip/b.luac", "input/dump.zip/b.luac"),
+        ]
+        .map(|(path, source)| (path.to_string(), source.to_string()))
+    );
+    assert!(inputs.iter().all(|input| input.read().is_ok()));
+
+    // the same input twice can't be told apart
+    let inputs = batch::collect(&[input.join("a.luac"), input.join("a.luac")]).unwrap();
+    assert!(inputs[0].read().is_ok());
+    assert_eq!(
+        inputs[1].read().unwrap_err(),
+        "another input has the same output path"
+    );
+}
+
+#[test]
+fn summaries_count_the_decompiled_files() {
+    let directory = Directory::new("summary");
+    let input = directory.0.join("input");
+    fs::create_dir(&input).unwrap();
+    let bytecode = assemble_bytecode(RETURN, &OpcodeDecoder::default()).unwrap();
+    directory.write("input/a.luac", &bytecode);
+    directory.write("input/b.c.luac", &bytecode);
+    directory.write("input/unknown.luac", b"\x07");
+    let output = directory.0.join("output");
+
+    let inputs = batch::collect(&[input]).unwrap();
+    let summary = batch::decompile(inputs, &output, &FormatOptions::default(), None);
+    let written = |path: &str| Path::new(&output).join(path).is_file();
+    assert!(written("input/a.lua"));
+    assert!(written("input/b.c.lua"));
+    assert!(!written("input/unknown.lua"));
+
+    assert_eq!(summary.files.len(), 3);
+    assert_eq!(summary.failed_files, 1);
+    assert_eq!(summary.counts.functions, 2);
+    assert_eq!(summary.counts.ok, 2);
+    let failed = summary
+        .files
+        .iter()
+        .find(|file| file.error.is_some())
+        .unwrap();
+    assert_eq!(failed.path, Path::new("input/unknown"));
+    assert_eq!(
+        failed.error.as_deref(),
+        Some("unrecognized bytecode version 7")
+    );
+    let text = summary.to_string();
+    assert!(text.starts_with("files      3 (1 failed)\n"));
+    assert!(text.ends_with("\n\nfailed files\n  input/unknown: unrecognized bytecode version 7"));
+}
+
+#[test]
+fn inputs_are_read_when_they_are_decompiled() {
+    let directory = Directory::new("lazy");
+    directory.write("a.luac", COMPILE_ERROR);
+    let inputs = batch::collect(&[directory.0.join("a.luac")]).unwrap();
+
+    // the file changes after it was collected
+    let bytecode = assemble_bytecode(RETURN, &OpcodeDecoder::default()).unwrap();
+    directory.write("a.luac", &bytecode);
+    let output = directory.0.join("output");
+    let summary = batch::decompile(inputs, &output, &FormatOptions::default(), None);
+    assert_eq!(summary.failed_files, 0);
+    assert!(output.join("a.lua").is_file());
+}
@@ -1,8 +1,10 @@
 #![feature(let_chains)]
 
+use ast::{LocalRw, Traverse};
 use cfg::{block::BranchType, function::Function};
 use itertools::Itertools;
 use rustc_hash::{FxHashMap, FxHashSet};
+use std::ops::Range;
 
 use petgraph::{
     algo::dominators::{simple_fast, Dominators},
@@ -296,6 +298,9 @@ impl GraphStructurer {
                             ast::Statement::Repeat(repeat) => {
                                 collect_gotos(&repeat.block.lock(), gotos);
                             }
+                            ast::Statement::Do(r#do) => {
+                                collect_gotos(&r#do.block.lock(), gotos);
+                            }
                             ast::Statement::NumericFor(numeric_for) => {
                                 collect_gotos(&numeric_for.block.lock(), gotos);
                             }
@@ -358,3 +363,347 @@ impl GraphStructurer {
 pub fn lift(function: cfg::function::Function) -> ast::Block {
     GraphStructurer::new(function).structure()
 }
+
+// the loop statements are only understood by the structurer, so they are written out as the
+// assignments the VM does for them
+fn lower_for_init(statement: ast::Statement) -> Vec<ast::Statement> {
+    // the registers are usually assigned to themselves
+    let changed = |assignments: Vec<(ast::LValue, ast::RValue)>| {
+        let (left, right): (Vec<_>, Vec<_>) = assignments
+            .into_iter()
+            .filter(|(lvalue, rvalue)| {
+                !matches!((lvalue, rvalue), (ast::LValue::Local(l), ast::RValue::Local(r)) if l == r)
+            })
+            .unzip();
+        (!left.is_empty()).then(|| ast::Assign::new(left, right).into())
+    };
+    match statement {
+        // like Lua 5.1's FORPREP, which subtracts the step once so that the first FORLOOP can
+        // add it back
+        ast::Statement::NumForInit(init) => {
+            let counter = init.counter.0.as_local().unwrap().clone();
+            let step = init.step.0.as_local().unwrap().clone();
+            changed(vec![init.counter, init.limit, init.step])
+                .into_iter()
+                .chain(std::iter::once(
+                    ast::Assign::new(
+                        vec![counter.clone().into()],
+                        vec![ast::Binary::new(
+                            counter.into(),
+                            step.into(),
+                            ast::BinaryOperation::Sub,
+                        )
+                        .into()],
+                    )
+                    .into(),
+                ))
+                .collect()
+        }
+        ast::Statement::GenericForInit(init) => {
+            changed(init.0.left.into_iter().zip(init.0.right).collect())
+                .into_iter()
+                .collect()
+        }
+        statement => vec![statement],
+    }
+}
+
+// returns the condition to continue the loop and what to do before continuing
+fn lower_for_next(
+    block: &mut ast::Block,
+    generic_for_control: Option<&ast::RcLocal>,
+) -> (ast::RValue, ast::Block) {
+    match block.pop().unwrap() {
+        // counter = counter + step
+        // if step > 0 and counter <= limit or step <= 0 and counter >= limit then
+        ast::Statement::NumForNext(next) => {
+            let (counter, value) = next.counter;
+            let counter = counter.into_local().unwrap();
+            block.push(
+                ast::Assign::new(
+                    vec![counter.clone().into()],
+                    vec![
+                        ast::Binary::new(value, next.step.clone(), ast::BinaryOperation::Add)
+                            .into(),
+                    ],
+                )
+                .into(),
+            );
+            let zero = || ast::RValue::from(ast::Literal::Number(0.0));
+            let compare = |left: ast::RValue, right: ast::RValue, operation| {
+                ast::RValue::from(ast::Binary::new(left, right, operation))
+            };
+            let condition = compare(
+                compare(
+                    compare(next.step.clone(), zero(), ast::BinaryOperation::GreaterThan),
+                    compare(
+                        counter.clone().into(),
+                        next.limit.clone(),
+                        ast::BinaryOperation::LessThanOrEqual,
+                    ),
+                    ast::BinaryOperation::And,
+                ),
+                compare(
+                    compare(next.step, zero(), ast::BinaryOperation::LessThanOrEqual),
+                    compare(
+                        counter.into(),
+                        next.limit,
+                        ast::BinaryOperation::GreaterThanOrEqual,
+                    ),
+                    ast::BinaryOperation::And,
+                ),
+                ast::BinaryOperation::Or,
+            );
+            (condition, ast::Block::default())
+        }
+        // a, b = generator(state, control)
+        // if a ~= nil then control = a
+        ast::Statement::GenericForNext(next) => {
+            let control = generic_for_control.unwrap().clone();
+            let first = next.res_locals[0].as_local().unwrap().clone();
+            block.push(
+                ast::Assign::new(
+                    next.res_locals,
+                    vec![ast::RValue::Select(
+                        ast::Call::new(next.generator, vec![next.state, control.clone().into()])
+                            .into(),
+                    )],
+                )
+                .into(),
+            );
+            let condition = ast::Binary::new(
+                first.clone().into(),
+                ast::Literal::Nil.into(),
+                ast::BinaryOperation::NotEqual,
+            )
+            .into();
+            (
+                condition,
+                ast::Block(vec![ast::Assign::new(
+                    vec![control.into()],
+                    vec![first.into()],
+                )
+                .into()]),
+            )
+        }
+        statement => unreachable!("{:?} has conditional edges", statement),
+    }
+}
+
+// the locals that closures created by `statement` capture by reference
+fn captured_by_reference(statement: &mut ast::Statement) -> Vec<ast::RcLocal> {
+    let mut captured = Vec::new();
+    statement.traverse_rvalues(&mut |rvalue| {
+        if let ast::RValue::Closure(closure) = rvalue {
+            captured.extend(closure.upvalues.iter().filter_map(|upvalue| match upvalue {
+                ast::Upvalue::Ref(local) => Some(local.clone()),
+                ast::Upvalue::Copy(_) => None,
+            }));
+        }
+    });
+    captured
+}
+
+// the statements of `block` from where a local captured by reference is written to where its
+// upvalue is closed, e.g. the rest of a loop iteration, along with the locals captured in them
+fn capture_scopes(block: &mut ast::Block) -> Vec<(Range<usize>, Vec<ast::RcLocal>)> {
+    let mut scopes = Vec::new();
+    let mut scope_end = 0;
+    for close_index in 0..block.len() {
+        let ast::Statement::Close(close) = &block[close_index] else {
+            continue;
+        };
+        let closed = close.locals.clone();
+        let captured = block[scope_end..close_index]
+            .iter_mut()
+            .flat_map(captured_by_reference)
+            .filter(|local| closed.contains(local))
+            .unique()
+            .collect_vec();
+        // the first use of a local in its scope has to overwrite it
+        let starts = captured
+            .into_iter()
+            .filter_map(|local| {
+                let start = (scope_end..close_index)
+                    .find(|&index| block[index].values().contains(&&local))?;
+                (!block[start].values_read().contains(&&local)).then_some((start, local))
+            })
+            .collect_vec();
+        if let Some(start) = starts.iter().map(|&(start, _)| start).min() {
+            scopes.push((
+                start..close_index,
+                starts.into_iter().map(|(_, local)| local).collect(),
+            ));
+        }
+        scope_end = close_index + 1;
+    }
+    scopes
+}
+
+/// Emits every block behind a label and every edge as a goto without structuring anything,
+/// for functions that can't be structured. All locals are declared up front so that no goto
+/// jumps into the scope of a local. The exception are locals that closures capture by reference
+/// and that are only used between where they are written and where their upvalues are closed in
+/// the same block, e.g. in the body of a loop, which are declared in a `do ... end` block around
+/// those statements so that every iteration gets its own. The numeric and generic for loop
+/// instructions are written out as the assignments and conditions the VM evaluates for them.
+pub fn lift_unstructured(
+    mut function: cfg::function::Function,
+    locals_to_ignore: &FxHashSet<ast::RcLocal>,
+) -> ast::Block {
+    let label = |node: NodeIndex| ast::Label(format!("l{}", node.index()));
+    let entry = function.entry().unwrap();
+    let nodes = std::iter::once(entry)
+        .chain(function.graph().node_indices().filter(|&n| n != entry))
+        .collect_vec();
+
+    let mut scopes = nodes
+        .iter()
+        .map(|&node| (node, capture_scopes(function.block_mut(node).unwrap())))
+        .collect::<FxHashMap<_, _>>();
+    // a local can only be declared in its scopes if it isn't used anywhere else
+    let mut scoped_locals = scopes
+        .values()
+        .flatten()
+        .flat_map(|(_, locals)| locals.iter().cloned())
+        .collect::<FxHashSet<_>>();
+    for &node in &nodes {
+        for (index, statement) in function.block(node).unwrap().iter().enumerate() {
+            for local in statement.values() {
+                if scoped_locals.contains(local)
+                    && !scopes[&node]
+                        .iter()
+                        .any(|(range, locals)| range.contains(&index) && locals.contains(local))
+                {
+                    scoped_locals.remove(local);
+                }
+            }
+        }
+    }
+    for node_scopes in scopes.values_mut() {
+        for (_, locals) in node_scopes.iter_mut() {
+            locals.retain(|local| scoped_locals.contains(local));
+        }
+        node_scopes.retain(|(_, locals)| !locals.is_empty());
+    }
+
+    let locals = nodes
+        .iter()
+        .flat_map(|&node| function.block(node).unwrap().iter())
+        .flat_map(|statement| statement.values_written())
+        .filter(|local| {
+            !locals_to_ignore.contains(local)
+                && !function.parameters.contains(local)
+                && !scoped_locals.contains(local)
+        })
+        .unique()
+        .cloned()
+        .collect_vec();
+
+    // the control variable of a generic for loop is only assigned by its init
+    let generic_for_controls = nodes
+        .iter()
+        .filter_map(|&node| {
+            let init = function
+                .block(node)
+                .unwrap()
+                .last()?
+                .as_generic_for_init()?;
+            let target = function.unconditional_edge(node)?.target();
+            Some((target, init.0.left[2].as_local()?.clone()))
+        })
+        .collect::<FxHashMap<_, _>>();
+
+    let mut res_block = ast::Block::default();
+    if !locals.is_empty() {
+        let mut declaration =
+            ast::Assign::new(locals.into_iter().map(|l| l.into()).collect(), vec![]);
+        declaration.prefix = true;
+        res_block.push(declaration.into());
+    }
+    // removing a block removes the edges into it, so they are all collected first
+    let targets = nodes
+        .iter()
+        .map(|&node| {
+            (
+                function
+                    .conditional_edges(node)
+                    .map(|(then_edge, else_edge)| (then_edge.target(), else_edge.target())),
+                function.unconditional_edge(node).map(|e| e.target()),
+            )
+        })
+        .collect_vec();
+    for (node, (conditional_targets, unconditional_target)) in nodes.into_iter().zip(targets) {
+        let mut statements = function.remove_block(node).unwrap().0;
+        // the scopes are moved into `do` blocks from the last one so that the indices stay valid
+        for (range, locals) in scopes.remove(&node).unwrap().into_iter().rev() {
+            let mut scope = statements
+                .splice(range.clone(), [])
+                .flat_map(lower_for_init)
+                .collect_vec();
+            match scope.first_mut() {
+                Some(ast::Statement::Assign(assign))
+                    if assign.left.len() == locals.len()
+                        && assign.left.iter().all(|lvalue| {
+                            lvalue.as_local().is_some_and(|l| locals.contains(l))
+                        }) =>
+                {
+                    assign.prefix = true;
+                }
+                _ => {
+                    let mut declaration =
+                        ast::Assign::new(locals.into_iter().map(|l| l.into()).collect(), vec![]);
+                    declaration.prefix = true;
+                    scope.insert(0, declaration.into());
+                }
+            }
+            statements.insert(range.start, ast::Do::new(ast::Block(scope)).into());
+        }
+        // the upvalues that need to be closed are in scopes now
+        let mut block = ast::Block(
+            statements
+                .into_iter()
+                .filter(|statement| !matches!(statement, ast::Statement::Close(_)))
+                .flat_map(lower_for_init)
+                .collect(),
+        );
+        if node != entry {
+            if let Some(ast::Statement::Goto(goto)) = res_block.last()
+                && goto.0 == label(node)
+            {
+                res_block.pop();
+            }
+            res_block.push(label(node).into());
+        }
+        if let Some((then_target, else_target)) = conditional_targets {
+            match block.last_mut() {
+                Some(ast::Statement::If(r#if)) => {
+                    r#if.then_block
+                        .lock()
+                        .push(ast::Goto::new(label(then_target)).into());
+                    r#if.else_block
+                        .lock()
+                        .push(ast::Goto::new(label(else_target)).into());
+                }
+                // the loop statements are conditional themselves
+                _ => {
+                    let (condition, mut then_block) =
+                        lower_for_next(&mut block, generic_for_controls.get(&node));
+                    then_block.push(ast::Goto::new(label(then_target)).into());
+                    block.push(
+                        ast::If::new(
+                            condition,
+                            then_block,
+                            ast::Block(vec![ast::Goto::new(label(else_target)).into()]),
+                        )
+                        .into(),
+                    );
+                }
+            }
+        } else if let Some(target) = unconditional_target {
+            block.push(ast::Goto::new(label(target)).into());
+        }
+        res_block.extend(block.0);
+    }
+    GraphStructurer::remove_last_return(res_block)
+}